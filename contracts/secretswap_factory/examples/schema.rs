@@ -5,7 +5,10 @@ use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 use cosmwasm_std::HandleResponse;
 
 use secretswap::PairInfo;
-use secretswap_factory::msg::{ConfigResponse, HandleMsg, InitMsg, PairsResponse, QueryMsg};
+use secretswap_factory::msg::{
+    AssetVolumeResponse, AssetVolumesResponse, ConfigResponse, HandleMsg, InitMsg, PairsResponse,
+    ProtocolStatsResponse, QueryMsg,
+};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -20,4 +23,7 @@ fn main() {
     export_schema(&schema_for!(PairInfo), &out_dir);
     export_schema(&schema_for!(PairsResponse), &out_dir);
     export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(ProtocolStatsResponse), &out_dir);
+    export_schema(&schema_for!(AssetVolumeResponse), &out_dir);
+    export_schema(&schema_for!(AssetVolumesResponse), &out_dir);
 }
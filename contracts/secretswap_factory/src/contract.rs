@@ -5,13 +5,21 @@ use cosmwasm_std::{
 use secret_toolkit::crypto::{sha_256, Prng};
 
 use secretswap::{
-    AssetInfo, Factory, Fee, InitHook, PairInfo, PairInfoRaw, PairInitMsg, PairSettings,
-    SwapDataEndpoint,
+    Asset, AssetInfo, AssetInfoRaw, Factory, Fee, InitHook, PairInfo, PairInfoRaw, PairInitMsg,
+    PairSettings, SwapDataEndpoint, TokenMetadata,
 };
 
-use crate::msg::{ConfigResponse, HandleMsg, InitMsg, PairsResponse, QueryMsg};
-use crate::querier::query_liquidity_token;
-use crate::state::{read_config, read_pair, read_pairs, store_config, store_pair, Config};
+use crate::msg::{
+    AssetVolumeResponse, AssetVolumesResponse, ConfigResponse, HandleMsg, InitMsg, PairsResponse,
+    ProtocolStatsResponse, QueryMsg,
+};
+use crate::querier::{query_liquidity_token, query_token_metadata};
+use crate::state::{
+    read_asset_volume, read_assets, read_config, read_pair, read_pair_metadata, read_pair_tracker,
+    read_pairs, read_pairs_with_asset, read_protocol_stats, read_token_metadata,
+    store_asset_volume, store_config, store_pair, store_protocol_stats, store_token_metadata,
+    Config,
+};
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
@@ -32,7 +40,7 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
                 commission_rate_nom: Uint128(3),
                 commission_rate_denom: Uint128(1000),
             },
-            swap_data_endpoint: None,
+            swap_data_endpoints: vec![],
         },
     };
 
@@ -67,7 +75,6 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             pair_code_hash,
             token_code_hash,
             swap_fee,
-            swap_data_endpoint,
         } => try_update_config(
             deps,
             env,
@@ -77,13 +84,25 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             pair_code_hash,
             token_code_hash,
             swap_fee,
-            swap_data_endpoint,
         ),
+        HandleMsg::AddSwapDataEndpoint { endpoint } => {
+            try_add_swap_data_endpoint(deps, env, endpoint)
+        }
+        HandleMsg::RemoveSwapDataEndpoint { address } => {
+            try_remove_swap_data_endpoint(deps, env, address)
+        }
         HandleMsg::CreatePair {
             asset_infos,
             init_hook,
         } => try_create_pair(deps, env, asset_infos, init_hook),
         HandleMsg::Register { asset_infos } => try_register(deps, env, asset_infos),
+        HandleMsg::RefreshTokenMetadata { asset_info } => {
+            try_refresh_token_metadata(deps, asset_info)
+        }
+        HandleMsg::ReportSwapVolume {
+            asset_infos,
+            offer_asset,
+        } => try_report_swap_volume(deps, env, asset_infos, offer_asset),
     }
 }
 
@@ -97,7 +116,6 @@ pub fn try_update_config<S: Storage, A: Api, Q: Querier>(
     pair_code_hash: Option<String>,
     token_code_hash: Option<String>,
     swap_fee: Option<Fee>,
-    swap_data_endpoint: Option<SwapDataEndpoint>,
 ) -> HandleResult {
     let mut config: Config = read_config(&deps.storage)?;
 
@@ -130,8 +148,6 @@ pub fn try_update_config<S: Storage, A: Api, Q: Querier>(
         config.pair_settings.swap_fee = swap_fee;
     }
 
-    config.pair_settings.swap_data_endpoint = swap_data_endpoint;
-
     store_config(&mut deps.storage, &config)?;
 
     Ok(HandleResponse {
@@ -141,6 +157,67 @@ pub fn try_update_config<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+// Only owner can execute it
+pub fn try_add_swap_data_endpoint<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    endpoint: SwapDataEndpoint,
+) -> HandleResult {
+    let mut config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let endpoints = &mut config.pair_settings.swap_data_endpoints;
+    endpoints.retain(|e| e.address != endpoint.address);
+    endpoints.push(endpoint.clone());
+
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "add_swap_data_endpoint"),
+            log("endpoint", endpoint.address),
+        ],
+        data: None,
+    })
+}
+
+// Only owner can execute it
+pub fn try_remove_swap_data_endpoint<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> HandleResult {
+    let mut config: Config = read_config(&deps.storage)?;
+
+    // permission check
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(StdError::unauthorized());
+    }
+
+    let endpoints = &mut config.pair_settings.swap_data_endpoints;
+    let len_before = endpoints.len();
+    endpoints.retain(|e| e.address != address);
+    if endpoints.len() == len_before {
+        return Err(StdError::generic_err("Swap data endpoint not found"));
+    }
+
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "remove_swap_data_endpoint"),
+            log("endpoint", address),
+        ],
+        data: None,
+    })
+}
+
 #[allow(clippy::too_many_arguments)]
 // Anyone can execute it to create swap pair
 pub fn try_create_pair<S: Storage, A: Api, Q: Querier>(
@@ -171,6 +248,12 @@ pub fn try_create_pair<S: Storage, A: Api, Q: Querier>(
         },
     )?;
 
+    // cache the token metadata of both assets, and use their symbols to name the LP token
+    let asset_symbols = [
+        load_asset_symbol(deps, &asset_infos[0], &raw_infos[0])?,
+        load_asset_symbol(deps, &asset_infos[1], &raw_infos[1])?,
+    ];
+
     let mut rng = Prng::new(&config.prng_seed, &env.block.time.to_be_bytes());
     let pair_seed = rng.rand_bytes();
 
@@ -196,6 +279,7 @@ pub fn try_create_pair<S: Storage, A: Api, Q: Querier>(
                 })?,
             }),
             prng_seed: Binary::from(&pair_seed),
+            asset_symbols: Some(asset_symbols),
         })?,
         callback_code_hash: config.pair_code_hash,
     })];
@@ -219,6 +303,61 @@ pub fn try_create_pair<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn load_asset_symbol<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    asset_info: &AssetInfo,
+    raw_info: &AssetInfoRaw,
+) -> StdResult<String> {
+    match asset_info {
+        AssetInfo::Token {
+            contract_addr,
+            token_code_hash,
+            ..
+        } => {
+            let metadata: TokenMetadata = match read_token_metadata(&deps.storage, raw_info)? {
+                Some(metadata) => metadata,
+                None => {
+                    let metadata = query_token_metadata(&deps, contract_addr, token_code_hash)?;
+                    store_token_metadata(&mut deps.storage, raw_info, &metadata)?;
+                    metadata
+                }
+            };
+            Ok(metadata.symbol)
+        }
+        AssetInfo::NativeToken { denom } => Ok(denom.clone()),
+    }
+}
+
+// Anyone can execute it to refresh the cached token metadata
+pub fn try_refresh_token_metadata<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    asset_info: AssetInfo,
+) -> HandleResult {
+    let metadata = match &asset_info {
+        AssetInfo::Token {
+            contract_addr,
+            token_code_hash,
+            ..
+        } => query_token_metadata(&deps, contract_addr, token_code_hash)?,
+        AssetInfo::NativeToken { .. } => {
+            return Err(StdError::generic_err(
+                "Token metadata is only available for token assets",
+            ))
+        }
+    };
+    store_token_metadata(&mut deps.storage, &asset_info.to_raw(&deps)?, &metadata)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "refresh_token_metadata"),
+            log("asset", asset_info.to_string()),
+            log("symbol", metadata.symbol),
+        ],
+        data: None,
+    })
+}
+
 /// create pair execute this message
 pub fn try_register<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
@@ -255,6 +394,71 @@ pub fn try_register<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+/// registered pairs execute this message after every swap. It never returns an error, because
+/// that would revert the swap, so reports that are not from the registered pair are only logged
+pub fn try_report_swap_volume<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    asset_infos: [AssetInfo; 2],
+    offer_asset: Asset,
+) -> HandleResult {
+    let mut logs = vec![
+        log("action", "report_swap_volume"),
+        log("offer_asset", offer_asset.to_string()),
+    ];
+    if let Err(err) = record_swap_volume(deps, &env, &asset_infos, &offer_asset) {
+        logs.push(log("ignored", err.to_string()));
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: None,
+    })
+}
+
+fn record_swap_volume<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    asset_infos: &[AssetInfo; 2],
+    offer_asset: &Asset,
+) -> StdResult<()> {
+    let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
+    let mut pair_info: PairInfoRaw = read_pair(&deps.storage, &raw_infos)?;
+
+    // permission check
+    if pair_info.contract_addr == CanonicalAddr::default()
+        || deps.api.canonical_address(&env.message.sender)? != pair_info.contract_addr
+    {
+        return Err(StdError::unauthorized());
+    }
+
+    let offer_info = offer_asset.info.to_raw(&deps)?;
+    let amount = offer_asset.amount;
+    if offer_info.equal(&pair_info.asset_infos[0]) {
+        pair_info.asset0_volume = saturating_add(pair_info.asset0_volume, amount);
+    } else if offer_info.equal(&pair_info.asset_infos[1]) {
+        pair_info.asset1_volume = saturating_add(pair_info.asset1_volume, amount);
+    } else {
+        return Err(StdError::generic_err("Wrong asset info is given"));
+    }
+
+    // everything is checked before anything is stored, so a report is never half recorded
+    let asset_volume = saturating_add(read_asset_volume(&deps.storage, &offer_info)?, amount);
+    let mut stats = read_protocol_stats(&deps.storage)?;
+    stats.swap_count = stats.swap_count.saturating_add(1);
+    stats.cumulative_volume = saturating_add(stats.cumulative_volume, amount);
+
+    store_pair(&mut deps.storage, &pair_info)?;
+    store_asset_volume(&mut deps.storage, &offer_info, asset_volume)?;
+    store_protocol_stats(&mut deps.storage, &stats)
+}
+
+// overflowing the analytics must not panic and revert the swap that is reported
+fn saturating_add(volume: Uint128, amount: Uint128) -> Uint128 {
+    Uint128(volume.u128().saturating_add(amount.u128()))
+}
+
 pub fn query<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     msg: QueryMsg,
@@ -265,7 +469,22 @@ pub fn query<S: Storage, A: Api, Q: Querier>(
         QueryMsg::Pairs { start_after, limit } => {
             to_binary(&query_pairs(deps, start_after, limit)?)
         }
+        QueryMsg::PairsWithAsset {
+            asset_info,
+            start_after,
+            limit,
+        } => to_binary(&query_pairs_with_asset(
+            deps,
+            asset_info,
+            start_after,
+            limit,
+        )?),
         QueryMsg::PairSettings {} => to_binary(&query_pair_settings(deps)?),
+        QueryMsg::ProtocolStats {} => to_binary(&query_protocol_stats(deps)?),
+        QueryMsg::AssetVolume { asset_info } => to_binary(&query_asset_volume(deps, asset_info)?),
+        QueryMsg::AssetVolumes { start_after, limit } => {
+            to_binary(&query_asset_volumes(deps, start_after, limit)?)
+        }
     }
 }
 
@@ -291,7 +510,9 @@ pub fn query_pair<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<PairInfo> {
     let raw_infos = [asset_infos[0].to_raw(&deps)?, asset_infos[1].to_raw(&deps)?];
     let pair_info: PairInfoRaw = read_pair(&deps.storage, &raw_infos)?;
-    pair_info.to_normal(&deps)
+    let mut pair = pair_info.to_normal(&deps)?;
+    pair.asset_metadata = read_pair_metadata(&deps.storage, &pair_info)?;
+    Ok(pair)
 }
 
 pub fn query_pairs<S: Storage, A: Api, Q: Querier>(
@@ -311,6 +532,23 @@ pub fn query_pairs<S: Storage, A: Api, Q: Querier>(
     Ok(resp)
 }
 
+pub fn query_pairs_with_asset<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    asset_info: AssetInfo,
+    start_after: Option<[AssetInfo; 2]>,
+    limit: Option<u32>,
+) -> StdResult<PairsResponse> {
+    let start_after = if let Some(start_after) = start_after {
+        Some([start_after[0].to_raw(&deps)?, start_after[1].to_raw(&deps)?])
+    } else {
+        None
+    };
+
+    let pairs = read_pairs_with_asset(&deps, &asset_info.to_raw(&deps)?, start_after, limit)?;
+
+    Ok(PairsResponse { pairs })
+}
+
 pub fn query_pair_settings<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
 ) -> StdResult<PairSettings> {
@@ -318,3 +556,51 @@ pub fn query_pair_settings<S: Storage, A: Api, Q: Querier>(
 
     Ok(config.pair_settings)
 }
+
+pub fn query_protocol_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<ProtocolStatsResponse> {
+    let stats = read_protocol_stats(&deps.storage)?;
+    let pair_count = read_pair_tracker(&deps.storage).unwrap_or_default().0.len() as u64;
+
+    Ok(ProtocolStatsResponse {
+        pair_count,
+        swap_count: stats.swap_count,
+        cumulative_volume: stats.cumulative_volume,
+    })
+}
+
+pub fn query_asset_volume<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    asset_info: AssetInfo,
+) -> StdResult<AssetVolumeResponse> {
+    let raw_info = asset_info.to_raw(&deps)?;
+    let volume = read_asset_volume(&deps.storage, &raw_info)?;
+
+    Ok(AssetVolumeResponse { asset_info, volume })
+}
+
+pub fn query_asset_volumes<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<AssetInfo>,
+    limit: Option<u32>,
+) -> StdResult<AssetVolumesResponse> {
+    let start_after = if let Some(start_after) = start_after {
+        Some(start_after.to_raw(&deps)?)
+    } else {
+        None
+    };
+
+    let assets = read_assets(&deps.storage, start_after, limit)?;
+    let volumes = assets
+        .iter()
+        .map(|raw_info| {
+            Ok(AssetVolumeResponse {
+                asset_info: raw_info.to_normal(&deps)?,
+                volume: read_asset_volume(&deps.storage, raw_info)?,
+            })
+        })
+        .collect::<StdResult<Vec<AssetVolumeResponse>>>()?;
+
+    Ok(AssetVolumesResponse { volumes })
+}
@@ -2,12 +2,14 @@ use std::collections::HashMap;
 
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    from_slice, to_binary, Api, Coin, Empty, Extern, HumanAddr, Querier, QuerierResult,
-    QueryRequest, SystemError, WasmQuery,
+    from_binary, from_slice, to_binary, Api, Coin, Empty, Extern, HumanAddr, Querier,
+    QuerierResult, QueryRequest, SystemError, WasmQuery,
 };
-use cosmwasm_storage::to_length_prefixed;
+use schemars::JsonSchema;
+use secret_toolkit::snip20::{TokenInfo, TokenInfoResponse};
+use serde::{Deserialize, Serialize};
 
-use secretswap::{AssetInfoRaw, PairInfo, PairInfoRaw};
+use secretswap::{PairInfo, TokenMetadata};
 
 /// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
 /// this uses our CustomQuerier.
@@ -32,7 +34,7 @@ pub fn mock_dependencies(
 pub struct WasmMockQuerier {
     base: MockQuerier<Empty>,
     terraswap_pair_querier: TerraswapPairQuerier,
-    canonical_length: usize,
+    token_querier: TokenQuerier,
 }
 
 #[derive(Clone, Default)]
@@ -56,6 +58,31 @@ pub(crate) fn pairs_to_map(pairs: &[(&HumanAddr, &PairInfo)]) -> HashMap<HumanAd
     pairs_map
 }
 
+#[derive(Clone, Default)]
+pub struct TokenQuerier {
+    metadata: HashMap<HumanAddr, TokenMetadata>,
+}
+
+impl TokenQuerier {
+    pub fn new(metadata: &[(&HumanAddr, &TokenMetadata)]) -> Self {
+        let mut metadata_map: HashMap<HumanAddr, TokenMetadata> = HashMap::new();
+        for (key, token) in metadata.iter() {
+            metadata_map.insert(HumanAddr::from(key), (*token).clone());
+        }
+        TokenQuerier {
+            metadata: metadata_map,
+        }
+    }
+}
+
+/// The queries the factory sends to pairs and tokens
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum MockQueryMsg {
+    Pair {},
+    TokenInfo {},
+}
+
 impl Querier for WasmMockQuerier {
     fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
         // MockQuerier doesn't support Custom, so we ignore it completely here
@@ -75,55 +102,45 @@ impl Querier for WasmMockQuerier {
 impl WasmMockQuerier {
     pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
         match &request {
-            QueryRequest::Wasm(WasmQuery::Raw { contract_addr, key }) => {
-                let key: &[u8] = key.as_slice();
-                let prefix_pair_info = to_length_prefixed(b"pair_info").to_vec();
-
-                if key.to_vec() == prefix_pair_info {
-                    let pair_info: PairInfo =
-                        match self.terraswap_pair_querier.pairs.get(&contract_addr) {
-                            Some(v) => v.clone(),
-                            None => {
-                                return Err(SystemError::InvalidRequest {
-                                    error: format!("PairInfo is not found for {}", contract_addr),
-                                    request: key.into(),
-                                });
-                            }
-                        };
-
-                    let api: MockApi = MockApi::new(self.canonical_length);
-                    Ok(to_binary(
-                        &to_binary(&PairInfoRaw {
-                            contract_addr: api.canonical_address(&pair_info.contract_addr).unwrap(),
-                            liquidity_token: api
-                                .canonical_address(&pair_info.liquidity_token)
-                                .unwrap(),
-                            asset_infos: [
-                                AssetInfoRaw::NativeToken {
-                                    denom: "uusd".to_string(),
-                                },
-                                AssetInfoRaw::NativeToken {
-                                    denom: "uusd".to_string(),
-                                },
-                            ],
-                        })
-                        .unwrap(),
-                    ))
-                } else {
-                    panic!("DO NOT ENTER HERE")
+            QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr, msg, ..
+            }) => match from_binary(&msg).unwrap() {
+                MockQueryMsg::Pair {} => {
+                    match self.terraswap_pair_querier.pairs.get(&contract_addr) {
+                        Some(v) => Ok(to_binary(&v)),
+                        None => Err(SystemError::InvalidRequest {
+                            error: format!("PairInfo is not found for {}", contract_addr),
+                            request: msg.as_slice().into(),
+                        }),
+                    }
                 }
-            }
+                MockQueryMsg::TokenInfo {} => match self.token_querier.metadata.get(&contract_addr)
+                {
+                    Some(v) => Ok(to_binary(&TokenInfoResponse {
+                        token_info: TokenInfo {
+                            name: v.name.clone(),
+                            symbol: v.symbol.clone(),
+                            decimals: v.decimals,
+                            total_supply: None,
+                        },
+                    })),
+                    None => Err(SystemError::InvalidRequest {
+                        error: format!("TokenInfo is not found for {}", contract_addr),
+                        request: msg.as_slice().into(),
+                    }),
+                },
+            },
             _ => self.base.handle_query(request),
         }
     }
 }
 
 impl WasmMockQuerier {
-    pub fn new<A: Api>(base: MockQuerier<Empty>, canonical_length: usize, _api: A) -> Self {
+    pub fn new<A: Api>(base: MockQuerier<Empty>, _canonical_length: usize, _api: A) -> Self {
         WasmMockQuerier {
             base,
             terraswap_pair_querier: TerraswapPairQuerier::default(),
-            canonical_length,
+            token_querier: TokenQuerier::default(),
         }
     }
 
@@ -132,9 +149,8 @@ impl WasmMockQuerier {
         self.terraswap_pair_querier = TerraswapPairQuerier::new(pairs);
     }
 
-    // pub fn with_balance(&mut self, balances: &[(&HumanAddr, &[Coin])]) {
-    //     for (addr, balance) in balances {
-    //         self.base.update_balance(addr, balance.to_vec());
-    //     }
-    // }
+    // configure the token_info of snip20 tokens
+    pub fn with_token_metadata(&mut self, metadata: &[(&HumanAddr, &TokenMetadata)]) {
+        self.token_querier = TokenQuerier::new(metadata);
+    }
 }
@@ -1,8 +1,8 @@
-use cosmwasm_std::{Binary, HumanAddr};
+use cosmwasm_std::{Binary, HumanAddr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use secretswap::{AssetInfo, Fee, InitHook, PairInfo, PairSettings, SwapDataEndpoint};
+use secretswap::{Asset, AssetInfo, Fee, InitHook, PairInfo, PairSettings, SwapDataEndpoint};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
@@ -26,7 +26,13 @@ pub enum HandleMsg {
         pair_code_hash: Option<String>,
         token_code_hash: Option<String>,
         swap_fee: Option<Fee>,
-        swap_data_endpoint: Option<SwapDataEndpoint>,
+    },
+    /// AddSwapDataEndpoint adds an endpoint, or replaces the one with the same address
+    AddSwapDataEndpoint {
+        endpoint: SwapDataEndpoint,
+    },
+    RemoveSwapDataEndpoint {
+        address: HumanAddr,
     },
     /// CreatePair instantiates pair contract
     CreatePair {
@@ -36,7 +42,19 @@ pub enum HandleMsg {
         init_hook: Option<InitHook>,
     },
     /// Register is invoked from created pair contract after initialzation
-    Register { asset_infos: [AssetInfo; 2] },
+    Register {
+        asset_infos: [AssetInfo; 2],
+    },
+    /// RefreshTokenMetadata queries and caches the token_info of a SNIP-20 asset
+    RefreshTokenMetadata {
+        asset_info: AssetInfo,
+    },
+    /// ReportSwapVolume is invoked by a registered pair contract after every swap.
+    /// It never fails, reports from anything but the registered pair are ignored
+    ReportSwapVolume {
+        asset_infos: [AssetInfo; 2],
+        offer_asset: Asset,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -51,6 +69,22 @@ pub enum QueryMsg {
         start_after: Option<[AssetInfo; 2]>,
         limit: Option<u32>,
     },
+    /// PairsWithAsset pages through the pairs that have `asset_info` as one of their assets
+    PairsWithAsset {
+        asset_info: AssetInfo,
+        start_after: Option<[AssetInfo; 2]>,
+        limit: Option<u32>,
+    },
+    /// ProtocolStats returns aggregate analytics across all registered pairs
+    ProtocolStats {},
+    /// AssetVolume returns the amount of an asset the registered pairs reported as offered
+    AssetVolume {
+        asset_info: AssetInfo,
+    },
+    AssetVolumes {
+        start_after: Option<AssetInfo>,
+        limit: Option<u32>,
+    },
 }
 
 // We define a custom struct for each query response
@@ -69,3 +103,23 @@ pub struct ConfigResponse {
 pub struct PairsResponse {
     pub pairs: Vec<PairInfo>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolStatsResponse {
+    pub pair_count: u64,
+    pub swap_count: u64,
+    /// Sum of all offered amounts, in each asset's raw units
+    pub cumulative_volume: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetVolumeResponse {
+    pub asset_info: AssetInfo,
+    /// Total amount of this asset offered to all pairs
+    pub volume: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetVolumesResponse {
+    pub volumes: Vec<AssetVolumeResponse>,
+}
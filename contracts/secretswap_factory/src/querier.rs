@@ -2,9 +2,10 @@ use cosmwasm_std::{
     to_binary, Api, Extern, HumanAddr, Querier, QueryRequest, StdResult, Storage, WasmQuery,
 };
 use schemars::JsonSchema;
+use secret_toolkit::snip20::token_info_query;
 use serde::{Deserialize, Serialize};
 
-use secretswap::PairInfo;
+use secretswap::{PairInfo, TokenMetadata};
 
 // copied from secretswap_pair.. todo: move it to secretswap common package
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -27,3 +28,18 @@ pub fn query_liquidity_token<S: Storage, A: Api, Q: Querier>(
 
     Ok(pair_info.liquidity_token)
 }
+
+pub fn query_token_metadata<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    contract_addr: &HumanAddr,
+    code_hash: &String,
+) -> StdResult<TokenMetadata> {
+    let token_info =
+        token_info_query(&deps.querier, 256, code_hash.clone(), contract_addr.clone())?;
+
+    Ok(TokenMetadata {
+        name: token_info.name,
+        symbol: token_info.symbol,
+        decimals: token_info.decimals,
+    })
+}
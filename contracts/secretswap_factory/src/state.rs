@@ -1,11 +1,18 @@
-use cosmwasm_std::{Api, CanonicalAddr, Extern, Querier, StdError, StdResult, Storage};
+use cosmwasm_std::{Api, CanonicalAddr, Extern, Querier, StdError, StdResult, Storage, Uint128};
 use cosmwasm_storage::{Bucket, ReadonlyBucket, ReadonlySingleton, Singleton};
 use schemars::JsonSchema;
-use secretswap::{AssetInfoRaw, PairInfo, PairInfoRaw, PairSettings};
+use secretswap::{
+    AssetInfoRaw, PairInfo, PairInfoRaw, PairSettings, SwapDataEndpoint, TokenMetadata,
+};
 use serde::{Deserialize, Serialize};
 static KEY_CONFIG: &[u8] = b"config";
 static PAIR_TRACKER: &[u8] = b"pair_tracker";
 static PREFIX_PAIR_INFO: &[u8] = b"pair_info";
+static KEY_PROTOCOL_STATS: &[u8] = b"protocol_stats";
+static ASSET_TRACKER: &[u8] = b"asset_tracker";
+static PREFIX_ASSET_PAIRS: &[u8] = b"asset_pairs";
+static PREFIX_ASSET_VOLUME: &[u8] = b"asset_volume";
+static PREFIX_TOKEN_METADATA: &[u8] = b"token_metadata";
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner: CanonicalAddr,
@@ -27,8 +34,28 @@ pub fn read_pair_tracker<S: Storage>(storage: &S) -> StdResult<PairTracker> {
 pub fn store_config<S: Storage>(storage: &mut S, data: &Config) -> StdResult<()> {
     Singleton::new(storage, KEY_CONFIG).save(data)
 }
+// pair settings stored before there could be several swap data endpoints had a single one
+#[derive(Serialize, Deserialize)]
+struct LegacyConfig {
+    pair_settings: LegacyPairSettings,
+}
+#[derive(Serialize, Deserialize)]
+struct LegacyPairSettings {
+    swap_data_endpoint: Option<SwapDataEndpoint>,
+}
 pub fn read_config<S: Storage>(storage: &S) -> StdResult<Config> {
-    ReadonlySingleton::new(storage, KEY_CONFIG).load()
+    let mut config: Config = ReadonlySingleton::new(storage, KEY_CONFIG).load()?;
+
+    // carry the legacy endpoint over, the next store_config drops the legacy field
+    let legacy: LegacyConfig = ReadonlySingleton::new(storage, KEY_CONFIG).load()?;
+    if let Some(endpoint) = legacy.pair_settings.swap_data_endpoint {
+        let endpoints = &mut config.pair_settings.swap_data_endpoints;
+        if !endpoints.iter().any(|e| e.address == endpoint.address) {
+            endpoints.insert(0, endpoint);
+        }
+    }
+
+    Ok(config)
 }
 pub fn store_pair<S: Storage>(storage: &mut S, data: &PairInfoRaw) -> StdResult<()> {
     let mut asset_infos = data.asset_infos.clone().to_vec();
@@ -42,7 +69,9 @@ pub fn store_pair<S: Storage>(storage: &mut S, data: &PairInfoRaw) -> StdResult<
     if !tracker.0.iter().any(|i| *i == key_as_vec) {
         // new pair
         tracker.0.push(key_as_vec);
-        store_pair_tracker(storage, &tracker)
+        store_pair_tracker(storage, &tracker)?;
+        track_assets(storage, &data.asset_infos)?;
+        index_pair(storage, &data.asset_infos, key)
     } else {
         // pair already stored in pair_tracker
         Ok(())
@@ -79,7 +108,15 @@ pub fn read_pairs<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<Vec<PairInfo>> {
     //return pair_bucket.load()
     let tracker = read_pair_tracker(&deps.storage)?;
-    let mut iter = tracker.0.iter();
+    read_pairs_page(deps, &tracker.0, start_after, limit)
+}
+fn read_pairs_page<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    keys: &[Vec<u8>],
+    start_after: Option<[AssetInfoRaw; 2]>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PairInfo>> {
+    let mut iter = keys.iter();
     let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
     if let Some(start) = calc_range_start(start_after) {
         iter.position(|key| key == &start);
@@ -90,7 +127,10 @@ pub fn read_pairs<S: Storage, A: Api, Q: Querier>(
         if info.is_none() {
             break;
         }
-        pairs.push(read_pair_by_key(&deps.storage, info.unwrap())?.to_normal(&deps)?)
+        let pair_info = read_pair_by_key(&deps.storage, info.unwrap())?;
+        let mut pair = pair_info.to_normal(&deps)?;
+        pair.asset_metadata = read_pair_metadata(&deps.storage, &pair_info)?;
+        pairs.push(pair)
     }
     Ok(pairs)
     //Ok(vec![])
@@ -104,16 +144,138 @@ pub fn read_pairs<S: Storage, A: Api, Q: Querier>(
     //     })
     //     .collect()
 }
-// this will set the first key after the provided key, by appending a 1 byte
+// the keys of the pairs of every asset, in creation order
+fn index_pair<S: Storage>(
+    storage: &mut S,
+    asset_infos: &[AssetInfoRaw; 2],
+    key: &[u8],
+) -> StdResult<()> {
+    for asset_info in asset_infos.iter() {
+        let mut keys = read_asset_pair_keys(storage, asset_info)?;
+        if keys.iter().any(|indexed| indexed.as_slice() == key) {
+            continue;
+        }
+        keys.push(key.to_vec());
+        let mut index_bucket: Bucket<S, Vec<Vec<u8>>> = Bucket::new(PREFIX_ASSET_PAIRS, storage);
+        index_bucket.save(asset_info.as_bytes(), &keys)?;
+    }
+    Ok(())
+}
+fn read_asset_pair_keys<S: Storage>(
+    storage: &S,
+    asset_info: &AssetInfoRaw,
+) -> StdResult<Vec<Vec<u8>>> {
+    let index_bucket: ReadonlyBucket<S, Vec<Vec<u8>>> =
+        ReadonlyBucket::new(PREFIX_ASSET_PAIRS, storage);
+    index_bucket
+        .may_load(asset_info.as_bytes())
+        .map(Option::unwrap_or_default)
+}
+/// Pages through the pairs that have `asset_info` as one of their assets
+pub fn read_pairs_with_asset<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    asset_info: &AssetInfoRaw,
+    start_after: Option<[AssetInfoRaw; 2]>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PairInfo>> {
+    let keys = read_asset_pair_keys(&deps.storage, asset_info)?;
+    read_pairs_page(deps, &keys, start_after, limit)
+}
+// this will set the key of the provided pair, pages continue after it in the pair tracker
 fn calc_range_start(start_after: Option<[AssetInfoRaw; 2]>) -> Option<Vec<u8>> {
     start_after.map(|asset_infos| {
         let mut asset_infos = asset_infos.to_vec();
         asset_infos.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
-        let mut v = [asset_infos[0].as_bytes(), asset_infos[1].as_bytes()]
-            .concat()
-            .as_slice()
-            .to_vec();
-        v.push(1);
-        v
+        [asset_infos[0].as_bytes(), asset_infos[1].as_bytes()].concat()
     })
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct ProtocolStats {
+    pub swap_count: u64,
+    pub cumulative_volume: Uint128,
+}
+pub fn store_protocol_stats<S: Storage>(storage: &mut S, data: &ProtocolStats) -> StdResult<()> {
+    Singleton::new(storage, KEY_PROTOCOL_STATS).save(data)
+}
+pub fn read_protocol_stats<S: Storage>(storage: &S) -> StdResult<ProtocolStats> {
+    ReadonlySingleton::new(storage, KEY_PROTOCOL_STATS)
+        .may_load()
+        .map(Option::unwrap_or_default)
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct AssetTracker(pub Vec<AssetInfoRaw>);
+pub fn store_asset_tracker<S: Storage>(storage: &mut S, data: &AssetTracker) -> StdResult<()> {
+    Singleton::new(storage, ASSET_TRACKER).save(data)
+}
+pub fn read_asset_tracker<S: Storage>(storage: &S) -> StdResult<AssetTracker> {
+    ReadonlySingleton::new(storage, ASSET_TRACKER)
+        .may_load()
+        .map(Option::unwrap_or_default)
+}
+// every asset of a pair is tracked from the moment the pair is created
+fn track_assets<S: Storage>(storage: &mut S, asset_infos: &[AssetInfoRaw; 2]) -> StdResult<()> {
+    let mut tracker = read_asset_tracker(storage)?;
+    for asset_info in asset_infos.iter() {
+        if !tracker.0.iter().any(|tracked| tracked.equal(asset_info)) {
+            tracker.0.push(asset_info.clone());
+        }
+    }
+    store_asset_tracker(storage, &tracker)
+}
+pub fn read_assets<S: Storage>(
+    storage: &S,
+    start_after: Option<AssetInfoRaw>,
+    limit: Option<u32>,
+) -> StdResult<Vec<AssetInfoRaw>> {
+    let tracker = read_asset_tracker(storage)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some(start_after) => match tracker.0.iter().position(|key| key.equal(&start_after)) {
+            Some(position) => position + 1,
+            None => return Err(StdError::generic_err("start_after is not a tracked asset")),
+        },
+        None => 0,
+    };
+    Ok(tracker.0.into_iter().skip(start).take(limit).collect())
+}
+pub fn store_asset_volume<S: Storage>(
+    storage: &mut S,
+    asset_info: &AssetInfoRaw,
+    volume: Uint128,
+) -> StdResult<()> {
+    let mut volume_bucket: Bucket<S, Uint128> = Bucket::new(PREFIX_ASSET_VOLUME, storage);
+    volume_bucket.save(asset_info.as_bytes(), &volume)
+}
+pub fn read_asset_volume<S: Storage>(storage: &S, asset_info: &AssetInfoRaw) -> StdResult<Uint128> {
+    let volume_bucket: ReadonlyBucket<S, Uint128> =
+        ReadonlyBucket::new(PREFIX_ASSET_VOLUME, storage);
+    volume_bucket
+        .may_load(asset_info.as_bytes())
+        .map(Option::unwrap_or_default)
+}
+pub fn store_token_metadata<S: Storage>(
+    storage: &mut S,
+    asset_info: &AssetInfoRaw,
+    data: &TokenMetadata,
+) -> StdResult<()> {
+    let mut metadata_bucket: Bucket<S, TokenMetadata> = Bucket::new(PREFIX_TOKEN_METADATA, storage);
+    metadata_bucket.save(asset_info.as_bytes(), data)
+}
+pub fn read_token_metadata<S: Storage>(
+    storage: &S,
+    asset_info: &AssetInfoRaw,
+) -> StdResult<Option<TokenMetadata>> {
+    let metadata_bucket: ReadonlyBucket<S, TokenMetadata> =
+        ReadonlyBucket::new(PREFIX_TOKEN_METADATA, storage);
+    metadata_bucket.may_load(asset_info.as_bytes())
+}
+pub fn read_pair_metadata<S: Storage>(
+    storage: &S,
+    pair_info: &PairInfoRaw,
+) -> StdResult<[Option<TokenMetadata>; 2]> {
+    Ok([
+        read_token_metadata(storage, &pair_info.asset_infos[0])?,
+        read_token_metadata(storage, &pair_info.asset_infos[1])?,
+    ])
+}
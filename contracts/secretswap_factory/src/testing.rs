@@ -1,29 +1,99 @@
-use cosmwasm_std::testing::{mock_env, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::testing::{mock_env, MockApi, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    from_binary, log, to_binary, CanonicalAddr, CosmosMsg, HumanAddr, StdError, WasmMsg,
+    from_binary, log, to_binary, Binary, CanonicalAddr, CosmosMsg, Extern, HumanAddr, LogAttribute,
+    ReadonlyStorage, StdError, Storage, Uint128, WasmMsg,
 };
+use cosmwasm_storage::to_length_prefixed;
 
-use secretswap::{AssetInfo, InitHook, PairInfo, PairInitMsg};
+use secretswap::{
+    Asset, AssetInfo, Factory, InitHook, PairInfo, PairInitMsg, PairSettings, SwapDataEndpoint,
+    TokenMetadata,
+};
 
 use crate::contract::{handle, init, query};
-use crate::mock_querier::mock_dependencies;
-use crate::msg::{ConfigResponse, HandleMsg, InitMsg, PairsResponse, QueryMsg};
+use crate::mock_querier::{mock_dependencies, WasmMockQuerier};
+use crate::msg::{
+    AssetVolumeResponse, AssetVolumesResponse, ConfigResponse, HandleMsg, InitMsg, PairsResponse,
+    ProtocolStatsResponse, QueryMsg,
+};
 use crate::state::read_pair;
 
-#[test]
-fn proper_initialization() {
-    let mut deps = mock_dependencies(20, &[]);
-
-    let msg = InitMsg {
+fn init_msg() -> InitMsg {
+    InitMsg {
         pair_code_id: 321u64,
         token_code_id: 123u64,
         init_hook: None,
+        token_code_hash: "token_code_hash".to_string(),
+        pair_code_hash: "pair_code_hash".to_string(),
+        prng_seed: Binary::from(b"seed".to_vec()),
+    }
+}
+
+fn native(denom: &str) -> AssetInfo {
+    AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    }
+}
+
+fn token(contract_addr: &str) -> AssetInfo {
+    AssetInfo::Token {
+        contract_addr: HumanAddr::from(contract_addr),
+        token_code_hash: "token_code_hash".to_string(),
+        viewing_key: "".to_string(),
+    }
+}
+
+fn pair_info(
+    asset_infos: &[AssetInfo; 2],
+    contract_addr: &str,
+    liquidity_token: &str,
+    volumes: [u128; 2],
+) -> PairInfo {
+    PairInfo {
+        asset_infos: asset_infos.clone(),
+        contract_addr: HumanAddr::from(contract_addr),
+        liquidity_token: HumanAddr::from(liquidity_token),
+        token_code_hash: "pair_code_hash".to_string(),
+        asset0_volume: Uint128(volumes[0]),
+        asset1_volume: Uint128(volumes[1]),
+        factory: Factory {
+            address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+            code_hash: "".to_string(),
+        },
+        asset_metadata: Default::default(),
+    }
+}
+
+fn create_pair(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    asset_infos: &[AssetInfo; 2],
+) {
+    let msg = HandleMsg::CreatePair {
+        asset_infos: asset_infos.clone(),
+        init_hook: None,
+    };
+    handle(deps, mock_env("addr0000", &[]), msg).unwrap();
+}
+
+fn register_pair(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    asset_infos: &[AssetInfo; 2],
+    pair_contract: &str,
+) {
+    let msg = HandleMsg::Register {
+        asset_infos: asset_infos.clone(),
     };
+    handle(deps, mock_env(pair_contract, &[]), msg).unwrap();
+}
+
+#[test]
+fn proper_initialization() {
+    let mut deps = mock_dependencies(20, &[]);
 
     let env = mock_env("addr0000", &[]);
 
     // we can just call .unwrap() to assert this was a success
-    let _res = init(&mut deps, env, msg).unwrap();
+    let _res = init(&mut deps, env, init_msg()).unwrap();
 
     let query_res = query(&deps, QueryMsg::Config {}).unwrap();
     let config_res: ConfigResponse = from_binary(&query_res).unwrap();
@@ -36,16 +106,10 @@ fn proper_initialization() {
 fn update_config() {
     let mut deps = mock_dependencies(20, &[]);
 
-    let msg = InitMsg {
-        pair_code_id: 321u64,
-        token_code_id: 123u64,
-        init_hook: None,
-    };
-
     let env = mock_env("addr0000", &[]);
 
     // we can just call .unwrap() to assert this was a success
-    let _res = init(&mut deps, env, msg).unwrap();
+    let _res = init(&mut deps, env, init_msg()).unwrap();
 
     // update owner
     let env = mock_env("addr0000", &[]);
@@ -53,6 +117,9 @@ fn update_config() {
         owner: Some(HumanAddr("addr0001".to_string())),
         pair_code_id: None,
         token_code_id: None,
+        pair_code_hash: None,
+        token_code_hash: None,
+        swap_fee: None,
     };
 
     let res = handle(&mut deps, env, msg).unwrap();
@@ -71,6 +138,9 @@ fn update_config() {
         owner: None,
         pair_code_id: Some(100u64),
         token_code_id: Some(200u64),
+        pair_code_hash: None,
+        token_code_hash: None,
+        swap_fee: None,
     };
 
     let res = handle(&mut deps, env, msg).unwrap();
@@ -89,6 +159,9 @@ fn update_config() {
         owner: None,
         pair_code_id: None,
         token_code_id: None,
+        pair_code_hash: None,
+        token_code_hash: None,
+        swap_fee: None,
     };
 
     let res = handle(&mut deps, env, msg);
@@ -99,28 +172,22 @@ fn update_config() {
 }
 
 #[test]
-fn create_pair() {
+fn create_pair_instantiates_pair() {
     let mut deps = mock_dependencies(20, &[]);
 
-    let msg = InitMsg {
-        pair_code_id: 321u64,
-        token_code_id: 123u64,
-        init_hook: None,
-    };
-
     let env = mock_env("addr0000", &[]);
-
-    // we can just call .unwrap() to assert this was a success
-    let _res = init(&mut deps, env, msg).unwrap();
-
-    let asset_infos = [
-        AssetInfo::Token {
-            contract_addr: HumanAddr::from("asset0000"),
-        },
-        AssetInfo::Token {
-            contract_addr: HumanAddr::from("asset0001"),
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    deps.querier.with_token_metadata(&[(
+        &HumanAddr::from("asset0000"),
+        &TokenMetadata {
+            name: "Secret Token".to_string(),
+            symbol: "STKN".to_string(),
+            decimals: 6,
         },
-    ];
+    )]);
+
+    let asset_infos = [token("asset0000"), native("uscrt")];
 
     let msg = HandleMsg::CreatePair {
         asset_infos: asset_infos.clone(),
@@ -131,31 +198,39 @@ fn create_pair() {
     let res = handle(&mut deps, env, msg).unwrap();
     assert_eq!(
         res.log,
-        vec![
-            log("action", "create_pair"),
-            log("pair", "asset0000-asset0001")
-        ]
-    );
-    assert_eq!(
-        res.messages,
-        vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
-            msg: to_binary(&PairInitMsg {
-                asset_infos: asset_infos.clone(),
-                token_code_id: 123u64,
-                init_hook: Some(InitHook {
-                    contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
-                    msg: to_binary(&HandleMsg::Register {
-                        asset_infos: asset_infos.clone()
-                    })
-                    .unwrap(),
-                }),
-            })
-            .unwrap(),
-            code_id: 321u64,
-            send: vec![],
-            label: None,
-        })]
+        vec![log("action", "create_pair"), log("pair", "asset0000-uscrt")]
     );
+    assert_eq!(res.messages.len(), 1);
+    match &res.messages[0] {
+        CosmosMsg::Wasm(WasmMsg::Instantiate {
+            code_id,
+            msg,
+            callback_code_hash,
+            ..
+        }) => {
+            assert_eq!(*code_id, 321u64);
+            assert_eq!(callback_code_hash, "pair_code_hash");
+
+            let init_msg: PairInitMsg = from_binary(msg).unwrap();
+            assert_eq!(init_msg.asset_infos, asset_infos);
+            assert_eq!(init_msg.token_code_id, 123u64);
+            assert_eq!(
+                init_msg.asset_symbols,
+                Some(["STKN".to_string(), "uscrt".to_string()])
+            );
+
+            let init_hook: InitHook = init_msg.init_hook.unwrap();
+            assert_eq!(init_hook.contract_addr, HumanAddr::from(MOCK_CONTRACT_ADDR));
+            assert_eq!(
+                init_hook.msg,
+                to_binary(&HandleMsg::Register {
+                    asset_infos: asset_infos.clone()
+                })
+                .unwrap()
+            );
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
 
     let raw_infos = [
         asset_infos[0].to_raw(&deps).unwrap(),
@@ -164,61 +239,43 @@ fn create_pair() {
     let pair_info = read_pair(&deps.storage, &raw_infos).unwrap();
 
     assert_eq!(pair_info.contract_addr, CanonicalAddr::default(),);
+
+    // the same pair can not be created twice
+    let msg = HandleMsg::CreatePair {
+        asset_infos: [asset_infos[1].clone(), asset_infos[0].clone()],
+        init_hook: None,
+    };
+    let res = handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap_err();
+    match res {
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "Pair already exists"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
 }
 
 #[test]
 fn register() {
     let mut deps = mock_dependencies(20, &[]);
 
-    let msg = InitMsg {
-        pair_code_id: 321u64,
-        token_code_id: 123u64,
-        init_hook: None,
-    };
-
     let env = mock_env("addr0000", &[]);
-    let _res = init(&mut deps, env, msg).unwrap();
+    let _res = init(&mut deps, env, init_msg()).unwrap();
 
-    let asset_infos = [
-        AssetInfo::Token {
-            contract_addr: HumanAddr::from("asset0000"),
-        },
-        AssetInfo::Token {
-            contract_addr: HumanAddr::from("asset0001"),
-        },
-    ];
-
-    let msg = HandleMsg::CreatePair {
-        asset_infos: asset_infos.clone(),
-        init_hook: None,
-    };
-
-    let env = mock_env("addr0000", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+    let asset_infos = [native("uusd"), native("uscrt")];
+    let asset_infos_2 = [native("uusd"), native("ueur")];
 
     // register terraswap pair querier
-    deps.querier.with_terraswap_pairs(&[(
-        &HumanAddr::from("pair0000"),
-        &PairInfo {
-            asset_infos: [
-                AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
-                AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
-            ],
-            contract_addr: HumanAddr::from("pair0000"),
-            liquidity_token: HumanAddr::from("liquidity0000"),
-        },
-    )]);
-
-    let msg = HandleMsg::Register {
-        asset_infos: asset_infos.clone(),
-    };
-
-    let env = mock_env("pair0000", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+    deps.querier.with_terraswap_pairs(&[
+        (
+            &HumanAddr::from("pair0000"),
+            &pair_info(&asset_infos, "pair0000", "liquidity0000", [0, 0]),
+        ),
+        (
+            &HumanAddr::from("pair0001"),
+            &pair_info(&asset_infos_2, "pair0001", "liquidity0001", [0, 0]),
+        ),
+    ]);
+
+    create_pair(&mut deps, &asset_infos);
+    register_pair(&mut deps, &asset_infos, "pair0000");
 
     let query_res = query(
         &deps,
@@ -229,14 +286,9 @@ fn register() {
     .unwrap();
 
     let pair_res: PairInfo = from_binary(&query_res).unwrap();
-    assert_eq!(
-        pair_res,
-        PairInfo {
-            liquidity_token: HumanAddr::from("liquidity0000"),
-            contract_addr: HumanAddr::from("pair0000"),
-            asset_infos: asset_infos.clone(),
-        }
-    );
+    assert_eq!(pair_res.contract_addr, HumanAddr::from("pair0000"));
+    assert_eq!(pair_res.liquidity_token, HumanAddr::from("liquidity0000"));
+    assert_eq!(pair_res.asset_infos, asset_infos);
 
     let msg = HandleMsg::Register {
         asset_infos: [asset_infos[1].clone(), asset_infos[0].clone()],
@@ -250,99 +302,717 @@ fn register() {
     }
 
     // Store one more item to test query pairs
-    let asset_infos_2 = [
-        AssetInfo::Token {
-            contract_addr: HumanAddr::from("asset0000"),
+    create_pair(&mut deps, &asset_infos_2);
+    register_pair(&mut deps, &asset_infos_2, "pair0001");
+
+    let query_msg = QueryMsg::Pairs {
+        start_after: None,
+        limit: None,
+    };
+
+    let res = query(&deps, query_msg).unwrap();
+    let pairs_res: PairsResponse = from_binary(&res).unwrap();
+    let pair_addrs: Vec<HumanAddr> = pairs_res
+        .pairs
+        .iter()
+        .map(|pair| pair.contract_addr.clone())
+        .collect();
+    assert_eq!(
+        pair_addrs,
+        vec![HumanAddr::from("pair0000"), HumanAddr::from("pair0001")]
+    );
+
+    let query_msg = QueryMsg::Pairs {
+        start_after: None,
+        limit: Some(1),
+    };
+
+    let res = query(&deps, query_msg).unwrap();
+    let pairs_res: PairsResponse = from_binary(&res).unwrap();
+    assert_eq!(pairs_res.pairs.len(), 1);
+    assert_eq!(
+        pairs_res.pairs[0].contract_addr,
+        HumanAddr::from("pair0000")
+    );
+}
+
+fn report_swap_volume(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    pair_contract: &str,
+    asset_infos: &[AssetInfo; 2],
+    offer_asset: Asset,
+) -> Vec<LogAttribute> {
+    let msg = HandleMsg::ReportSwapVolume {
+        asset_infos: asset_infos.clone(),
+        offer_asset,
+    };
+    handle(deps, mock_env(pair_contract, &[]), msg).unwrap().log
+}
+
+fn asset(info: &AssetInfo, amount: u128) -> Asset {
+    Asset {
+        info: info.clone(),
+        amount: Uint128(amount),
+    }
+}
+
+#[test]
+fn protocol_stats_and_asset_volumes() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    let usd_scrt = [native("uusd"), native("uscrt")];
+    let usd_eur = [native("uusd"), native("ueur")];
+    let scrt_eur = [native("uscrt"), native("ueur")];
+
+    deps.querier.with_terraswap_pairs(&[
+        (
+            &HumanAddr::from("pair0000"),
+            &pair_info(&usd_scrt, "pair0000", "liquidity0000", [0, 0]),
+        ),
+        (
+            &HumanAddr::from("pair0001"),
+            &pair_info(&usd_eur, "pair0001", "liquidity0001", [0, 0]),
+        ),
+    ]);
+
+    create_pair(&mut deps, &usd_scrt);
+    register_pair(&mut deps, &usd_scrt, "pair0000");
+    create_pair(&mut deps, &usd_eur);
+    register_pair(&mut deps, &usd_eur, "pair0001");
+    // never registered, so it never reports
+    create_pair(&mut deps, &scrt_eur);
+
+    // the pairs report the volume offered to them after every swap
+    report_swap_volume(
+        &mut deps,
+        "pair0000",
+        &usd_scrt,
+        asset(&native("uusd"), 100),
+    );
+    report_swap_volume(
+        &mut deps,
+        "pair0000",
+        &usd_scrt,
+        asset(&native("uscrt"), 200),
+    );
+    report_swap_volume(&mut deps, "pair0001", &usd_eur, asset(&native("uusd"), 50));
+    report_swap_volume(&mut deps, "pair0001", &usd_eur, asset(&native("ueur"), 70));
+
+    let res = query(&deps, QueryMsg::ProtocolStats {}).unwrap();
+    let stats: ProtocolStatsResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        stats,
+        ProtocolStatsResponse {
+            pair_count: 3,
+            swap_count: 4,
+            cumulative_volume: Uint128(420),
+        }
+    );
+
+    // the factory keeps the volumes of the pair as well
+    let res = query(
+        &deps,
+        QueryMsg::Pair {
+            asset_infos: usd_scrt.clone(),
         },
-        AssetInfo::Token {
-            contract_addr: HumanAddr::from("asset0002"),
+    )
+    .unwrap();
+    let pair_res: PairInfo = from_binary(&res).unwrap();
+    assert_eq!(pair_res.asset0_volume, Uint128(100));
+    assert_eq!(pair_res.asset1_volume, Uint128(200));
+
+    let res = query(
+        &deps,
+        QueryMsg::AssetVolume {
+            asset_info: native("uusd"),
         },
-    ];
+    )
+    .unwrap();
+    let volume: AssetVolumeResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        volume,
+        AssetVolumeResponse {
+            asset_info: native("uusd"),
+            volume: Uint128(150),
+        }
+    );
 
-    let msg = HandleMsg::CreatePair {
-        asset_infos: asset_infos_2.clone(),
-        init_hook: None,
-    };
+    // the volumes are read from storage, the pairs are not asked for them
+    deps.querier.with_terraswap_pairs(&[]);
+
+    let res = query(
+        &deps,
+        QueryMsg::AssetVolumes {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    let volumes: AssetVolumesResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        volumes.volumes,
+        vec![
+            AssetVolumeResponse {
+                asset_info: native("uusd"),
+                volume: Uint128(150),
+            },
+            AssetVolumeResponse {
+                asset_info: native("uscrt"),
+                volume: Uint128(200),
+            },
+            AssetVolumeResponse {
+                asset_info: native("ueur"),
+                volume: Uint128(70),
+            },
+        ]
+    );
+
+    let res = query(
+        &deps,
+        QueryMsg::AssetVolumes {
+            start_after: Some(native("uusd")),
+            limit: Some(1),
+        },
+    )
+    .unwrap();
+    let volumes: AssetVolumesResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        volumes.volumes,
+        vec![AssetVolumeResponse {
+            asset_info: native("uscrt"),
+            volume: Uint128(200),
+        }]
+    );
+
+    let res = query(
+        &deps,
+        QueryMsg::AssetVolumes {
+            start_after: Some(native("ueur")),
+            limit: None,
+        },
+    )
+    .unwrap();
+    let volumes: AssetVolumesResponse = from_binary(&res).unwrap();
+    assert_eq!(volumes.volumes, vec![]);
+}
+
+#[test]
+fn report_swap_volume_never_fails() {
+    let mut deps = mock_dependencies(20, &[]);
 
     let env = mock_env("addr0000", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    let usd_scrt = [native("uusd"), native("uscrt")];
+    let usd_eur = [native("uusd"), native("ueur")];
 
-    // register terraswap pair querier
     deps.querier.with_terraswap_pairs(&[(
-        &HumanAddr::from("pair0001"),
-        &PairInfo {
-            asset_infos: [
-                AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
-                AssetInfo::NativeToken {
-                    denom: "uusd".to_string(),
-                },
-            ],
-            contract_addr: HumanAddr::from("pair0001"),
-            liquidity_token: HumanAddr::from("liquidity0001"),
-        },
+        &HumanAddr::from("pair0000"),
+        &pair_info(&usd_scrt, "pair0000", "liquidity0000", [0, 0]),
     )]);
 
-    let msg = HandleMsg::Register {
-        asset_infos: asset_infos_2.clone(),
+    create_pair(&mut deps, &usd_scrt);
+    register_pair(&mut deps, &usd_scrt, "pair0000");
+    create_pair(&mut deps, &usd_eur);
+
+    // reports that can't be attributed to a registered pair succeed but are not recorded
+    let logs = report_swap_volume(
+        &mut deps,
+        "addr0000",
+        &usd_scrt,
+        asset(&native("uusd"), 100),
+    );
+    assert_eq!(
+        logs[2],
+        log("ignored", StdError::unauthorized().to_string())
+    );
+    let logs = report_swap_volume(&mut deps, "pair0000", &usd_eur, asset(&native("uusd"), 100));
+    assert_eq!(
+        logs[2],
+        log("ignored", StdError::unauthorized().to_string())
+    );
+    let logs = report_swap_volume(
+        &mut deps,
+        "pair0000",
+        &usd_scrt,
+        asset(&native("ueur"), 100),
+    );
+    assert_eq!(
+        logs[2],
+        log(
+            "ignored",
+            StdError::generic_err("Wrong asset info is given").to_string()
+        )
+    );
+    let logs = report_swap_volume(
+        &mut deps,
+        "pair0000",
+        &[native("uusd"), native("ujpy")],
+        asset(&native("uusd"), 100),
+    );
+    assert_eq!(
+        logs[2],
+        log(
+            "ignored",
+            StdError::generic_err("no pair data stored").to_string()
+        )
+    );
+
+    let res = query(&deps, QueryMsg::ProtocolStats {}).unwrap();
+    let stats: ProtocolStatsResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        stats,
+        ProtocolStatsResponse {
+            pair_count: 2,
+            swap_count: 0,
+            cumulative_volume: Uint128::zero(),
+        }
+    );
+
+    // the volumes saturate instead of overflowing
+    report_swap_volume(
+        &mut deps,
+        "pair0000",
+        &usd_scrt,
+        asset(&native("uusd"), u128::max_value()),
+    );
+    let logs = report_swap_volume(&mut deps, "pair0000", &usd_scrt, asset(&native("uusd"), 1));
+    assert_eq!(logs.len(), 2);
+
+    let res = query(
+        &deps,
+        QueryMsg::AssetVolume {
+            asset_info: native("uusd"),
+        },
+    )
+    .unwrap();
+    let volume: AssetVolumeResponse = from_binary(&res).unwrap();
+    assert_eq!(volume.volume, Uint128(u128::max_value()));
+}
+
+#[test]
+fn asset_volumes_rejects_unknown_start_after() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    create_pair(&mut deps, &[native("uusd"), native("uscrt")]);
+
+    let res = query(
+        &deps,
+        QueryMsg::AssetVolumes {
+            start_after: Some(native("ujpy")),
+            limit: None,
+        },
+    )
+    .unwrap_err();
+    match res {
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "start_after is not a tracked asset"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+fn endpoint(address: &str, pairs: Option<Vec<&str>>) -> SwapDataEndpoint {
+    SwapDataEndpoint {
+        address: HumanAddr::from(address),
+        code_hash: "endpoint_code_hash".to_string(),
+        pairs: pairs.map(|pairs| pairs.into_iter().map(HumanAddr::from).collect()),
+    }
+}
+
+#[test]
+fn swap_data_endpoints() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    let rewards = endpoint("rewards0000", Some(vec!["pair0000"]));
+    let analytics = endpoint("analytics0000", None);
+    for endpoint in [rewards.clone(), analytics.clone()].iter() {
+        let msg = HandleMsg::AddSwapDataEndpoint {
+            endpoint: endpoint.clone(),
+        };
+        handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
+    }
+
+    // adding an endpoint with a known address replaces it
+    let rewards = endpoint("rewards0000", Some(vec!["pair0000", "pair0001"]));
+    let msg = HandleMsg::AddSwapDataEndpoint {
+        endpoint: rewards.clone(),
     };
+    handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
 
-    let env = mock_env("pair0001", &[]);
-    let _res = handle(&mut deps, env, msg).unwrap();
+    let res = query(&deps, QueryMsg::PairSettings {}).unwrap();
+    let settings: PairSettings = from_binary(&res).unwrap();
+    assert_eq!(
+        settings.swap_data_endpoints,
+        vec![analytics.clone(), rewards.clone()]
+    );
+    assert!(rewards.is_subscribed(&HumanAddr::from("pair0001")));
+    assert!(!rewards.is_subscribed(&HumanAddr::from("pair0002")));
+    assert!(analytics.is_subscribed(&HumanAddr::from("pair0002")));
 
-    let query_msg = QueryMsg::Pairs {
-        start_after: None,
-        limit: None,
+    // updating the config leaves the endpoints alone
+    let msg = HandleMsg::UpdateConfig {
+        owner: None,
+        pair_code_id: Some(100u64),
+        token_code_id: None,
+        pair_code_hash: None,
+        token_code_hash: None,
+        swap_fee: None,
+    };
+    handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
+
+    let msg = HandleMsg::RemoveSwapDataEndpoint {
+        address: HumanAddr::from("analytics0000"),
+    };
+    handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
+
+    let res = query(&deps, QueryMsg::PairSettings {}).unwrap();
+    let settings: PairSettings = from_binary(&res).unwrap();
+    assert_eq!(settings.swap_data_endpoints, vec![rewards]);
+
+    let msg = HandleMsg::RemoveSwapDataEndpoint {
+        address: HumanAddr::from("analytics0000"),
+    };
+    let res = handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap_err();
+    match res {
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "Swap data endpoint not found"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn swap_data_endpoints_are_owner_only() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    let msg = HandleMsg::AddSwapDataEndpoint {
+        endpoint: endpoint("rewards0000", None),
     };
+    match handle(&mut deps, mock_env("addr0001", &[]), msg) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    let msg = HandleMsg::AddSwapDataEndpoint {
+        endpoint: endpoint("rewards0000", None),
+    };
+    handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
+
+    let msg = HandleMsg::RemoveSwapDataEndpoint {
+        address: HumanAddr::from("rewards0000"),
+    };
+    match handle(&mut deps, mock_env("addr0001", &[]), msg) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("Must return unauthorized error"),
+    }
+}
+
+#[test]
+fn legacy_swap_data_endpoint_is_carried_over() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    // store the config the way it was stored when pairs had a single swap data endpoint
+    let key = to_length_prefixed(b"config");
+    let config = String::from_utf8(deps.storage.get(&key).unwrap()).unwrap();
+    let legacy_config = config.replace(
+        r#""swap_data_endpoints":[]"#,
+        r#""swap_data_endpoint":{"address":"analytics0000","code_hash":"endpoint_code_hash"}"#,
+    );
+    assert_ne!(config, legacy_config);
+    deps.storage.set(&key, legacy_config.as_bytes());
+
+    let analytics = endpoint("analytics0000", None);
+    let res = query(&deps, QueryMsg::PairSettings {}).unwrap();
+    let settings: PairSettings = from_binary(&res).unwrap();
+    assert_eq!(settings.swap_data_endpoints, vec![analytics.clone()]);
+
+    let rewards = endpoint("rewards0000", Some(vec!["pair0000"]));
+    let msg = HandleMsg::AddSwapDataEndpoint {
+        endpoint: rewards.clone(),
+    };
+    handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
+
+    let res = query(&deps, QueryMsg::PairSettings {}).unwrap();
+    let settings: PairSettings = from_binary(&res).unwrap();
+    assert_eq!(
+        settings.swap_data_endpoints,
+        vec![analytics, rewards.clone()]
+    );
+
+    // once removed, the legacy endpoint does not come back
+    let msg = HandleMsg::RemoveSwapDataEndpoint {
+        address: HumanAddr::from("analytics0000"),
+    };
+    handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap();
+
+    let res = query(&deps, QueryMsg::PairSettings {}).unwrap();
+    let settings: PairSettings = from_binary(&res).unwrap();
+    assert_eq!(settings.swap_data_endpoints, vec![rewards]);
+}
+
+fn token_metadata(name: &str, symbol: &str) -> TokenMetadata {
+    TokenMetadata {
+        name: name.to_string(),
+        symbol: symbol.to_string(),
+        decimals: 6,
+    }
+}
+
+#[test]
+fn token_metadata_is_cached() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    let asset_infos = [token("asset0000"), native("uscrt")];
+    deps.querier.with_token_metadata(&[(
+        &HumanAddr::from("asset0000"),
+        &token_metadata("Secret Token", "STKN"),
+    )]);
+    deps.querier.with_terraswap_pairs(&[(
+        &HumanAddr::from("pair0000"),
+        &pair_info(&asset_infos, "pair0000", "liquidity0000", [0, 0]),
+    )]);
+
+    create_pair(&mut deps, &asset_infos);
+    register_pair(&mut deps, &asset_infos, "pair0000");
 
-    let res = query(&mut deps, query_msg).unwrap();
+    let res = query(
+        &deps,
+        QueryMsg::Pair {
+            asset_infos: asset_infos.clone(),
+        },
+    )
+    .unwrap();
+    let pair_res: PairInfo = from_binary(&res).unwrap();
+    assert_eq!(
+        pair_res.asset_metadata,
+        [Some(token_metadata("Secret Token", "STKN")), None]
+    );
+
+    // the cache is only updated on demand
+    deps.querier.with_token_metadata(&[(
+        &HumanAddr::from("asset0000"),
+        &token_metadata("Secret Token v2", "STKN2"),
+    )]);
+
+    let res = query(
+        &deps,
+        QueryMsg::Pairs {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
     let pairs_res: PairsResponse = from_binary(&res).unwrap();
     assert_eq!(
-        pairs_res.pairs,
+        pairs_res.pairs[0].asset_metadata,
+        [Some(token_metadata("Secret Token", "STKN")), None]
+    );
+
+    let msg = HandleMsg::RefreshTokenMetadata {
+        asset_info: token("asset0000"),
+    };
+    let res = handle(&mut deps, mock_env("addr0001", &[]), msg).unwrap();
+    assert_eq!(
+        res.log,
         vec![
-            PairInfo {
-                liquidity_token: HumanAddr::from("liquidity0000"),
-                contract_addr: HumanAddr::from("pair0000"),
-                asset_infos: asset_infos.clone(),
-            },
-            PairInfo {
-                liquidity_token: HumanAddr::from("liquidity0001"),
-                contract_addr: HumanAddr::from("pair0001"),
-                asset_infos: asset_infos_2.clone(),
-            }
+            log("action", "refresh_token_metadata"),
+            log("asset", "asset0000"),
+            log("symbol", "STKN2"),
         ]
     );
 
-    let query_msg = QueryMsg::Pairs {
-        start_after: None,
-        limit: Some(1),
+    let res = query(
+        &deps,
+        QueryMsg::Pairs {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    let pairs_res: PairsResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        pairs_res.pairs[0].asset_metadata,
+        [Some(token_metadata("Secret Token v2", "STKN2")), None]
+    );
+}
+
+#[test]
+fn refresh_token_metadata_rejects_native_tokens() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    let msg = HandleMsg::RefreshTokenMetadata {
+        asset_info: native("uscrt"),
     };
+    let res = handle(&mut deps, mock_env("addr0000", &[]), msg).unwrap_err();
+    match res {
+        StdError::GenericErr { msg, .. } => {
+            assert_eq!(msg, "Token metadata is only available for token assets")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn pairs_pagination_past_the_first_page() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    let pairs: Vec<(HumanAddr, PairInfo)> = (0..35)
+        .map(|i| {
+            let pair_contract = format!("pair{:04}", i);
+            let asset_infos = [native(&format!("u{:03}", i)), native("uscrt")];
+            (
+                HumanAddr::from(pair_contract.as_str()),
+                pair_info(&asset_infos, &pair_contract, "liquidity0000", [0, 0]),
+            )
+        })
+        .collect();
+    let pair_refs: Vec<(&HumanAddr, &PairInfo)> =
+        pairs.iter().map(|(addr, pair)| (addr, pair)).collect();
+    deps.querier.with_terraswap_pairs(&pair_refs);
+
+    for (pair_contract, pair) in pairs.iter() {
+        create_pair(&mut deps, &pair.asset_infos);
+        register_pair(&mut deps, &pair.asset_infos, pair_contract.as_str());
+    }
+
+    let mut start_after = None;
+    let mut listed = vec![];
+    loop {
+        let res = query(
+            &deps,
+            QueryMsg::Pairs {
+                start_after,
+                limit: Some(30),
+            },
+        )
+        .unwrap();
+        let pairs_res: PairsResponse = from_binary(&res).unwrap();
+        if pairs_res.pairs.is_empty() {
+            break;
+        }
+
+        start_after = pairs_res.pairs.last().map(|pair| pair.asset_infos.clone());
+        listed.push(pairs_res.pairs.len());
+        assert!(listed.len() <= 2, "pages must not repeat");
+    }
+    assert_eq!(listed, vec![30, 5]);
 
-    let res = query(&mut deps, query_msg).unwrap();
+    // the pair to start after can be given in either order
+    let res = query(
+        &deps,
+        QueryMsg::Pairs {
+            start_after: Some([native("uscrt"), native("u029")]),
+            limit: Some(30),
+        },
+    )
+    .unwrap();
     let pairs_res: PairsResponse = from_binary(&res).unwrap();
+    assert_eq!(pairs_res.pairs.len(), 5);
     assert_eq!(
-        pairs_res.pairs,
-        vec![PairInfo {
-            liquidity_token: HumanAddr::from("liquidity0000"),
-            contract_addr: HumanAddr::from("pair0000"),
-            asset_infos: asset_infos.clone(),
-        }]
+        pairs_res.pairs[0].contract_addr,
+        HumanAddr::from("pair0030")
     );
+}
 
-    let query_msg = QueryMsg::Pairs {
-        start_after: Some(asset_infos.clone()),
-        limit: None,
+#[test]
+fn pairs_with_asset() {
+    let mut deps = mock_dependencies(20, &[]);
+
+    let env = mock_env("addr0000", &[]);
+    let _res = init(&mut deps, env, init_msg()).unwrap();
+
+    let usd_scrt = [native("uusd"), native("uscrt")];
+    let scrt_eur = [native("uscrt"), native("ueur")];
+    let eur_usd = [native("ueur"), native("uusd")];
+
+    deps.querier.with_terraswap_pairs(&[
+        (
+            &HumanAddr::from("pair0000"),
+            &pair_info(&usd_scrt, "pair0000", "liquidity0000", [0, 0]),
+        ),
+        (
+            &HumanAddr::from("pair0001"),
+            &pair_info(&scrt_eur, "pair0001", "liquidity0001", [0, 0]),
+        ),
+        (
+            &HumanAddr::from("pair0002"),
+            &pair_info(&eur_usd, "pair0002", "liquidity0002", [0, 0]),
+        ),
+    ]);
+
+    for (asset_infos, pair_contract) in [
+        (&usd_scrt, "pair0000"),
+        (&scrt_eur, "pair0001"),
+        (&eur_usd, "pair0002"),
+    ]
+    .iter()
+    {
+        create_pair(&mut deps, asset_infos);
+        register_pair(&mut deps, asset_infos, pair_contract);
+    }
+
+    let pairs_with_asset = |start_after: Option<[AssetInfo; 2]>, limit: Option<u32>| {
+        let res = query(
+            &deps,
+            QueryMsg::PairsWithAsset {
+                asset_info: native("uusd"),
+                start_after,
+                limit,
+            },
+        )
+        .unwrap();
+        let pairs_res: PairsResponse = from_binary(&res).unwrap();
+        pairs_res
+            .pairs
+            .iter()
+            .map(|pair| pair.contract_addr.clone())
+            .collect::<Vec<HumanAddr>>()
     };
 
-    let res = query(&mut deps, query_msg).unwrap();
-    let pairs_res: PairsResponse = from_binary(&res).unwrap();
     assert_eq!(
-        pairs_res.pairs,
-        vec![PairInfo {
-            liquidity_token: HumanAddr::from("liquidity0001"),
-            contract_addr: HumanAddr::from("pair0001"),
-            asset_infos: asset_infos_2.clone(),
-        }]
+        pairs_with_asset(None, None),
+        vec![HumanAddr::from("pair0000"), HumanAddr::from("pair0002")]
+    );
+    assert_eq!(
+        pairs_with_asset(None, Some(1)),
+        vec![HumanAddr::from("pair0000")]
     );
+    assert_eq!(
+        pairs_with_asset(Some(usd_scrt.clone()), None),
+        vec![HumanAddr::from("pair0002")]
+    );
+    assert_eq!(pairs_with_asset(Some(eur_usd), None), vec![]);
+
+    let res = query(
+        &deps,
+        QueryMsg::PairsWithAsset {
+            asset_info: native("ujpy"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    let pairs_res: PairsResponse = from_binary(&res).unwrap();
+    assert_eq!(pairs_res.pairs, vec![]);
 }
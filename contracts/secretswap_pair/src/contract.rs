@@ -13,8 +13,8 @@ use primitive_types::U256;
 use secret_toolkit::snip20;
 
 use secretswap::{
-    query_supply, Asset, AssetInfo, AssetInfoRaw, Factory, InitHook, PairInfo, PairInfoRaw,
-    PairInitMsg, TokenInitMsg,
+    query_supply, Asset, AssetInfo, AssetInfoRaw, Factory, FactoryHandleMsg, InitHook, PairInfo,
+    PairInfoRaw, PairInitMsg, TokenInitMsg,
 };
 
 use crate::{
@@ -99,14 +99,22 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         _ => {}
     }
 
+    let lp_token_name = match &msg.asset_symbols {
+        Some(symbols) => format!(
+            "SecretSwap Liquidity Provider (LP) token for {}-{}",
+            &symbols[0], &symbols[1]
+        ),
+        None => format!(
+            "SecretSwap Liquidity Provider (LP) token for {}-{}",
+            &msg.asset_infos[0], &msg.asset_infos[1]
+        ),
+    };
+
     // Create LP token
     messages.extend(vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
         code_id: msg.token_code_id,
         msg: to_binary(&TokenInitMsg::new(
-            format!(
-                "SecretSwap Liquidity Provider (LP) token for {}-{}",
-                &msg.asset_infos[0], &msg.asset_infos[1]
-            ),
+            lp_token_name,
             env.contract.address.clone(),
             "SWAP-LP".to_string(),
             6,
@@ -181,6 +189,7 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             belief_price,
             max_spread,
             to,
+            callback,
         } => {
             if !offer_asset.is_native_token() {
                 return Err(StdError::unauthorized());
@@ -195,6 +204,7 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
                 belief_price,
                 max_spread,
                 to,
+                callback,
             )
         }
     }
@@ -216,6 +226,7 @@ pub fn receive_cw20<S: Storage, A: Api, Q: Querier>(
                 belief_price,
                 max_spread,
                 to,
+                callback,
             } => {
                 // only asset contract can execute this message
                 let mut authorized: bool = false;
@@ -249,6 +260,7 @@ pub fn receive_cw20<S: Storage, A: Api, Q: Querier>(
                     belief_price,
                     max_spread,
                     to,
+                    callback,
                 )
             }
             Cw20HookMsg::WithdrawLiquidity {} => {
@@ -513,6 +525,7 @@ pub fn try_withdraw_liquidity<S: Storage, A: Api, Q: Querier>(
 }
 
 // CONTRACT - a user must do token approval
+#[allow(clippy::too_many_arguments)]
 pub fn try_swap<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -522,6 +535,7 @@ pub fn try_swap<S: Storage, A: Api, Q: Querier>(
     belief_price: Option<Decimal>,
     max_spread: Option<Decimal>,
     to: Option<HumanAddr>,
+    callback: Option<Binary>,
 ) -> HandleResult {
     offer_asset.assert_sent_native_token_balance(&env)?;
 
@@ -601,24 +615,43 @@ pub fn try_swap<S: Storage, A: Api, Q: Querier>(
         amount: return_amount,
     };
 
+    let receiver = to.unwrap_or(sender);
+
     let mut messages = Vec::<CosmosMsg>::new();
-    messages.push(return_asset.clone().into_msg(
+    messages.push(return_asset.clone().into_msg_with_callback(
         &deps,
         env.contract.address.clone(),
-        to.clone().unwrap_or(sender.clone()),
+        receiver.clone(),
+        callback,
     )?);
 
-    if let Some(data_endpoint) = pair_settings.swap_data_endpoint {
+    for data_endpoint in pair_settings.swap_data_endpoints {
+        if !data_endpoint.is_subscribed(&env.contract.address) {
+            continue;
+        }
+
         messages.push(data_endpoint.into_msg(
             offer_asset.clone(),
             Asset {
-                info: return_asset.info,
+                info: return_asset.info.clone(),
                 amount: return_amount + commission_amount,
             },
-            to.unwrap_or(sender),
+            receiver.clone(),
         )?);
     }
 
+    // report the swap volume to the factory, which aggregates protocol-wide analytics.
+    // the factory never fails this message, so it can't revert the swap
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: pair_info.factory.address.clone(),
+        callback_code_hash: pair_info.factory.code_hash.clone(),
+        msg: to_binary(&FactoryHandleMsg::ReportSwapVolume {
+            asset_infos: [pools[0].info.clone(), pools[1].info.clone()],
+            offer_asset: offer_asset.clone(),
+        })?,
+        send: vec![],
+    }));
+
     // 1. send collateral token from the contract to a user
     // 2. send inactive commission to collector
     Ok(HandleResponse {
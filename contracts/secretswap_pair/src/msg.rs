@@ -26,6 +26,9 @@ pub enum HandleMsg {
         belief_price: Option<Decimal>,
         max_spread: Option<Decimal>,
         to: Option<HumanAddr>,
+        /// Sent along with a token return as the msg of the Receive of `to`
+        #[serde(default)]
+        callback: Option<Binary>,
     },
 }
 
@@ -38,6 +41,9 @@ pub enum Cw20HookMsg {
         belief_price: Option<Decimal>,
         max_spread: Option<Decimal>,
         to: Option<HumanAddr>,
+        /// Sent along with a token return as the msg of the Receive of `to`
+        #[serde(default)]
+        callback: Option<Binary>,
     },
     WithdrawLiquidity {},
 }
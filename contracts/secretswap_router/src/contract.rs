@@ -1,19 +1,42 @@
 use cosmwasm_std::{
-    debug_print, from_binary, to_binary, Api, BankMsg, Binary, Coin, CosmosMsg, Env, Extern,
-    HandleResponse, HumanAddr, InitResponse, Querier, StdError, StdResult, Storage, Uint128,
-    WasmMsg,
+    debug_print, from_binary, log, to_binary, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal, Env,
+    Extern, HandleResponse, HumanAddr, InitResponse, Querier, StdError, StdResult, Storage,
+    Uint128, WasmMsg,
 };
 use secret_toolkit::snip20;
-use secretswap::{Asset, AssetInfo};
+use secretswap::{query_balance, Asset, AssetInfo};
+
+use std::collections::VecDeque;
 
 use crate::{
-    msg::{HandleMsg, Hop, InitMsg, NativeSwap, QueryMsg, Route, Snip20Data, Snip20Swap, Token},
+    msg::{
+        BatchRoute, HandleMsg, Hop, InitMsg, LegShare, NativeSwap, OpenRoute, OpenRoutesResponse,
+        QueryMsg, ReceiveMsg, Referral, ReferralEarningsResponse, ReverseSimulateRouteResponse,
+        RewardEarningsResponse, RewardProgramInfo, RewardProgramsResponse, Route, RouteLeg,
+        Snip20Data, Snip20Swap, SscrtMsg, SupportedTokensResponse, Token,
+    },
+    rewards::route_reward,
+    routing::{
+        find_best_route, is_same_token, native_denom, native_token, reverse_simulate_route,
+        simulate_route,
+    },
     state::{
-        delete_route_state, read_cashback, read_owner, read_route_state, read_tokens,
-        store_cashback, store_owner, store_route_state, store_tokens, RouteState,
+        add_referral_earnings, add_reward_earnings, delete_batch, delete_reward_program,
+        delete_route_state, next_reward_program_id, next_route_id, read_active_route, read_batch,
+        read_factory, read_max_referral_fee_bps, read_open_routes, read_owner,
+        read_referral_earnings, read_reward_earnings, read_reward_program, read_reward_program_ids,
+        read_route_state, read_sscrt, read_token, read_tokens_page, read_viewing_key, remove_token,
+        store_active_route, store_batch, store_factory, store_max_referral_fee_bps, store_owner,
+        store_reward_program, store_route_state, store_sscrt, store_token, store_tokens,
+        write_viewing_key, Batch, BatchDeposit, CollectedReturn, Cycle, InitialBalance, LegState,
+        QueuedRoute, Refund, RewardProgramState, RouteState, SecretContract, SwappedHop,
     },
+    viewing_key::{check_viewing_key, hash_viewing_key, VIEWING_KEY_SIZE},
 };
 
+/// Referral fees are expressed in basis points of the route's input
+const MAX_FEE_BPS: u16 = 10_000;
+
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -32,16 +55,13 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         output_msgs.extend(register_tokens(deps, &env, tokens)?);
     }
 
-    if let Some(cashback) = msg.cashback {
-        store_cashback(&mut deps.storage, &cashback)?;
-        output_msgs.extend(register_tokens(
-            deps,
-            &env,
-            vec![Snip20Data {
-                address: cashback.address,
-                code_hash: cashback.code_hash,
-            }],
-        )?);
+    if let Some(factory) = msg.factory {
+        store_factory(&mut deps.storage, &factory)?;
+    }
+
+    if let Some(sscrt) = msg.sscrt {
+        store_sscrt(&mut deps.storage, &sscrt)?;
+        output_msgs.extend(register_tokens(deps, &env, vec![sscrt])?);
     }
 
     Ok(InitResponse {
@@ -57,16 +77,31 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<HandleResponse> {
     match msg {
         HandleMsg::Receive {
-            from: _,
+            from,
             msg: Some(msg),
             amount,
-        } => handle_first_hop(deps, &env, msg, amount),
+        } => handle_first_hop(deps, &env, from, msg, amount),
         HandleMsg::Receive {
             from,
             msg: None,
             amount,
-        } => handle_hop(deps, &env, from, amount),
-        HandleMsg::FinalizeRoute {} => finalize_route(deps, &env),
+        } => {
+            // pairs deployed before the HopReturn callback pay out without a msg, so this can only
+            // be the return of the active route's current hop, which handle_hop makes sure of
+            match read_active_route(&deps.storage)? {
+                Some(route_id) => handle_hop(deps, &env, from, amount, route_id),
+                None => Err(StdError::generic_err(
+                    "tokens can only be sent here along with a route",
+                )),
+            }
+        }
+        HandleMsg::FinalizeRoute { route_id } => finalize_route(deps, &env, route_id),
+        HandleMsg::ContinueRoute { route_id } => continue_route(deps, &env, route_id),
+        HandleMsg::ClearRoutes { route_ids } => clear_routes(deps, &env, route_ids),
+        HandleMsg::ExecuteBatch { routes } => execute_batch(deps, &env, routes),
+        HandleMsg::CancelBatch {} => cancel_batch(deps, &env),
+        HandleMsg::StartBatchRoute { sender } => start_batch_route(deps, &env, sender),
+        HandleMsg::FinalizeBatch { sender } => finalize_batch(deps, &env, sender),
         HandleMsg::RegisterTokens { tokens } => {
             check_owner(deps, &env)?;
 
@@ -78,6 +113,28 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
                 data: None,
             })
         }
+        HandleMsg::UnregisterTokens { tokens } => {
+            check_owner(deps, &env)?;
+
+            for token in tokens.iter() {
+                if read_token(&deps.storage, token)?.is_none() {
+                    return Err(StdError::generic_err(format!(
+                        "{} is not registered",
+                        token
+                    )));
+                }
+                remove_token(&mut deps.storage, token)?;
+            }
+
+            Ok(HandleResponse {
+                messages: vec![],
+                log: vec![
+                    log("action", "unregister_tokens"),
+                    log("tokens", format!("{:?}", tokens)),
+                ],
+                data: None,
+            })
+        }
         HandleMsg::RecoverFunds {
             token,
             amount,
@@ -101,6 +158,11 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
                     to_address: to,
                     amount: vec![Coin::new(amount.u128(), "uscrt")],
                 })],
+                Token::Native { denom } => vec![CosmosMsg::Bank(BankMsg::Send {
+                    from_address: env.contract.address,
+                    to_address: to,
+                    amount: vec![Coin::new(amount.u128(), &denom)],
+                })],
             };
 
             Ok(HandleResponse {
@@ -111,19 +173,122 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         }
         HandleMsg::UpdateSettings {
             new_owner,
-            new_cashback,
+            new_factory,
+            new_sscrt,
+            new_max_referral_fee_bps,
         } => {
             check_owner(deps, &env)?;
 
+            let mut output_msgs = vec![];
+
             if let Some(new_owner) = new_owner {
                 store_owner(&mut deps.storage, &new_owner)?;
             }
 
-            if let Some(new_cashback) = new_cashback {
-                store_cashback(&mut deps.storage, &new_cashback)?;
+            if let Some(new_factory) = new_factory {
+                store_factory(&mut deps.storage, &new_factory)?;
+            }
+
+            if let Some(new_sscrt) = new_sscrt {
+                store_sscrt(&mut deps.storage, &new_sscrt)?;
+                output_msgs.extend(register_tokens(deps, &env, vec![new_sscrt])?);
+            }
+
+            if let Some(new_max_referral_fee_bps) = new_max_referral_fee_bps {
+                if new_max_referral_fee_bps > MAX_FEE_BPS {
+                    return Err(StdError::generic_err(format!(
+                        "max referral fee cannot exceed {} bps",
+                        MAX_FEE_BPS
+                    )));
+                }
+                store_max_referral_fee_bps(&mut deps.storage, new_max_referral_fee_bps)?;
+            }
+
+            Ok(HandleResponse {
+                messages: output_msgs,
+                log: vec![],
+                data: None,
+            })
+        }
+        HandleMsg::SetViewingKey { key, .. } => {
+            write_viewing_key(
+                &mut deps.storage,
+                &env.message.sender,
+                &hash_viewing_key(&key),
+            )?;
+
+            Ok(HandleResponse {
+                messages: vec![],
+                log: vec![log("action", "set_viewing_key")],
+                data: None,
+            })
+        }
+        HandleMsg::AddRewardProgram { program } => {
+            check_owner(deps, &env)?;
+
+            if program.budget.is_zero() {
+                return Err(StdError::generic_err("reward program must have a budget"));
+            }
+            if program.end_time <= env.block.time {
+                return Err(StdError::generic_err(
+                    "reward program must end in the future",
+                ));
+            }
+
+            let output_msgs = register_tokens(deps, &env, vec![program.reward_token.clone()])?;
+
+            let program_id = next_reward_program_id(&mut deps.storage)?;
+            store_reward_program(
+                &mut deps.storage,
+                &RewardProgramState {
+                    id: program_id,
+                    program,
+                    paid_out: Uint128::zero(),
+                    escrow: Uint128::zero(),
+                },
+            )?;
+
+            Ok(HandleResponse {
+                messages: output_msgs,
+                log: vec![
+                    log("action", "add_reward_program"),
+                    log("program_id", program_id),
+                ],
+                data: None,
+            })
+        }
+        HandleMsg::EndRewardProgram { program_id } => {
+            check_owner(deps, &env)?;
+
+            let program = match read_reward_program(&deps.storage, program_id)? {
+                Some(program) => program,
+                None => {
+                    return Err(StdError::generic_err(format!(
+                        "cannot find reward program {}",
+                        program_id
+                    )))
+                }
+            };
+            delete_reward_program(&mut deps.storage, program_id)?;
+
+            let mut messages = vec![];
+            if !program.escrow.is_zero() {
+                messages.push(transfer_msg(
+                    &env,
+                    &Token::Snip20(program.program.reward_token),
+                    env.message.sender.clone(),
+                    program.escrow,
+                )?);
             }
 
-            Ok(HandleResponse::default())
+            Ok(HandleResponse {
+                messages,
+                log: vec![
+                    log("action", "end_reward_program"),
+                    log("program_id", program_id),
+                ],
+                data: None,
+            })
         }
     }
 }
@@ -131,6 +296,7 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
 fn handle_first_hop<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: &Env,
+    from: HumanAddr,
     msg: Binary,
     amount: Uint128,
 ) -> StdResult<HandleResponse> {
@@ -139,347 +305,1516 @@ fn handle_first_hop<S: Storage, A: Api, Q: Querier>(
     // 2. send `amount` X to pair X/Y
     // 3. call FinalizeRoute to make sure everything went ok, otherwise revert the tx
 
+    // native tokens are sent along with this msg, so `from` is only meaningful for SNIP-20 tokens
+    let sender = if env.message.sent_funds.is_empty() {
+        from
+    } else {
+        if env.message.sent_funds.len() != 1 || env.message.sent_funds[0].amount != amount {
+            return Err(StdError::generic_err(
+                "route can only be initiated by sending here the token of the first hop",
+            ));
+        }
+        env.message.sender.clone()
+    };
+    let offer_token = received_token(env);
+
+    match from_binary(&msg) {
+        Ok(ReceiveMsg::HopReturn { route_id }) => {
+            return handle_hop(deps, env, sender, amount, route_id);
+        }
+        Ok(ReceiveMsg::BatchDeposit {}) => {
+            return batch_deposit(deps, sender, offer_token, amount);
+        }
+        Ok(ReceiveMsg::FundRewardProgram { program_id }) => {
+            return fund_reward_program(deps, env, offer_token, program_id, amount);
+        }
+        Ok(ReceiveMsg::AutoRoute {
+            to_token,
+            expected_return,
+            to,
+            max_hops,
+            max_price_impact,
+            referral,
+        }) => {
+            let factory = load_factory(deps)?;
+            let best_route =
+                find_best_route(deps, &factory, &offer_token, &to_token, amount, max_hops)?;
+
+            let mut res = start_route(
+                deps,
+                env,
+                offer_token,
+                vec![(best_route.hops, amount)],
+                RouteOptions {
+                    expected_return,
+                    to,
+                    return_token: None,
+                    max_price_impact,
+                    referral,
+                    exact_return: None,
+                    sender,
+                    min_profit: None,
+                    profit_to: None,
+                },
+            )?;
+            if best_route.truncated {
+                res.log.push(log("route_search", "truncated"));
+            }
+            return Ok(res);
+        }
+        Ok(ReceiveMsg::SplitRoute {
+            legs,
+            return_token,
+            expected_return,
+            to,
+            max_price_impact,
+            referral,
+        }) => {
+            if legs.is_empty() {
+                return Err(StdError::generic_err("route must be at least 1 leg"));
+            }
+
+            let amounts = split_amount(amount, &legs)?;
+            let legs = legs.into_iter().map(|leg| leg.hops).zip(amounts).collect();
+
+            return start_route(
+                deps,
+                env,
+                offer_token,
+                legs,
+                RouteOptions {
+                    expected_return,
+                    to,
+                    return_token: Some(return_token),
+                    max_price_impact,
+                    referral,
+                    exact_return: None,
+                    sender,
+                    min_profit: None,
+                    profit_to: None,
+                },
+            );
+        }
+        Ok(ReceiveMsg::ExactOutputRoute {
+            hops,
+            return_token,
+            return_amount,
+            to,
+            max_price_impact,
+            referral,
+        }) => {
+            return start_route(
+                deps,
+                env,
+                offer_token,
+                vec![(hops, amount)],
+                RouteOptions {
+                    expected_return: Some(return_amount),
+                    to,
+                    return_token: Some(return_token),
+                    max_price_impact,
+                    referral,
+                    exact_return: Some(return_amount),
+                    sender,
+                    min_profit: None,
+                    profit_to: None,
+                },
+            );
+        }
+        Err(_) => {}
+    }
+
     let Route {
-        mut hops,
+        hops,
         to,
         expected_return,
+        return_token,
+        max_price_impact,
+        referral,
+        min_profit,
+        profit_to,
     } = from_binary(&msg)?;
 
     if hops.len() < 2 {
         return Err(StdError::generic_err("route must be at least 2 hops"));
     }
 
-    // uscrt can only be the input or output token
-    // check that uscrt is not the input token for any hop that is not the first hop
-    // (we don't need to check if it's the output token because it's handled in the swap_pair contract)
-    for i in 1..(hops.len() - 1) {
-        match hops[i].from_token {
-            Token::Scrt => {
-                return Err(StdError::generic_err(
-                    "cannot route via uscrt. uscrt can only be route input token or output token.",
-                ))
-            }
-            _ => continue,
-        }
-    }
+    start_route(
+        deps,
+        env,
+        offer_token,
+        vec![(hops, amount)],
+        RouteOptions {
+            expected_return,
+            to,
+            return_token,
+            max_price_impact,
+            referral,
+            exact_return: None,
+            sender,
+            min_profit,
+            profit_to,
+        },
+    )
+}
 
-    let first_hop: Hop = hops.pop_front().unwrap(); // unwrap is cool because `hops.len() >= 2`
+/// Everything about a route except for its legs
+struct RouteOptions {
+    expected_return: Option<Uint128>,
+    to: HumanAddr,
+    /// Set when the router collects the return of the route, instead of the last pair paying it out
+    return_token: Option<Token>,
+    max_price_impact: Option<Decimal>,
+    referral: Option<Referral>,
+    /// Set for exact-output routes, which only swap the input needed to return this much
+    exact_return: Option<Uint128>,
+    /// Who sent the input of the route
+    sender: HumanAddr,
+    /// Set for arbitrage cycles
+    min_profit: Option<Uint128>,
+    profit_to: Option<HumanAddr>,
+}
 
-    let received_first_hop: bool = match first_hop.from_token {
+/// The token that was sent to the router along with the route
+fn received_token(env: &Env) -> Token {
+    if env.message.sent_funds.is_empty() {
         Token::Snip20(Snip20Data {
-            ref address,
-            code_hash: _,
-        }) => env.message.sender == *address,
-        Token::Scrt => {
-            env.message.sent_funds.len() == 1
-                && env.message.sent_funds[0].amount == amount
-                && env.message.sent_funds[0].denom == "uscrt"
-        }
-    };
+            address: env.message.sender.clone(),
+            code_hash: String::new(),
+        })
+    } else {
+        native_token(&env.message.sent_funds[0].denom)
+    }
+}
 
-    if !received_first_hop {
+/// Splits the received `amount` between the legs of a split route
+fn split_amount(amount: Uint128, legs: &[RouteLeg]) -> StdResult<Vec<Uint128>> {
+    let explicit_amount: u128 = legs
+        .iter()
+        .map(|leg| match leg.share {
+            LegShare::Amount(leg_amount) => leg_amount.u128(),
+            LegShare::Percentage(_) => 0,
+        })
+        .sum();
+    let total_percentage: u32 = legs
+        .iter()
+        .map(|leg| match leg.share {
+            LegShare::Percentage(percentage) => percentage as u32,
+            LegShare::Amount(_) => 0,
+        })
+        .sum();
+
+    // percentages are taken out of whatever the explicit amounts leave
+    let rest = (amount - Uint128(explicit_amount))
+        .map_err(|_| StdError::generic_err("leg amounts exceed the amount sent"))?
+        .u128();
+    if total_percentage == 0 && rest != 0 {
         return Err(StdError::generic_err(
-            "route can only be initiated by sending here the token of the first hop",
+            "leg amounts must add up to the amount sent",
         ));
     }
+    if total_percentage != 0 && total_percentage != 100 {
+        return Err(StdError::generic_err("leg percentages must add up to 100"));
+    }
 
-    store_route_state(
-        &mut deps.storage,
-        &RouteState {
-            is_done: false,
-            current_hop: Some(first_hop.clone()),
-            remaining_route: Route {
-                hops, // hops was mutated earlier when we did `hops.pop_front()`
-                expected_return,
-                to,
-            },
-        },
-    )?;
-
-    let mut msgs = vec![];
+    let mut amounts: Vec<Uint128> = legs
+        .iter()
+        .map(|leg| match leg.share {
+            LegShare::Amount(leg_amount) => leg_amount,
+            LegShare::Percentage(percentage) => Uint128(rest * percentage as u128 / 100),
+        })
+        .collect();
 
-    match first_hop.from_token {
-        Token::Snip20(Snip20Data { address, code_hash }) => {
-            // first hop is a snip20
-            msgs.push(snip20::send_msg(
-                first_hop.pair_address,
-                amount,
-                // build swap msg for the next hop
-                Some(to_binary(&Snip20Swap::Swap {
-                    // set expected_return to None because we don't care about slippage mid-route
-                    expected_return: None,
-                    // set the recepient of the swap to be this contract (the router)
-                    to: Some(env.contract.address.clone()),
-                })?),
-                None,
-                256,
-                code_hash,
-                address,
-            )?);
-        }
-        Token::Scrt => {
-            // first hop is SCRT
-            msgs.push(
-                // build swap msg for the next hop
-                CosmosMsg::Wasm(WasmMsg::Execute {
-                    contract_addr: first_hop.pair_address,
-                    callback_code_hash: first_hop.pair_code_hash,
-                    msg: to_binary(&NativeSwap::Swap {
-                        offer_asset: Asset {
-                            amount,
-                            info: AssetInfo::NativeToken {
-                                denom: "uscrt".into(),
-                            },
-                        },
-                        // set expected_return to None because we don't care about slippage mid-route
-                        expected_return: None,
-                        // set the recepient of the swap to be this contract (the router)
-                        to: Some(env.contract.address.clone()),
-                    })?,
-                    send: vec![Coin::new(amount.u128(), "uscrt")],
-                }),
-            );
-        }
+    // rounding leftovers go to the last percentage leg
+    if let Some(last) = legs
+        .iter()
+        .rposition(|leg| matches!(leg.share, LegShare::Percentage(_)))
+    {
+        let split_rest: u128 = amounts.iter().map(|a| a.u128()).sum::<u128>() - explicit_amount;
+        amounts[last] = amounts[last] + Uint128(rest - split_rest);
     }
 
-    msgs.push(
-        // finalize the route at the end, to make sure the route was completed successfully
-        CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: env.contract.address.clone(),
-            callback_code_hash: env.contract_code_hash.clone(),
-            msg: to_binary(&HandleMsg::FinalizeRoute {})?,
-            send: vec![],
-        }),
-    );
+    if amounts.iter().any(|a| a.is_zero()) {
+        return Err(StdError::generic_err(
+            "every leg must swap a non-zero amount",
+        ));
+    }
 
-    Ok(HandleResponse {
-        messages: msgs,
-        log: vec![],
-        data: None,
-    })
+    Ok(amounts)
 }
 
-fn handle_hop<S: Storage, A: Api, Q: Querier>(
+fn start_route<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: &Env,
-    from: HumanAddr,
-    amount: Uint128,
+    offer_token: Token,
+    mut legs: Vec<(VecDeque<Hop>, Uint128)>,
+    options: RouteOptions,
 ) -> StdResult<HandleResponse> {
-    // This is a receive msg somewhere along the route
-    // 1. load route from state (Y/Z -> Z/W)
-    // 2. save the remaining route to state (Z/W)
-    // 3. send `amount` Y to pair Y/Z
+    let RouteOptions {
+        expected_return,
+        to,
+        mut return_token,
+        max_price_impact,
+        referral,
+        exact_return,
+        sender,
+        min_profit,
+        profit_to,
+    } = options;
 
-    // 1'. load route from state (Z/W)
-    // 2'. this is the last hop so delete the entire route state
-    // 3'. send `amount` Z to pair Z/W with recepient `to`
-    match read_route_state(&deps.storage)? {
-        Some(RouteState {
-            is_done,
-            current_hop,
-            remaining_route:
-                Route {
-                    mut hops,
-                    expected_return,
-                    to,
-                },
-        }) => {
-            let next_hop: Hop = match hops.pop_front() {
-                Some(next_hop) => next_hop,
-                None => return Err(StdError::generic_err("route must be at least 1 hop")),
-            };
+    // hops and the return token can leave out the code hashes of registered tokens
+    for (hops, _) in legs.iter_mut() {
+        fill_code_hashes(&deps.storage, hops)?;
+    }
+    if let Some(ref mut return_token) = return_token {
+        fill_code_hash(&deps.storage, return_token)?;
+    }
 
-            let (from_token_address, from_token_code_hash) = match next_hop.clone().from_token {
-                Token::Snip20(Snip20Data { address, code_hash }) => (address, code_hash),
-                Token::Scrt => {
-                    return Err(StdError::generic_err(
-                        "weird. cannot route via uscrt. uscrt can only be route input token or output token.",
-                        ));
-                }
-            };
+    // a route can't start while another one is being swapped, as the returns of the pairs
+    // would be credited to the wrong route. Routes left over from earlier blocks are abandoned
+    if let Some(active_id) = read_active_route(&deps.storage)? {
+        if let Some(active_route) = read_route_state(&deps.storage, active_id)? {
+            if active_route.started_at >= env.block.height {
+                return Err(StdError::generic_err("another route is in progress"));
+            }
+        }
+    }
 
-            let from_pair_of_current_hop = match current_hop {
-                Some(Hop {
-                    from_token: _,
-                    pair_code_hash: _,
-                    ref pair_address,
-                }) => *pair_address == from,
-                None => false,
-            };
+    let sscrt = read_sscrt(&deps.storage)?;
+    let received_amount: u128 = legs.iter().map(|(_, amount)| amount.u128()).sum();
 
-            if env.message.sender != from_token_address || !from_pair_of_current_hop {
-                return Err(StdError::generic_err(
-                    "route can only be called by receiving the token of the next hop from the previous pair",
-                ));
-            }
+    for (hops, _) in legs.iter() {
+        let first_hop = match hops.front() {
+            Some(first_hop) => first_hop,
+            None => return Err(StdError::generic_err("route must be at least 1 hop")),
+        };
 
-            let mut is_done = false;
-            let mut msgs = vec![];
-            let mut current_hop = Some(next_hop.clone());
-            if hops.len() == 0 {
-                // last hop
-                // 1. set is_done to true for FinalizeRoute
-                // 2. set expected_return for the final swap
-                // 3. set the recipient of the final swap to be the user
-                is_done = true;
-                current_hop = None;
-                msgs.push(snip20::send_msg(
-                    next_hop.clone().pair_address,
-                    amount,
-                    Some(to_binary(&Snip20Swap::Swap {
-                        expected_return,
-                        to: Some(to.clone()),
-                    })?),
-                    None,
-                    256,
-                    from_token_code_hash,
-                    from_token_address,
-                )?);
-            } else {
-                // not last hop
-                // 1. set expected_return to None because we don't care about slippage mid-route
-                // 2. set the recipient of the swap to be this contract (the router)
-                msgs.push(snip20::send_msg(
-                    next_hop.clone().pair_address,
-                    amount,
-                    Some(to_binary(&Snip20Swap::Swap {
-                        expected_return: None,
-                        to: Some(env.contract.address.clone()),
-                    })?),
-                    None,
-                    256,
-                    from_token_code_hash,
-                    from_token_address,
-                )?);
-            }
+        // SCRT can be sent in either form, the router wraps or unwraps it for the first pair
+        let received_first_hop = is_same_token(&first_hop.from_token, &offer_token)
+            || (is_scrt(&first_hop.from_token, &sscrt) && is_scrt(&offer_token, &sscrt));
+        if !received_first_hop {
+            return Err(StdError::generic_err(
+                "route can only be initiated by sending here the token of the first hop",
+            ));
+        }
+    }
 
-            store_route_state(
-                &mut deps.storage,
-                &RouteState {
-                    is_done,
-                    current_hop,
-                    remaining_route: Route {
-                        hops, // hops was mutated earlier when we did `hops.pop_front()`
-                        expected_return,
-                        to,
-                    },
-                },
-            )?;
+    if min_profit.is_some() && return_token.is_none() {
+        // the router collects the return of a cycle, to check its profit
+        return_token = Some(offer_token_with_code_hash(&offer_token, &legs, &sscrt));
+    }
 
-            Ok(HandleResponse {
-                messages: msgs,
-                log: vec![],
-                data: None,
-            })
+    // a route back to the token that was sent is an arbitrage cycle, which must make a profit
+    let is_cycle = match return_token {
+        Some(ref return_token) => {
+            is_same_token(return_token, &offer_token)
+                || (is_scrt(return_token, &sscrt) && is_scrt(&offer_token, &sscrt))
         }
-        None => Err(StdError::generic_err("cannot find route")),
+        None => false,
+    };
+    let cycle = match min_profit {
+        Some(min_profit) if is_cycle => Some(Cycle {
+            input: Uint128(received_amount),
+            min_profit,
+            profit_to: profit_to.unwrap_or_else(|| sender.clone()),
+        }),
+        Some(_) => {
+            return Err(StdError::generic_err(
+                "min_profit only applies to routes back to the token sent",
+            ))
+        }
+        None if is_cycle => {
+            return Err(StdError::generic_err(
+                "cannot route a token to itself without a min_profit",
+            ))
+        }
+        None => None,
+    };
+
+    let tokens = route_tokens(&offer_token, &legs, &return_token, &sscrt);
+
+    // the input of this route is already in the router's balance
+    let mut initial_balances = vec![];
+    for token in tokens {
+        let mut amount = query_router_balance(deps, env, &token)?;
+        if is_same_token(&token, &offer_token) {
+            amount = (amount - Uint128(received_amount))?;
+        }
+        initial_balances.push(InitialBalance { token, amount });
     }
-}
 
-fn finalize_route<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: &Env,
-) -> StdResult<HandleResponse> {
-    match read_route_state(&deps.storage)? {
-        Some(RouteState {
-            is_done,
-            current_hop,
-            remaining_route,
-        }) => {
-            // this function is called only by the route creation function
-            // it is intended to always make sure that the route was completed successfully
-            // otherwise we revert the transaction
+    // the referral fee is taken out of the input of every leg, before anything is swapped
+    let mut referral_msgs = vec![];
+    let mut logs = vec![];
+    if let Some(Referral { address, fee_bps }) = referral {
+        let max_fee_bps = read_max_referral_fee_bps(&deps.storage)?;
+        if fee_bps > max_fee_bps {
+            return Err(StdError::generic_err(format!(
+                "referral fee cannot exceed {} bps",
+                max_fee_bps
+            )));
+        }
 
-            if env.contract.address != env.message.sender {
-                return Err(StdError::unauthorized());
-            }
-            if !is_done {
-                return Err(StdError::generic_err(format!(
-                    "cannot finalize: route is not done: {:?}",
-                    remaining_route
-                )));
+        let mut fee = Uint128::zero();
+        for (_, amount) in legs.iter_mut() {
+            let leg_fee = amount.multiply_ratio(fee_bps as u128, MAX_FEE_BPS as u128);
+            *amount = (*amount - leg_fee)?;
+            if amount.is_zero() {
+                return Err(StdError::generic_err(
+                    "every leg must swap a non-zero amount",
+                ));
             }
-            if remaining_route.hops.len() != 0 {
-                return Err(StdError::generic_err(format!(
-                    "cannot finalize: route still contains hops: {:?}",
-                    remaining_route
-                )));
+            fee = fee + leg_fee;
+        }
+
+        if !fee.is_zero() {
+            let fee_token = offer_token_with_code_hash(&offer_token, &legs, &sscrt);
+            referral_msgs.push(transfer_msg(env, &fee_token, address.clone(), fee)?);
+            add_referral_earnings(&mut deps.storage, &address, &fee_token, fee)?;
+
+            logs.push(log("referrer", address.as_str()));
+            logs.push(log("referral_fee", fee));
+        }
+    }
+
+    // exact-output routes back-calculate their input, and keep the rest of what was sent
+    let refund = match (exact_return, &return_token) {
+        (Some(exact_return), Some(return_token)) => {
+            let refund_token = offer_token_with_code_hash(&offer_token, &legs, &sscrt);
+            if legs.len() != 1 {
+                return Err(StdError::generic_err(
+                    "exact-output routes must have a single leg",
+                ));
             }
-            if current_hop != None {
+
+            let (hops, amount) = &mut legs[0];
+            let required_amount =
+                reverse_simulate_route(deps, hops, return_token, exact_return, &sscrt)?;
+            if required_amount > *amount {
                 return Err(StdError::generic_err(format!(
-                    "cannot finalize: route still processing hops: {:?}",
-                    remaining_route
+                    "route requires an input of {}, but only {} was sent",
+                    required_amount, amount
                 )));
             }
 
-            delete_route_state(&mut deps.storage);
+            let refund_amount = (*amount - required_amount)?;
+            *amount = required_amount;
 
-            if let Some(cashback) = read_cashback(&deps.storage)? {
-                let balance = snip20::balance_query(
-                    &deps.querier,
-                    env.contract.address.clone(),
-                    "SecretSwap Router".into(),
-                    256,
-                    cashback.code_hash.clone(),
-                    cashback.address.clone(),
-                )?;
-
-                let mut messages = vec![];
-                if balance.amount.u128() > 0 {
-                    let msg = snip20::send_msg(
-                        remaining_route.to,
-                        balance.amount,
-                        None,
-                        None,
-                        256,
-                        cashback.code_hash,
-                        cashback.address,
-                    )?;
-                    messages.push(msg);
-                }
+            Some(Refund {
+                token: refund_token,
+                amount: refund_amount,
+            })
+        }
+        _ => None,
+    };
+
+    // the price impact is checked against the return of the route at spot prices,
+    // as simulated before any of its swaps happen
+    let price_impact_min_return = match max_price_impact {
+        Some(max_price_impact) => {
+            if max_price_impact > Decimal::one() {
+                return Err(StdError::generic_err("max_price_impact cannot exceed 1"));
+            }
 
-                Ok(HandleResponse {
-                    messages,
-                    log: vec![],
-                    data: None,
-                })
-            } else {
-                Ok(HandleResponse::default())
+            let mut spot_return = Uint128::zero();
+            for (hops, amount) in legs.iter() {
+                spot_return = spot_return + simulate_route(deps, hops, *amount)?.spot_return;
             }
+            Some((spot_return - spot_return * max_price_impact)?)
         }
-        None => Err(StdError::generic_err("no route to finalize")),
-    }
-}
+        None => None,
+    };
 
-fn check_owner<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: &Env,
-) -> StdResult<()> {
-    let owner = read_owner(&deps.storage)?;
-    if owner != env.message.sender {
-        Err(StdError::unauthorized())
-    } else {
-        Ok(())
-    }
-}
+    let mut route = RouteState {
+        id: next_route_id(&mut deps.storage)?,
+        started_at: env.block.height,
+        legs: legs
+            .into_iter()
+            .map(|(hops, amount)| LegState {
+                is_done: false,
+                current_hop: None,
+                remaining_hops: hops,
+                offer_amount: amount,
+                native_balance: None,
+            })
+            .collect(),
+        offer_token,
+        expected_return,
+        price_impact_min_return,
+        to,
+        sender,
+        collect: return_token.map(|token| CollectedReturn {
+            token,
+            amount: Uint128::zero(),
+            native_amount: Uint128::zero(),
+            exact_amount: exact_return,
+        }),
+        refund,
+        cycle,
+        initial_balances,
+        swapped_hops: vec![],
+    };
 
-fn register_tokens<S: Storage, A: Api, Q: Querier>(
+    // legs are swapped one after the other, each one starts when the one before it is done
+    let mut msgs = start_next_leg(deps, env, &mut route, &sscrt)?;
+    // the fee is paid out after the swaps, as a uscrt fee would otherwise leave the router
+    // between the uscrt snapshot of the first hop and its ContinueRoute
+    msgs.extend(referral_msgs);
+
+    store_route_state(&mut deps.storage, &route)?;
+    // the returns of the pairs are credited to this route until it's finalized
+    store_active_route(&mut deps.storage, route.id)?;
+
+    msgs.push(
+        // finalize the route at the end, to make sure the route was completed successfully
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.clone(),
+            callback_code_hash: env.contract_code_hash.clone(),
+            msg: to_binary(&HandleMsg::FinalizeRoute { route_id: route.id })?,
+            send: vec![],
+        }),
+    );
+
+    Ok(HandleResponse {
+        messages: msgs,
+        log: logs,
+        data: None,
+    })
+}
+
+/// The received token only carries its address, its code hash is taken from the route
+fn offer_token_with_code_hash(
+    offer_token: &Token,
+    legs: &[(VecDeque<Hop>, Uint128)],
+    sscrt: &Option<Snip20Data>,
+) -> Token {
+    let address = match offer_token {
+        Token::Snip20(Snip20Data { address, .. }) => address,
+        _ => return offer_token.clone(),
+    };
+
+    let code_hash = legs
+        .iter()
+        .filter_map(|(hops, _)| match hops.front() {
+            Some(Hop {
+                from_token: Token::Snip20(first),
+                ..
+            }) if first.address == *address => Some(first.code_hash.clone()),
+            _ => None,
+        })
+        .chain(
+            sscrt
+                .iter()
+                .filter(|sscrt| sscrt.address == *address)
+                .map(|sscrt| sscrt.code_hash.clone()),
+        )
+        .next()
+        .unwrap_or_default();
+
+    Token::Snip20(Snip20Data {
+        address: address.clone(),
+        code_hash,
+    })
+}
+
+fn transfer_msg(env: &Env, token: &Token, to: HumanAddr, amount: Uint128) -> StdResult<CosmosMsg> {
+    match token {
+        Token::Snip20(Snip20Data { address, code_hash }) => {
+            snip20::transfer_msg(to, amount, None, 256, code_hash.clone(), address.clone())
+        }
+        Token::Scrt => Ok(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address.clone(),
+            to_address: to,
+            amount: vec![Coin::new(amount.u128(), "uscrt")],
+        })),
+        Token::Native { denom } => Ok(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address.clone(),
+            to_address: to,
+            amount: vec![Coin::new(amount.u128(), denom)],
+        })),
+    }
+}
+
+fn query_router_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    token: &Token,
+) -> StdResult<Uint128> {
+    match token {
+        Token::Snip20(Snip20Data { address, code_hash }) => Ok(snip20::balance_query(
+            &deps.querier,
+            env.contract.address.clone(),
+            "SecretSwap Router".into(),
+            256,
+            code_hash.clone(),
+            address.clone(),
+        )?
+        .amount),
+        Token::Scrt => query_balance(deps, &env.contract.address, "uscrt".into()),
+        Token::Native { denom } => query_balance(deps, &env.contract.address, denom.clone()),
+    }
+}
+
+/// Every token that the router may hold while swapping a route, SCRT in both of its forms
+fn route_tokens(
+    offer_token: &Token,
+    legs: &[(VecDeque<Hop>, Uint128)],
+    return_token: &Option<Token>,
+    sscrt: &Option<Snip20Data>,
+) -> Vec<Token> {
+    let mut tokens: Vec<Token> = vec![];
+    let candidates = std::iter::once(offer_token_with_code_hash(offer_token, legs, sscrt))
+        .chain(
+            legs.iter()
+                .flat_map(|(hops, _)| hops.iter().map(|hop| hop.from_token.clone())),
+        )
+        .chain(return_token.iter().cloned());
+
+    for token in candidates {
+        let forms = match sscrt {
+            Some(sscrt_data) if is_scrt(&token, sscrt) => {
+                vec![Token::Scrt, Token::Snip20(sscrt_data.clone())]
+            }
+            _ => vec![token],
+        };
+        for token in forms {
+            if !tokens.iter().any(|t| is_same_token(t, &token)) {
+                tokens.push(token);
+            }
+        }
+    }
+
+    tokens
+}
+
+fn start_next_leg<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    route: &mut RouteState,
+    sscrt: &Option<Snip20Data>,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut leg = match route.legs.pop_front() {
+        Some(leg) => leg,
+        None => return Ok(vec![]),
+    };
+
+    let amount = leg.offer_amount;
+    let offer_token = route.offer_token.clone();
+    let (mut msgs, is_leg_finished) =
+        execute_next_hop(deps, env, route, sscrt, &mut leg, amount, &offer_token)?;
+
+    if is_leg_finished {
+        msgs.extend(start_next_leg(deps, env, route, sscrt)?);
+    } else {
+        route.legs.push_front(leg);
+    }
+
+    Ok(msgs)
+}
+
+/// Sends `amount` to the pair of the next hop of `leg`, and makes it the leg's current hop.
+/// `holding` is the form in which the router holds `amount`, which only differs from the
+/// hop's token when SCRT has to be wrapped or unwrapped first.
+/// Returns whether the leg is finished, i.e. whether nothing is going to come back from the pair
+fn execute_next_hop<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    route: &mut RouteState,
+    sscrt: &Option<Snip20Data>,
+    leg: &mut LegState,
+    amount: Uint128,
+    holding: &Token,
+) -> StdResult<(Vec<CosmosMsg>, bool)> {
+    let hop: Hop = match leg.remaining_hops.pop_front() {
+        Some(hop) => hop,
+        None => return Err(StdError::generic_err("route must be at least 1 hop")),
+    };
+
+    let mut msgs = vec![];
+
+    match (&hop.from_token, holding) {
+        (Token::Scrt, Token::Snip20(_)) => {
+            msgs.push(sscrt_msg(sscrt, &SscrtMsg::Redeem { amount }, vec![])?)
+        }
+        (Token::Snip20(_), Token::Scrt) => msgs.push(sscrt_msg(
+            sscrt,
+            &SscrtMsg::Deposit {},
+            vec![Coin::new(amount.u128(), "uscrt")],
+        )?),
+        _ => {}
+    }
+
+    let is_last_hop = leg.remaining_hops.is_empty();
+
+    let (expected_return, to, callback) = if is_last_hop && route.collect.is_none() {
+        // the last hop pays out straight to the user, with the slippage checks of the whole route
+        (
+            max_return(
+                hop.min_return,
+                max_return(route.expected_return, route.price_impact_min_return),
+            ),
+            route.to.clone(),
+            None,
+        )
+    } else {
+        // mid-route only the hop's own min_return is enforced
+        // set the recepient of the swap to be this contract (the router),
+        // which tells the return apart by the route id that comes back with it
+        (
+            hop.min_return,
+            env.contract.address.clone(),
+            Some(to_binary(&ReceiveMsg::HopReturn { route_id: route.id })?),
+        )
+    };
+
+    msgs.push(match hop.from_token {
+        Token::Snip20(Snip20Data {
+            ref address,
+            ref code_hash,
+        }) => snip20::send_msg(
+            hop.pair_address.clone(),
+            amount,
+            // build swap msg for the next hop
+            Some(to_binary(&Snip20Swap::Swap {
+                expected_return,
+                to: Some(to),
+                callback,
+            })?),
+            None,
+            256,
+            code_hash.clone(),
+            address.clone(),
+        )?,
+        Token::Scrt | Token::Native { .. } => {
+            let denom = native_denom(&hop.from_token).unwrap_or_default();
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: hop.pair_address.clone(),
+                callback_code_hash: hop.pair_code_hash.clone(),
+                msg: to_binary(&NativeSwap::Swap {
+                    offer_asset: Asset {
+                        amount,
+                        info: AssetInfo::NativeToken {
+                            denom: denom.clone(),
+                        },
+                    },
+                    expected_return,
+                    to: Some(to),
+                    callback,
+                })?,
+                send: vec![Coin::new(amount.u128(), &denom)],
+            })
+        }
+    });
+
+    // the native denom that the pair pays out, if the router is the one to receive it
+    let return_token = match leg.remaining_hops.front() {
+        Some(next_hop) => Some(&next_hop.from_token),
+        None => route.collect.as_ref().map(|collect| &collect.token),
+    };
+    let return_denom = match return_token {
+        Some(token) if is_scrt(token, sscrt) => Some("uscrt".to_string()),
+        Some(token) => native_denom(token),
+        None => None,
+    };
+
+    leg.native_balance = None;
+    if let Some(denom) = return_denom {
+        // a pair pays out native tokens without calling back the router, so right after the swap
+        // the router checks by how much its balance went up
+        let mut balance = query_balance(deps, &env.contract.address, denom.clone())?;
+        if native_denom(holding).as_ref() == Some(&denom) {
+            // `amount` is still in the router's balance, but it's about to be sent away
+            balance = (balance - amount)?;
+        }
+        leg.native_balance = Some(Coin::new(balance.u128(), &denom));
+        msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.clone(),
+            callback_code_hash: env.contract_code_hash.clone(),
+            msg: to_binary(&HandleMsg::ContinueRoute { route_id: route.id })?,
+            send: vec![],
+        }));
+    }
+
+    route.swapped_hops.push(SwappedHop {
+        pair_address: hop.pair_address.clone(),
+        from_token: hop.from_token.clone(),
+        amount,
+    });
+
+    leg.is_done = is_last_hop;
+    leg.current_hop = Some(hop);
+
+    // a collected return comes back to the router, otherwise it leaves the router for good
+    Ok((msgs, is_last_hop && route.collect.is_none()))
+}
+
+fn handle_hop<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    from: HumanAddr,
+    amount: Uint128,
+    route_id: u64,
+) -> StdResult<HandleResponse> {
+    // This is a receive msg somewhere along the route
+    // 1. load route from state (Y/Z -> Z/W)
+    // 2. save the remaining route to state (Z/W)
+    // 3. send `amount` Y to pair Y/Z
+
+    // 1'. load route from state (Z/W)
+    // 2'. this is the last hop so remove the leg from the route state
+    // 3'. send `amount` Z to pair Z/W with recepient `to`
+    let mut route = load_active_route(&deps.storage, route_id)?;
+    let sscrt = read_sscrt(&deps.storage)?;
+
+    // legs run one after the other, so the receive is always for the first leg that's not finished
+    let mut leg = match route.legs.pop_front() {
+        Some(leg) => leg,
+        None => return Err(StdError::generic_err("cannot find route")),
+    };
+
+    let from_pair_of_current_hop = match leg.current_hop {
+        Some(Hop {
+            ref pair_address, ..
+        }) => *pair_address == from,
+        None => false,
+    };
+
+    let expected_token = if leg.is_done {
+        match route.collect {
+            Some(CollectedReturn { ref token, .. }) => token.clone(),
+            None => return Err(StdError::generic_err("cannot find route")),
+        }
+    } else {
+        match leg.remaining_hops.front() {
+            Some(next_hop) => next_hop.from_token.clone(),
+            None => return Err(StdError::generic_err("route must be at least 1 hop")),
+        }
+    };
+
+    let holding = Token::Snip20(Snip20Data {
+        address: env.message.sender.clone(),
+        code_hash: String::new(),
+    });
+    // SCRT may come back from the pair in either form
+    let is_expected_token = is_same_token(&holding, &expected_token)
+        || (is_scrt(&expected_token, &sscrt) && is_scrt(&holding, &sscrt));
+
+    if !is_expected_token || !from_pair_of_current_hop {
+        return Err(StdError::generic_err(
+            "route can only be called by receiving the token of the next hop from the previous pair",
+        ));
+    }
+
+    // the pair didn't pay out native tokens after all
+    leg.native_balance = None;
+
+    let msgs = hop_returned(deps, env, &mut route, &sscrt, leg, amount, holding)?;
+
+    store_route_state(&mut deps.storage, &route)?;
+
+    Ok(HandleResponse {
+        messages: msgs,
+        log: vec![],
+        data: None,
+    })
+}
+
+fn continue_route<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    route_id: u64,
+) -> StdResult<HandleResponse> {
+    // This is called by the router right after a swap that may have paid out native tokens to it
+
+    if env.contract.address != env.message.sender {
+        return Err(StdError::unauthorized());
+    }
+
+    let mut route = load_active_route(&deps.storage, route_id)?;
+    let sscrt = read_sscrt(&deps.storage)?;
+
+    let mut leg = match route.legs.pop_front() {
+        Some(leg) => leg,
+        None => return Ok(HandleResponse::default()),
+    };
+    let native_balance = match leg.native_balance.take() {
+        Some(native_balance) => native_balance,
+        // the return came back as sSCRT through Receive, and the route already moved on
+        None => return Ok(HandleResponse::default()),
+    };
+
+    let amount = (query_balance(deps, &env.contract.address, native_balance.denom.clone())?
+        - native_balance.amount)?;
+    if amount.is_zero() {
+        return Err(StdError::generic_err(format!(
+            "route can only be continued after receiving {} from the previous pair",
+            native_balance.denom
+        )));
+    }
+
+    let holding = native_token(&native_balance.denom);
+    let msgs = hop_returned(deps, env, &mut route, &sscrt, leg, amount, holding)?;
+
+    store_route_state(&mut deps.storage, &route)?;
+
+    Ok(HandleResponse {
+        messages: msgs,
+        log: vec![],
+        data: None,
+    })
+}
+
+/// Moves `leg` forward with the `amount` that came back from its current hop
+fn hop_returned<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    route: &mut RouteState,
+    sscrt: &Option<Snip20Data>,
+    mut leg: LegState,
+    amount: Uint128,
+    holding: Token,
+) -> StdResult<Vec<CosmosMsg>> {
+    if leg.is_done {
+        // this is the return of a leg, which the router collects until FinalizeRoute
+        if let Some(collect) = route.collect.as_mut() {
+            match holding {
+                Token::Scrt => collect.native_amount = collect.native_amount + amount,
+                _ => collect.amount = collect.amount + amount,
+            }
+        }
+
+        return start_next_leg(deps, env, route, sscrt);
+    }
+
+    let (mut msgs, is_leg_finished) =
+        execute_next_hop(deps, env, route, sscrt, &mut leg, amount, &holding)?;
+
+    if is_leg_finished {
+        msgs.extend(start_next_leg(deps, env, route, sscrt)?);
+    } else {
+        route.legs.push_front(leg);
+    }
+
+    Ok(msgs)
+}
+
+fn finalize_route<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    route_id: u64,
+) -> StdResult<HandleResponse> {
+    // this function is called only by the route creation function
+    // it is intended to always make sure that the route was completed successfully
+    // otherwise we revert the transaction
+
+    if env.contract.address != env.message.sender {
+        return Err(StdError::unauthorized());
+    }
+
+    let route = load_active_route(&deps.storage, route_id)?;
+    if !route.legs.is_empty() {
+        return Err(StdError::generic_err(format!(
+            "cannot finalize: route is not done: {:?}",
+            route.legs
+        )));
+    }
+
+    delete_route_state(&mut deps.storage, route.id)?;
+
+    let mut messages = vec![];
+    // amounts that the messages of this function are about to send out of the router
+    let mut pending: Vec<(Token, Uint128)> = vec![];
+
+    if let Some(collect) = route.collect {
+        let sscrt = read_sscrt(&deps.storage)?;
+        let return_amount = collect.amount + collect.native_amount;
+
+        if let Some(expected_return) = route.expected_return {
+            if return_amount < expected_return {
+                return Err(StdError::generic_err(format!(
+                    "route return {} is less than expected_return {}",
+                    return_amount, expected_return
+                )));
+            }
+        }
+        if let Some(price_impact_min_return) = route.price_impact_min_return {
+            if return_amount < price_impact_min_return {
+                return Err(StdError::generic_err(format!(
+                    "route return {} exceeds max_price_impact, the minimum is {}",
+                    return_amount, price_impact_min_return
+                )));
+            }
+        }
+
+        // convert the whole return to the requested form of SCRT
+        match collect.token {
+            Token::Snip20(_) | Token::Native { .. } => {
+                if !collect.native_amount.is_zero() {
+                    messages.push(sscrt_msg(
+                        &sscrt,
+                        &SscrtMsg::Deposit {},
+                        vec![Coin::new(collect.native_amount.u128(), "uscrt")],
+                    )?);
+                }
+            }
+            Token::Scrt => {
+                if !collect.amount.is_zero() {
+                    messages.push(sscrt_msg(
+                        &sscrt,
+                        &SscrtMsg::Redeem {
+                            amount: collect.amount,
+                        },
+                        vec![],
+                    )?);
+                }
+            }
+        }
+
+        let (payout_amount, surplus_to) = match route.cycle {
+            Some(ref cycle) => {
+                if return_amount < cycle.input + cycle.min_profit {
+                    return Err(StdError::generic_err(format!(
+                        "route returned {} for an input of {}, short of min_profit {}",
+                        return_amount, cycle.input, cycle.min_profit
+                    )));
+                }
+                // the input goes back to `to`, the profit to its own recipient
+                (cycle.input, cycle.profit_to.clone())
+            }
+            // exact-output routes may return a bit more than asked for, it belongs to the sender
+            None => (
+                collect.exact_amount.unwrap_or(return_amount),
+                route.sender.clone(),
+            ),
+        };
+
+        messages.push(transfer_msg(
+            env,
+            &collect.token,
+            route.to.clone(),
+            payout_amount,
+        )?);
+
+        let surplus = (return_amount - payout_amount)?;
+        if !surplus.is_zero() {
+            messages.push(transfer_msg(env, &collect.token, surplus_to, surplus)?);
+        }
+
+        // until the messages above are executed, the return is still in the router's balance
+        let held_token = match (&collect.token, &sscrt) {
+            (Token::Scrt, Some(sscrt)) => Token::Snip20(sscrt.clone()),
+            (token, _) => token.clone(),
+        };
+        pending.push((held_token, collect.amount));
+        pending.push((Token::Scrt, collect.native_amount));
+    }
+
+    if let Some(refund) = route.refund {
+        if !refund.amount.is_zero() {
+            messages.push(transfer_msg(
+                env,
+                &refund.token,
+                route.sender.clone(),
+                refund.amount,
+            )?);
+            pending.push((refund.token, refund.amount));
+        }
+    }
+
+    // rounding dust that the route left with the router goes back to the sender
+    for initial_balance in route.initial_balances {
+        let balance = query_router_balance(deps, env, &initial_balance.token)?;
+        let dust = (balance
+            - (initial_balance.amount + pending_amount(&pending, &initial_balance.token)))
+        .unwrap_or_else(|_| Uint128::zero());
+
+        if !dust.is_zero() {
+            messages.push(transfer_msg(
+                env,
+                &initial_balance.token,
+                route.sender.clone(),
+                dust,
+            )?);
+        }
+    }
+
+    // reward programs pay the sender for the hops of this route
+    for program_id in read_reward_program_ids(&deps.storage)? {
+        let mut program = match read_reward_program(&deps.storage, program_id)? {
+            Some(program) => program,
+            None => continue,
+        };
+        if env.block.time >= program.program.end_time {
+            continue;
+        }
+
+        // rewards are paid out of the funds escrowed for the program, until they run out
+        let reward_token = Token::Snip20(program.program.reward_token.clone());
+        let reward = std::cmp::min(
+            route_reward(&program.program, &route.swapped_hops),
+            program.escrow,
+        );
+        if reward.is_zero() {
+            continue;
+        }
+
+        program.escrow = (program.escrow - reward)?;
+        program.paid_out = program.paid_out + reward;
+        store_reward_program(&mut deps.storage, &program)?;
+        add_reward_earnings(&mut deps.storage, &route.sender, &program, reward)?;
+
+        messages.push(transfer_msg(
+            env,
+            &reward_token,
+            route.sender.clone(),
+            reward,
+        )?);
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: None,
+    })
+}
+
+/// How much of `token` the router is about to send out
+fn pending_amount(pending: &[(Token, Uint128)], token: &Token) -> Uint128 {
+    Uint128(
+        pending
+            .iter()
+            .filter(|(pending_token, _)| is_same_token(pending_token, token))
+            .map(|(_, amount)| amount.u128())
+            .sum(),
+    )
+}
+
+fn max_return(a: Option<Uint128>, b: Option<Uint128>) -> Option<Uint128> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(std::cmp::max(a, b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Whether `token` is SCRT, in its native form or as the configured sSCRT
+fn is_scrt(token: &Token, sscrt: &Option<Snip20Data>) -> bool {
+    match (token, sscrt) {
+        (Token::Scrt, _) => true,
+        (Token::Snip20(Snip20Data { address, .. }), Some(sscrt)) => *address == sscrt.address,
+        _ => false,
+    }
+}
+
+fn sscrt_msg(sscrt: &Option<Snip20Data>, msg: &SscrtMsg, send: Vec<Coin>) -> StdResult<CosmosMsg> {
+    match sscrt {
+        Some(Snip20Data { address, code_hash }) => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: address.clone(),
+            callback_code_hash: code_hash.clone(),
+            msg: to_binary(msg)?,
+            send,
+        })),
+        None => Err(StdError::generic_err(
+            "cannot wrap or unwrap SCRT: sSCRT is not set",
+        )),
+    }
+}
+
+fn fund_reward_program<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    token: Token,
+    program_id: u64,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let mut program = match read_reward_program(&deps.storage, program_id)? {
+        Some(program) => program,
+        None => {
+            return Err(StdError::generic_err(format!(
+                "cannot find reward program {}",
+                program_id
+            )))
+        }
+    };
+
+    if !is_same_token(&token, &Token::Snip20(program.program.reward_token.clone())) {
+        return Err(StdError::generic_err(format!(
+            "reward program {} can only be funded with its reward token",
+            program_id
+        )));
+    }
+    if env.block.time >= program.program.end_time {
+        return Err(StdError::generic_err(format!(
+            "reward program {} has ended",
+            program_id
+        )));
+    }
+
+    program.escrow = program.escrow + amount;
+    if program.escrow + program.paid_out > program.program.budget {
+        return Err(StdError::generic_err(format!(
+            "funding exceeds the budget of reward program {}",
+            program_id
+        )));
+    }
+    store_reward_program(&mut deps.storage, &program)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "fund_reward_program"),
+            log("program_id", program_id),
+            log("amount", amount),
+        ],
+        data: None,
+    })
+}
+
+fn batch_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    sender: HumanAddr,
+    mut token: Token,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    // `sender` is only vouched for by registered tokens, any other contract could make up
+    // a Receive from someone else and write into their batch
+    if let Token::Snip20(Snip20Data { ref address, .. }) = token {
+        if read_token(&deps.storage, address)?.is_none() {
+            return Err(StdError::generic_err(format!(
+                "{} is not registered, only registered tokens can be deposited",
+                address
+            )));
+        }
+    }
+    // the deposit may have to be sent back, so it needs the token's code hash
+    fill_code_hash(&deps.storage, &mut token)?;
+
+    // deposits stay in the router's balance until the batch is executed or cancelled,
+    // only the sender's batch can spend them
+    let mut batch = read_batch(&deps.storage, &sender)?.unwrap_or_default();
+    if !batch.routes.is_empty() {
+        return Err(StdError::generic_err("batch is already being executed"));
+    }
+    add_batch_deposit(&mut batch, token, amount);
+    store_batch(&mut deps.storage, &sender, &batch)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "batch_deposit"), log("amount", amount)],
+        data: None,
+    })
+}
+
+fn add_batch_deposit(batch: &mut Batch, token: Token, amount: Uint128) {
+    match batch
+        .deposits
+        .iter_mut()
+        .find(|deposit| is_same_token(&deposit.token, &token))
+    {
+        Some(deposit) => deposit.amount = deposit.amount + amount,
+        None => batch.deposits.push(BatchDeposit { token, amount }),
+    }
+}
+
+/// Queues the routes of a batch, then has the router start them one after the other.
+/// Each route is finalized on its own, and the batch is finalized once they are all done
+fn execute_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    routes: Vec<BatchRoute>,
+) -> StdResult<HandleResponse> {
+    let sender = env.message.sender.clone();
+    let mut batch = read_batch(&deps.storage, &sender)?.unwrap_or_default();
+    if !batch.routes.is_empty() {
+        return Err(StdError::generic_err("batch is already being executed"));
+    }
+    if routes.is_empty() {
+        return Err(StdError::generic_err("batch must be at least 1 route"));
+    }
+
+    for coin in env.message.sent_funds.iter() {
+        add_batch_deposit(&mut batch, native_token(&coin.denom), coin.amount);
+    }
+
+    let sscrt = read_sscrt(&deps.storage)?;
+    for BatchRoute { amount, mut route } in routes {
+        if amount.is_zero() {
+            return Err(StdError::generic_err(
+                "every route must swap a non-zero amount",
+            ));
+        }
+        fill_code_hashes(&deps.storage, &mut route.hops)?;
+        let first_token = match route.hops.front() {
+            Some(first_hop) => first_hop.from_token.clone(),
+            None => return Err(StdError::generic_err("route must be at least 1 hop")),
+        };
+
+        // SCRT deposits can go into routes from either form of SCRT
+        let deposit = batch
+            .deposits
+            .iter_mut()
+            .find(|deposit| {
+                is_same_token(&deposit.token, &first_token)
+                    || (is_scrt(&deposit.token, &sscrt) && is_scrt(&first_token, &sscrt))
+            })
+            .ok_or_else(|| {
+                StdError::generic_err("a route starts with a token that was not deposited")
+            })?;
+        deposit.amount = (deposit.amount - amount)
+            .map_err(|_| StdError::generic_err("routes swap more than was deposited"))?;
+
+        batch.routes.push_back(QueuedRoute {
+            offer_token: deposit.token.clone(),
+            amount,
+            route,
+        });
+    }
+
+    if batch
+        .deposits
+        .iter()
+        .any(|deposit| !deposit.amount.is_zero())
+    {
+        return Err(StdError::generic_err(
+            "routes must swap everything that was deposited",
+        ));
+    }
+    batch.deposits.clear();
+
+    let mut msgs = vec![];
+    for _ in batch.routes.iter() {
+        msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.clone(),
+            callback_code_hash: env.contract_code_hash.clone(),
+            msg: to_binary(&HandleMsg::StartBatchRoute {
+                sender: sender.clone(),
+            })?,
+            send: vec![],
+        }));
+    }
+    msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: env.contract.address.clone(),
+        callback_code_hash: env.contract_code_hash.clone(),
+        msg: to_binary(&HandleMsg::FinalizeBatch {
+            sender: sender.clone(),
+        })?,
+        send: vec![],
+    }));
+
+    store_batch(&mut deps.storage, &sender, &batch)?;
+
+    Ok(HandleResponse {
+        messages: msgs,
+        log: vec![
+            log("action", "execute_batch"),
+            log("routes", batch.routes.len()),
+        ],
+        data: None,
+    })
+}
+
+fn start_batch_route<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    sender: HumanAddr,
+) -> StdResult<HandleResponse> {
+    if env.contract.address != env.message.sender {
+        return Err(StdError::unauthorized());
+    }
+
+    let mut batch = match read_batch(&deps.storage, &sender)? {
+        Some(batch) => batch,
+        None => return Err(StdError::generic_err("cannot find batch")),
+    };
+    let QueuedRoute {
+        offer_token,
+        amount,
+        route,
+    } = match batch.routes.pop_front() {
+        Some(queued_route) => queued_route,
+        None => return Err(StdError::generic_err("batch has no routes left")),
+    };
+    store_batch(&mut deps.storage, &sender, &batch)?;
+
+    let Route {
+        hops,
+        to,
+        expected_return,
+        return_token,
+        max_price_impact,
+        referral,
+        min_profit,
+        profit_to,
+    } = route;
+
+    start_route(
+        deps,
+        env,
+        offer_token,
+        vec![(hops, amount)],
+        RouteOptions {
+            expected_return,
+            to,
+            return_token,
+            max_price_impact,
+            referral,
+            exact_return: None,
+            sender,
+            min_profit,
+            profit_to,
+        },
+    )
+}
+
+fn finalize_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    sender: HumanAddr,
+) -> StdResult<HandleResponse> {
+    if env.contract.address != env.message.sender {
+        return Err(StdError::unauthorized());
+    }
+
+    match read_batch(&deps.storage, &sender)? {
+        Some(batch) if batch.routes.is_empty() && batch.deposits.is_empty() => {}
+        _ => return Err(StdError::generic_err("cannot finalize: batch is not done")),
+    }
+    delete_batch(&mut deps.storage, &sender);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "finalize_batch")],
+        data: None,
+    })
+}
+
+fn cancel_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<HandleResponse> {
+    let sender = env.message.sender.clone();
+    let batch = match read_batch(&deps.storage, &sender)? {
+        Some(batch) => batch,
+        None => return Err(StdError::generic_err("cannot find batch")),
+    };
+    if !batch.routes.is_empty() {
+        return Err(StdError::generic_err("batch is already being executed"));
+    }
+    delete_batch(&mut deps.storage, &sender);
+
+    let mut msgs = vec![];
+    for deposit in batch.deposits {
+        msgs.push(transfer_msg(
+            env,
+            &deposit.token,
+            sender.clone(),
+            deposit.amount,
+        )?);
+    }
+
+    Ok(HandleResponse {
+        messages: msgs,
+        log: vec![log("action", "cancel_batch")],
+        data: None,
+    })
+}
+
+/// Loads the route that is being swapped right now.
+/// Hop returns and the router's own callbacks name the route they were sent for,
+/// anything sent for another route is rejected
+fn load_active_route<S: Storage>(storage: &S, route_id: u64) -> StdResult<RouteState> {
+    if read_active_route(storage)? != Some(route_id) {
+        return Err(StdError::generic_err("cannot find route"));
+    }
+
+    match read_route_state(storage, route_id)? {
+        Some(route) => Ok(route),
+        None => Err(StdError::generic_err("cannot find route")),
+    }
+}
+
+/// Removes the state of routes that were abandoned without being finalized.
+/// Routes always complete within the transaction that started them, so anyone can clear
+/// a route from an earlier block, and the owner can clear any route
+fn clear_routes<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    route_ids: Vec<u64>,
+) -> StdResult<HandleResponse> {
+    let is_owner = read_owner(&deps.storage)? == env.message.sender;
+
+    for route_id in route_ids.iter() {
+        let route = match read_route_state(&deps.storage, *route_id)? {
+            Some(route) => route,
+            None => {
+                return Err(StdError::generic_err(format!(
+                    "cannot find route {}",
+                    route_id
+                )))
+            }
+        };
+
+        if !is_owner && route.started_at >= env.block.height {
+            return Err(StdError::unauthorized());
+        }
+
+        delete_route_state(&mut deps.storage, *route_id)?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "clear_routes"),
+            log("route_ids", format!("{:?}", route_ids)),
+        ],
+        data: None,
+    })
+}
+
+fn load_factory<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<SecretContract> {
+    read_factory(&deps.storage)?
+        .ok_or_else(|| StdError::generic_err("route discovery requires a factory to be set"))
+}
+
+fn check_owner<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<()> {
+    let owner = read_owner(&deps.storage)?;
+    if owner != env.message.sender {
+        Err(StdError::unauthorized())
+    } else {
+        Ok(())
+    }
+}
+
+fn register_tokens<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: &Env,
     tokens: Vec<Snip20Data>,
 ) -> StdResult<Vec<CosmosMsg>> {
-    let mut registered_tokens = read_tokens(&deps.storage)?;
     let mut output_msgs = vec![];
 
     for token in tokens {
+        if token.code_hash.is_empty() {
+            return Err(StdError::generic_err(format!(
+                "cannot register {} without its code hash",
+                token.address
+            )));
+        }
+        // registering a token again overwrites its code hash, e.g. to correct a wrong one
+        if let Some(registered) = read_token(&deps.storage, &token.address)? {
+            if registered.code_hash == token.code_hash {
+                continue;
+            }
+        }
+        store_token(&mut deps.storage, &token)?;
+
         let address = token.address;
         let code_hash = token.code_hash;
 
-        if registered_tokens.contains(&address) {
-            continue;
-        }
-        registered_tokens.push(address.clone());
-
         output_msgs.push(snip20::register_receive_msg(
             env.contract_code_hash.clone(),
             None,
@@ -496,19 +1831,138 @@ fn register_tokens<S: Storage, A: Api, Q: Querier>(
         )?);
     }
 
-    store_tokens(&mut deps.storage, &registered_tokens)?;
-
     return Ok(output_msgs);
 }
 
+/// Fills in the code hashes that were left out of a route, from the registered tokens
+fn fill_code_hashes<S: Storage>(storage: &S, hops: &mut VecDeque<Hop>) -> StdResult<()> {
+    for hop in hops.iter_mut() {
+        fill_code_hash(storage, &mut hop.from_token)?;
+    }
+    Ok(())
+}
+
+fn fill_code_hash<S: Storage>(storage: &S, token: &mut Token) -> StdResult<()> {
+    if let Token::Snip20(Snip20Data { address, code_hash }) = token {
+        if code_hash.is_empty() {
+            *code_hash = match read_token(storage, address)? {
+                Some(registered) => registered.code_hash,
+                None => {
+                    return Err(StdError::generic_err(format!(
+                        "the code hash of {} is required, as it is not registered",
+                        address
+                    )))
+                }
+            };
+        }
+    }
+    Ok(())
+}
+
 pub fn query<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     msg: QueryMsg,
 ) -> StdResult<Binary> {
     match msg {
-        QueryMsg::SupportedTokens {} => {
-            let tokens = read_tokens(&deps.storage)?;
-            Ok(to_binary(&tokens)?)
+        QueryMsg::SupportedTokens { start_after, limit } => {
+            let tokens = read_tokens_page(&deps.storage, start_after, limit)?;
+            Ok(to_binary(&SupportedTokensResponse { tokens })?)
         }
+        QueryMsg::BestRoute {
+            from_token,
+            to_token,
+            offer_amount,
+            max_hops,
+        } => {
+            let factory = load_factory(deps)?;
+            Ok(to_binary(&find_best_route(
+                deps,
+                &factory,
+                &from_token,
+                &to_token,
+                offer_amount,
+                max_hops,
+            )?)?)
+        }
+        QueryMsg::OpenRoutes {} => {
+            let mut routes = vec![];
+            for route_id in read_open_routes(&deps.storage)? {
+                if let Some(route) = read_route_state(&deps.storage, route_id)? {
+                    routes.push(OpenRoute {
+                        route_id,
+                        started_at: route.started_at,
+                    });
+                }
+            }
+            Ok(to_binary(&OpenRoutesResponse { routes })?)
+        }
+        QueryMsg::SimulateRoute {
+            mut hops,
+            offer_amount,
+        } => {
+            fill_code_hashes(&deps.storage, &mut hops)?;
+            Ok(to_binary(&simulate_route(deps, &hops, offer_amount)?)?)
+        }
+        QueryMsg::ReverseSimulateRoute {
+            mut hops,
+            mut return_token,
+            return_amount,
+        } => {
+            fill_code_hashes(&deps.storage, &mut hops)?;
+            fill_code_hash(&deps.storage, &mut return_token)?;
+            let sscrt = read_sscrt(&deps.storage)?;
+            let offer_amount =
+                reverse_simulate_route(deps, &hops, &return_token, return_amount, &sscrt)?;
+            Ok(to_binary(&ReverseSimulateRouteResponse { offer_amount })?)
+        }
+        QueryMsg::RewardPrograms {} => {
+            let mut programs = vec![];
+            for program_id in read_reward_program_ids(&deps.storage)? {
+                if let Some(program) = read_reward_program(&deps.storage, program_id)? {
+                    programs.push(RewardProgramInfo {
+                        program_id,
+                        remaining_budget: (program.program.budget - program.paid_out)?,
+                        escrow: program.escrow,
+                        program: program.program,
+                    });
+                }
+            }
+            Ok(to_binary(&RewardProgramsResponse { programs })?)
+        }
+        QueryMsg::RewardEarnings { address, key } => {
+            check_key(deps, &address, &key)?;
+
+            Ok(to_binary(&RewardEarningsResponse {
+                earnings: read_reward_earnings(&deps.storage, &address)?,
+            })?)
+        }
+        QueryMsg::ReferralEarnings { address, key } => {
+            check_key(deps, &address, &key)?;
+
+            Ok(to_binary(&ReferralEarningsResponse {
+                earnings: read_referral_earnings(&deps.storage, &address)?,
+            })?)
+        }
+    }
+}
+
+fn check_key<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    key: &str,
+) -> StdResult<()> {
+    let is_key_valid = match read_viewing_key(&deps.storage, address)? {
+        Some(hashed_key) => check_viewing_key(key, &hashed_key),
+        None => {
+            // check against a dummy key anyway, so the query takes the same time
+            check_viewing_key(key, &[0u8; VIEWING_KEY_SIZE]);
+            false
+        }
+    };
+
+    if is_key_valid {
+        Ok(())
+    } else {
+        Err(StdError::unauthorized())
     }
 }
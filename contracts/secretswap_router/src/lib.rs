@@ -1,6 +1,15 @@
 pub mod contract;
 pub mod msg;
+pub mod rewards;
+pub mod routing;
 pub mod state;
+mod viewing_key;
+
+#[cfg(test)]
+mod testing;
+
+#[cfg(test)]
+mod mock_querier;
 
 #[cfg(all(target_arch = "wasm32", not(feature = "library")))]
 cosmwasm_std::create_entry_points!(contract);
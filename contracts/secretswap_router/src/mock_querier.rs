@@ -0,0 +1,241 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::{
+    from_binary, from_slice, to_binary, Api, Coin, Empty, Extern, HumanAddr, Querier,
+    QuerierResult, QueryRequest, StdError, SystemError, Uint128, WasmQuery,
+};
+use schemars::JsonSchema;
+use secret_toolkit::snip20::{Balance, BalanceResponse};
+use serde::{Deserialize, Serialize};
+
+use secretswap::{
+    Asset, AssetInfo, Factory, FactoryPairsResponse, PairInfo, ReverseSimulationResponse,
+    SimulationResponse,
+};
+
+/// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
+/// this uses our CustomQuerier.
+pub fn mock_dependencies(
+    canonical_length: usize,
+    contract_balance: &[Coin],
+) -> Extern<MockStorage, MockApi, WasmMockQuerier> {
+    let contract_addr = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    let custom_querier: WasmMockQuerier = WasmMockQuerier::new(
+        MockQuerier::new(&[(&contract_addr, contract_balance)]),
+        canonical_length,
+        MockApi::new(canonical_length),
+    );
+
+    Extern {
+        storage: MockStorage::default(),
+        api: MockApi::new(canonical_length),
+        querier: custom_querier,
+    }
+}
+
+pub struct WasmMockQuerier {
+    base: MockQuerier<Empty>,
+    pair_querier: PairQuerier,
+    token_querier: TokenQuerier,
+    /// Number of simulations the pairs were asked for
+    simulations: Cell<u32>,
+    /// Number of pairs the factory listed
+    listed_pairs: Cell<u32>,
+}
+
+/// Pairs are constant product pools without a commission, keyed by their contract address
+#[derive(Clone, Default)]
+pub struct PairQuerier {
+    pools: HashMap<HumanAddr, [Asset; 2]>,
+    factory_pairs: Vec<PairInfo>,
+}
+
+#[derive(Clone, Default)]
+pub struct TokenQuerier {
+    balances: HashMap<HumanAddr, HashMap<HumanAddr, Uint128>>,
+}
+
+/// The queries the router sends to pairs, the factory and tokens
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum MockQueryMsg {
+    Simulation {
+        offer_asset: Asset,
+    },
+    ReverseSimulation {
+        ask_asset: Asset,
+    },
+    PairsWithAsset {
+        asset_info: AssetInfo,
+        start_after: Option<[AssetInfo; 2]>,
+        limit: Option<u32>,
+    },
+    Balance {
+        address: HumanAddr,
+        key: String,
+    },
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        // MockQuerier doesn't support Custom, so we ignore it completely here
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {}", e),
+                    request: bin_request.into(),
+                });
+            }
+        };
+        self.handle_query(&request)
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
+        match &request {
+            QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr, msg, ..
+            }) => match from_binary(&msg).unwrap() {
+                MockQueryMsg::Simulation { offer_asset } => {
+                    self.simulations.set(self.simulations.get() + 1);
+                    let (offer_pool, ask_pool) = self.pools(contract_addr, &offer_asset.info)?;
+
+                    let return_amount = ask_pool * offer_asset.amount.u128()
+                        / (offer_pool + offer_asset.amount.u128());
+                    let spot_amount = offer_asset.amount.u128() * ask_pool / offer_pool;
+                    Ok(to_binary(&SimulationResponse {
+                        return_amount: Uint128(return_amount),
+                        spread_amount: Uint128(spot_amount - return_amount),
+                        commission_amount: Uint128::zero(),
+                    }))
+                }
+                MockQueryMsg::ReverseSimulation { ask_asset } => {
+                    let (ask_pool, offer_pool) = self.pools(contract_addr, &ask_asset.info)?;
+                    if ask_asset.amount.u128() >= ask_pool {
+                        return Ok(Err(StdError::generic_err("not enough liquidity")));
+                    }
+
+                    let offer_amount =
+                        offer_pool * ask_asset.amount.u128() / (ask_pool - ask_asset.amount.u128());
+                    let spot_amount = ask_asset.amount.u128() * offer_pool / ask_pool;
+                    Ok(to_binary(&ReverseSimulationResponse {
+                        offer_amount: Uint128(offer_amount),
+                        spread_amount: Uint128(offer_amount.saturating_sub(spot_amount)),
+                        commission_amount: Uint128::zero(),
+                    }))
+                }
+                MockQueryMsg::PairsWithAsset {
+                    asset_info,
+                    start_after,
+                    limit,
+                } => {
+                    let pairs: Vec<&PairInfo> = self
+                        .pair_querier
+                        .factory_pairs
+                        .iter()
+                        .filter(|pair| pair.asset_infos.iter().any(|a| a.equal(&asset_info)))
+                        .collect();
+                    let start = match start_after {
+                        Some(start_after) => pairs
+                            .iter()
+                            .position(|pair| pair.asset_infos == start_after)
+                            .map(|position| position + 1)
+                            .unwrap_or_else(|| pairs.len()),
+                        None => 0,
+                    };
+                    let page: Vec<PairInfo> = pairs
+                        .into_iter()
+                        .skip(start)
+                        .take(limit.unwrap_or(10) as usize)
+                        .cloned()
+                        .collect();
+                    self.listed_pairs
+                        .set(self.listed_pairs.get() + page.len() as u32);
+                    Ok(to_binary(&FactoryPairsResponse { pairs: page }))
+                }
+                MockQueryMsg::Balance { address, .. } => {
+                    let amount = self
+                        .token_querier
+                        .balances
+                        .get(&contract_addr)
+                        .and_then(|balances| balances.get(&address))
+                        .cloned()
+                        .unwrap_or_default();
+                    Ok(to_binary(&BalanceResponse {
+                        balance: Balance { amount },
+                    }))
+                }
+            },
+            _ => self.base.handle_query(request),
+        }
+    }
+
+    /// The pool of the offered asset and the pool of the other asset of a pair
+    fn pools(&self, pair: &HumanAddr, offer_info: &AssetInfo) -> Result<(u128, u128), SystemError> {
+        let pools = match self.pair_querier.pools.get(pair) {
+            Some(pools) => pools,
+            None => return Err(SystemError::NoSuchContract { addr: pair.clone() }),
+        };
+
+        if pools[0].info.equal(offer_info) {
+            Ok((pools[0].amount.u128(), pools[1].amount.u128()))
+        } else {
+            Ok((pools[1].amount.u128(), pools[0].amount.u128()))
+        }
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn new<A: Api>(base: MockQuerier<Empty>, _canonical_length: usize, _api: A) -> Self {
+        WasmMockQuerier {
+            base,
+            pair_querier: PairQuerier::default(),
+            token_querier: TokenQuerier::default(),
+            simulations: Cell::new(0),
+            listed_pairs: Cell::new(0),
+        }
+    }
+
+    // configure a pair, which the factory lists in the order the pairs are added
+    pub fn with_pair(&mut self, pair: &HumanAddr, pools: [Asset; 2]) {
+        self.pair_querier.factory_pairs.push(PairInfo {
+            asset_infos: [pools[0].info.clone(), pools[1].info.clone()],
+            contract_addr: pair.clone(),
+            liquidity_token: HumanAddr::from(format!("{}_lp", pair)),
+            token_code_hash: "pair_code_hash".to_string(),
+            asset0_volume: Uint128::zero(),
+            asset1_volume: Uint128::zero(),
+            factory: Factory {
+                address: HumanAddr::from("factory"),
+                code_hash: "factory_code_hash".to_string(),
+            },
+            asset_metadata: Default::default(),
+        });
+        self.pair_querier.pools.insert(pair.clone(), pools);
+    }
+
+    // configure the snip20 balance of an address
+    pub fn with_token_balance(&mut self, token: &HumanAddr, address: &HumanAddr, amount: u128) {
+        self.token_querier
+            .balances
+            .entry(token.clone())
+            .or_default()
+            .insert(address.clone(), Uint128(amount));
+    }
+
+    pub fn with_balance(&mut self, address: &HumanAddr, balance: &[Coin]) {
+        self.base.update_balance(address.clone(), balance.to_vec());
+    }
+
+    pub fn simulation_count(&self) -> u32 {
+        self.simulations.get()
+    }
+
+    pub fn listed_pair_count(&self) -> u32 {
+        self.listed_pairs.get()
+    }
+}
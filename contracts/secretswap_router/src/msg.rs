@@ -1,7 +1,7 @@
 use std::collections::VecDeque;
 
 use crate::state::SecretContract;
-use cosmwasm_std::{Binary, HumanAddr, Uint128};
+use cosmwasm_std::{Binary, Decimal, HumanAddr, Uint128};
 use schemars::JsonSchema;
 use secretswap::Asset;
 use serde::{Deserialize, Serialize};
@@ -9,8 +9,11 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
     pub register_tokens: Option<Vec<Snip20Data>>,
-    pub cashback: Option<SecretContract>,
     pub owner: Option<HumanAddr>,
+    /// Factory used to discover routes for `ReceiveMsg::AutoRoute`
+    pub factory: Option<SecretContract>,
+    /// sSCRT used to wrap and unwrap SCRT between hops
+    pub sscrt: Option<Snip20Data>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -18,6 +21,9 @@ pub struct Hop {
     pub from_token: Token,
     pub pair_address: HumanAddr,
     pub pair_code_hash: String,
+    /// Minimum return of this hop, enforced by the pair
+    #[serde(default)]
+    pub min_return: Option<Uint128>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -25,19 +31,119 @@ pub struct Route {
     pub hops: VecDeque<Hop>,
     pub expected_return: Option<Uint128>,
     pub to: HumanAddr,
+    /// Have the router collect the return and pay it out as this token,
+    /// e.g. to get native SCRT out of an sSCRT pair
+    #[serde(default)]
+    pub return_token: Option<Token>,
+    /// Maximum price impact of the whole route, compared to its simulation when it starts
+    #[serde(default)]
+    pub max_price_impact: Option<Decimal>,
+    #[serde(default)]
+    pub referral: Option<Referral>,
+    /// Makes the route an arbitrage cycle back to the token sent, which reverts unless
+    /// it returns at least the input plus `min_profit`
+    #[serde(default)]
+    pub min_profit: Option<Uint128>,
+    /// Gets the profit of a cycle, while the input goes back to `to`. Defaults to the sender
+    #[serde(default)]
+    pub profit_to: Option<HumanAddr>,
+}
+
+/// Integrator that gets a fee out of the input of the route
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Referral {
+    pub address: HumanAddr,
+    /// Capped by the router's max_referral_fee_bps
+    pub fee_bps: u16,
+}
+
+/// ReceiveMsg holds the alternatives to sending a full `Route` along with the tokens
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Let the router find the best route to `to_token` among the factory's pairs
+    AutoRoute {
+        to_token: Token,
+        expected_return: Option<Uint128>,
+        to: HumanAddr,
+        /// Defaults to and is capped at 3 hops
+        max_hops: Option<u8>,
+        max_price_impact: Option<Decimal>,
+        referral: Option<Referral>,
+    },
+    /// Split the amount sent between several routes to the same `return_token`.
+    /// `expected_return` applies to the sum of all legs
+    SplitRoute {
+        legs: Vec<RouteLeg>,
+        return_token: Token,
+        expected_return: Option<Uint128>,
+        to: HumanAddr,
+        max_price_impact: Option<Decimal>,
+        referral: Option<Referral>,
+    },
+    /// Add the amount sent to the sender's batch, which `HandleMsg::ExecuteBatch` swaps.
+    /// Only registered tokens can be deposited
+    BatchDeposit {},
+    /// Deliver exactly `return_amount` of `return_token`, spending at most the amount sent.
+    /// The input the route doesn't need is sent back to the sender when it's finalized
+    ExactOutputRoute {
+        hops: VecDeque<Hop>,
+        return_token: Token,
+        return_amount: Uint128,
+        to: HumanAddr,
+        max_price_impact: Option<Decimal>,
+        referral: Option<Referral>,
+    },
+    /// Add the amount sent to the escrow of a reward program, up to the program's budget
+    FundRewardProgram { program_id: u64 },
+    /// The return of a hop of route `route_id`, which the router has the pairs send back
+    HopReturn { route_id: u64 },
+}
+
+/// A route of a batch, swapping `amount` out of the deposits of its first token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BatchRoute {
+    pub amount: Uint128,
+    pub route: Route,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RouteLeg {
+    pub hops: VecDeque<Hop>,
+    pub share: LegShare,
+}
+
+/// Part of the amount sent that goes through a leg.
+/// Percentages are taken from what is left after the explicit amounts, and must add up to 100
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LegShare {
+    Percentage(u8),
+    Amount(Uint128),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Snip20Data {
     pub address: HumanAddr,
+    /// Can be left out in hops and return tokens, for tokens that are registered with the router
+    #[serde(default)]
     pub code_hash: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SupportedTokensResponse {
+    pub tokens: Vec<Snip20Data>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Token {
     Snip20(Snip20Data),
     Scrt,
+    /// Any other native denom, e.g. an IBC asset
+    Native {
+        denom: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -48,10 +154,36 @@ pub enum HandleMsg {
         msg: Option<Binary>,
         amount: Uint128,
     },
-    FinalizeRoute {},
+    FinalizeRoute {
+        route_id: u64,
+    },
+    ContinueRoute {
+        route_id: u64,
+    },
+    /// Clear the state of routes that were never finalized
+    ClearRoutes {
+        route_ids: Vec<u64>,
+    },
+    /// Registering a token again overwrites its code hash
     RegisterTokens {
         tokens: Vec<Snip20Data>,
     },
+    UnregisterTokens {
+        tokens: Vec<HumanAddr>,
+    },
+    /// Swaps everything in the sender's batch through independent routes, one after the other.
+    /// Native tokens can also be sent along with this msg
+    ExecuteBatch {
+        routes: Vec<BatchRoute>,
+    },
+    /// Sends the sender's batch deposits back
+    CancelBatch {},
+    StartBatchRoute {
+        sender: HumanAddr,
+    },
+    FinalizeBatch {
+        sender: HumanAddr,
+    },
     RecoverFunds {
         token: Token,
         amount: Uint128,
@@ -60,14 +192,144 @@ pub enum HandleMsg {
     },
     UpdateSettings {
         new_owner: Option<HumanAddr>,
-        new_cashback: Option<SecretContract>,
+        new_factory: Option<SecretContract>,
+        new_sscrt: Option<Snip20Data>,
+        new_max_referral_fee_bps: Option<u16>,
+    },
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+    /// The program only pays out what is sent for it with `ReceiveMsg::FundRewardProgram`
+    AddRewardProgram {
+        program: RewardProgram,
+    },
+    /// Sends what is left of the program's escrow back to the owner
+    EndRewardProgram {
+        program_id: u64,
     },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    SupportedTokens {},
+    SupportedTokens {
+        start_after: Option<HumanAddr>,
+        limit: Option<u32>,
+    },
+    OpenRoutes {},
+    /// RewardPrograms returns the reward programs and what is left of their budgets and escrows
+    RewardPrograms {},
+    /// RewardEarnings returns the rewards a user got so far, per program
+    RewardEarnings {
+        address: HumanAddr,
+        key: String,
+    },
+    /// ReferralEarnings returns the fees a referrer got so far, per token
+    ReferralEarnings {
+        address: HumanAddr,
+        key: String,
+    },
+    /// BestRoute returns the route `ReceiveMsg::AutoRoute` would take
+    BestRoute {
+        from_token: Token,
+        to_token: Token,
+        offer_amount: Uint128,
+        max_hops: Option<u8>,
+    },
+    /// SimulateRoute chains the pair simulations across `hops`
+    SimulateRoute {
+        hops: VecDeque<Hop>,
+        offer_amount: Uint128,
+    },
+    /// ReverseSimulateRoute returns the input that makes `hops` return `return_amount`
+    ReverseSimulateRoute {
+        hops: VecDeque<Hop>,
+        return_token: Token,
+        return_amount: Uint128,
+    },
+}
+
+/// Rewards the sender of every route for the hops it swaps through eligible pairs and tokens,
+/// until the program's budget runs out or it ends
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardProgram {
+    pub reward_token: Snip20Data,
+    pub rate: RewardRate,
+    /// Only hops from these tokens are rewarded, any token if not set
+    pub eligible_tokens: Option<Vec<Token>>,
+    /// Only hops through these pairs are rewarded, any pair if not set
+    pub eligible_pairs: Option<Vec<HumanAddr>>,
+    /// Hops that swap less than this are not rewarded, so that dust hops can't farm the rewards
+    pub min_hop_amount: Option<Uint128>,
+    pub budget: Uint128,
+    /// Block time in seconds at which the program stops paying out rewards
+    pub end_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardRate {
+    /// Reward per unit of the amount that goes into a hop
+    PerVolume(Decimal),
+    /// Fixed reward for every hop
+    PerHop(Uint128),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardProgramInfo {
+    pub program_id: u64,
+    pub program: RewardProgram,
+    pub remaining_budget: Uint128,
+    /// Funds the program has left to pay out
+    pub escrow: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardProgramsResponse {
+    pub programs: Vec<RewardProgramInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardEarning {
+    pub program_id: u64,
+    pub reward_token: Snip20Data,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardEarningsResponse {
+    pub earnings: Vec<RewardEarning>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralEarning {
+    pub token: Token,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralEarningsResponse {
+    pub earnings: Vec<ReferralEarning>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OpenRoute {
+    pub route_id: u64,
+    pub started_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OpenRoutesResponse {
+    pub routes: Vec<OpenRoute>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BestRouteResponse {
+    pub hops: VecDeque<Hop>,
+    pub return_amount: Uint128,
+    /// Set when the search hit its simulation limit, so a better route may exist
+    pub truncated: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -76,9 +338,18 @@ pub enum Snip20Swap {
     Swap {
         expected_return: Option<Uint128>,
         to: Option<HumanAddr>,
+        /// The pair sends it back along with the return
+        callback: Option<Binary>,
     },
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SscrtMsg {
+    Deposit {},
+    Redeem { amount: Uint128 },
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum NativeSwap {
@@ -86,5 +357,29 @@ pub enum NativeSwap {
         offer_asset: Asset,
         expected_return: Option<Uint128>,
         to: Option<HumanAddr>,
+        /// The pair sends it back along with a token return
+        callback: Option<Binary>,
     },
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HopSimulation {
+    pub return_amount: Uint128,
+    pub spread_amount: Uint128,
+    pub commission_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReverseSimulateRouteResponse {
+    pub offer_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateRouteResponse {
+    pub hops: Vec<HopSimulation>,
+    pub return_amount: Uint128,
+    /// Return of the route at the spot price of every pair, after commission
+    pub spot_return: Uint128,
+    /// Share of the spot price return lost to spread over the whole route
+    pub price_impact: Decimal,
+}
@@ -0,0 +1,37 @@
+use cosmwasm_std::Uint128;
+
+use crate::msg::{RewardProgram, RewardRate};
+use crate::routing::is_same_token;
+use crate::state::SwappedHop;
+
+/// The reward that `program` pays out for the hops of a route
+pub fn route_reward(program: &RewardProgram, hops: &[SwappedHop]) -> Uint128 {
+    hops.iter()
+        .filter(|hop| is_eligible(program, hop))
+        .fold(Uint128::zero(), |reward, hop| {
+            reward
+                + match program.rate {
+                    RewardRate::PerVolume(rate) => hop.amount * rate,
+                    RewardRate::PerHop(amount) => amount,
+                }
+        })
+}
+
+fn is_eligible(program: &RewardProgram, hop: &SwappedHop) -> bool {
+    let is_token_eligible = match program.eligible_tokens {
+        Some(ref tokens) => tokens
+            .iter()
+            .any(|token| is_same_token(token, &hop.from_token)),
+        None => true,
+    };
+    let is_pair_eligible = match program.eligible_pairs {
+        Some(ref pairs) => pairs.contains(&hop.pair_address),
+        None => true,
+    };
+    let is_amount_eligible = match program.min_hop_amount {
+        Some(min_hop_amount) => hop.amount >= min_hop_amount,
+        None => true,
+    };
+
+    is_token_eligible && is_pair_eligible && is_amount_eligible
+}
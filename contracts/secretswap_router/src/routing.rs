@@ -0,0 +1,423 @@
+use std::collections::VecDeque;
+
+use cosmwasm_std::{Api, Decimal, Extern, Querier, StdError, StdResult, Storage, Uint128};
+use secretswap::{query_pairs_with_asset, reverse_simulate, simulate, Asset, AssetInfo, PairInfo};
+
+use crate::msg::{BestRouteResponse, Hop, HopSimulation, SimulateRouteResponse, Snip20Data, Token};
+use crate::state::SecretContract;
+
+/// Upper bound on the length of discovered routes, to keep the search cheap
+pub const MAX_HOPS: u8 = 3;
+/// Upper bound on the pair simulations of a route search, which returns the best route
+/// it found by then and reports that the search was truncated
+pub const MAX_SIMULATIONS: u32 = 100;
+const PAIRS_PAGE_LIMIT: u32 = 30;
+const DECIMAL_FRACTIONAL: Uint128 = Uint128(1_000_000_000u128);
+/// How many times the input of a hop is topped up when its reverse simulation rounds down
+const MAX_ROUNDING_ADJUSTMENTS: u8 = 3;
+
+pub fn is_same_token(a: &Token, b: &Token) -> bool {
+    match (a, b) {
+        (Token::Snip20(a), Token::Snip20(b)) => a.address == b.address,
+        (Token::Scrt, Token::Scrt) => true,
+        (Token::Native { denom: a }, Token::Native { denom: b }) => a == b,
+        _ => false,
+    }
+}
+
+fn asset_info_to_token(asset_info: &AssetInfo) -> Token {
+    match asset_info {
+        AssetInfo::Token {
+            contract_addr,
+            token_code_hash,
+            ..
+        } => Token::Snip20(Snip20Data {
+            address: contract_addr.clone(),
+            code_hash: token_code_hash.clone(),
+        }),
+        AssetInfo::NativeToken { denom } => native_token(denom),
+    }
+}
+
+/// The token of a native denom, uscrt is `Token::Scrt`
+pub fn native_token(denom: &str) -> Token {
+    if denom == "uscrt" {
+        Token::Scrt
+    } else {
+        Token::Native {
+            denom: denom.to_string(),
+        }
+    }
+}
+
+/// The denom of a native token
+pub fn native_denom(token: &Token) -> Option<String> {
+    match token {
+        Token::Snip20(_) => None,
+        Token::Scrt => Some("uscrt".to_string()),
+        Token::Native { denom } => Some(denom.clone()),
+    }
+}
+
+pub fn token_to_asset_info(token: &Token) -> AssetInfo {
+    match token {
+        Token::Snip20(Snip20Data { address, code_hash }) => AssetInfo::Token {
+            contract_addr: address.clone(),
+            token_code_hash: code_hash.clone(),
+            viewing_key: String::new(),
+        },
+        Token::Scrt => AssetInfo::NativeToken {
+            denom: "uscrt".into(),
+        },
+        Token::Native { denom } => AssetInfo::NativeToken {
+            denom: denom.clone(),
+        },
+    }
+}
+
+/// Chains the pair simulations along `hops`, feeding the return of each hop into the next one
+pub fn simulate_route<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    hops: &VecDeque<Hop>,
+    offer_amount: Uint128,
+) -> StdResult<SimulateRouteResponse> {
+    if hops.is_empty() {
+        return Err(StdError::generic_err("route must be at least 1 hop"));
+    }
+    if offer_amount.is_zero() {
+        return Err(StdError::generic_err("cannot simulate a zero offer_amount"));
+    }
+
+    let mut simulations = vec![];
+    let mut amount = offer_amount;
+    // what the route would return at the spot price of every pair
+    let mut spot_amount = offer_amount;
+    // share of the spot price return that is kept after the spread of every hop
+    let mut kept_ratio = Decimal::one();
+
+    for hop in hops {
+        let simulation = simulate(
+            deps,
+            &hop.pair_address,
+            &hop.pair_code_hash,
+            &Asset {
+                info: token_to_asset_info(&hop.from_token),
+                amount,
+            },
+        )?;
+
+        let spot_return = simulation.return_amount + simulation.spread_amount;
+        spot_amount = spot_amount.multiply_ratio(spot_return, amount);
+        kept_ratio = if spot_return.is_zero() {
+            Decimal::zero()
+        } else {
+            decimal_multiplication(
+                kept_ratio,
+                Decimal::from_ratio(simulation.return_amount, spot_return),
+            )
+        };
+        amount = simulation.return_amount;
+        if amount.is_zero() {
+            return Err(StdError::generic_err("route returns nothing"));
+        }
+
+        simulations.push(HopSimulation {
+            return_amount: simulation.return_amount,
+            spread_amount: simulation.spread_amount,
+            commission_amount: simulation.commission_amount,
+        });
+    }
+
+    Ok(SimulateRouteResponse {
+        hops: simulations,
+        return_amount: amount,
+        spot_return: spot_amount,
+        price_impact: Decimal::from_ratio(
+            (DECIMAL_FRACTIONAL - kept_ratio * DECIMAL_FRACTIONAL)?,
+            DECIMAL_FRACTIONAL,
+        ),
+    })
+}
+
+/// Walks `hops` backwards with reverse simulations, to find the input that makes the route
+/// return `return_amount` of `return_token`
+pub fn reverse_simulate_route<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    hops: &VecDeque<Hop>,
+    return_token: &Token,
+    return_amount: Uint128,
+    sscrt: &Option<Snip20Data>,
+) -> StdResult<Uint128> {
+    if hops.is_empty() {
+        return Err(StdError::generic_err("route must be at least 1 hop"));
+    }
+    if return_amount.is_zero() {
+        return Err(StdError::generic_err(
+            "cannot simulate a zero return_amount",
+        ));
+    }
+
+    let mut ask_token = return_token.clone();
+    let mut ask_amount = return_amount;
+
+    for hop in hops.iter().rev() {
+        let mut offer_amount = reverse_simulate_hop(deps, hop, &ask_token, ask_amount, sscrt)?;
+
+        // the pairs round the reverse simulation down, so make sure the hop really returns enough
+        let mut adjustments = 0;
+        loop {
+            let simulated_return = simulate(
+                deps,
+                &hop.pair_address,
+                &hop.pair_code_hash,
+                &Asset {
+                    info: token_to_asset_info(&hop.from_token),
+                    amount: offer_amount,
+                },
+            )?
+            .return_amount;
+            if simulated_return >= ask_amount {
+                break;
+            }
+
+            adjustments += 1;
+            if adjustments > MAX_ROUNDING_ADJUSTMENTS {
+                return Err(StdError::generic_err(format!(
+                    "cannot find the input for pair {}",
+                    hop.pair_address
+                )));
+            }
+
+            let shortfall = (ask_amount - simulated_return)?;
+            offer_amount = offer_amount
+                + offer_amount
+                    .multiply_ratio(shortfall, std::cmp::max(simulated_return, Uint128(1)))
+                + Uint128(1);
+        }
+
+        ask_token = hop.from_token.clone();
+        ask_amount = offer_amount;
+    }
+
+    Ok(ask_amount)
+}
+
+/// The router converts between SCRT and sSCRT in between hops, so the pair may hold
+/// the other form of SCRT than the token the next hop starts with
+fn reverse_simulate_hop<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    hop: &Hop,
+    ask_token: &Token,
+    ask_amount: Uint128,
+    sscrt: &Option<Snip20Data>,
+) -> StdResult<Uint128> {
+    let mut ask_tokens = vec![ask_token.clone()];
+    match (ask_token, sscrt) {
+        (Token::Scrt, Some(sscrt)) => ask_tokens.push(Token::Snip20(sscrt.clone())),
+        (Token::Snip20(Snip20Data { address, .. }), Some(sscrt)) if *address == sscrt.address => {
+            ask_tokens.push(Token::Scrt)
+        }
+        _ => {}
+    }
+
+    let mut result = Err(StdError::generic_err("route returns nothing"));
+    for ask_token in ask_tokens {
+        result = reverse_simulate(
+            deps,
+            &hop.pair_address,
+            &hop.pair_code_hash,
+            &Asset {
+                info: token_to_asset_info(&ask_token),
+                amount: ask_amount,
+            },
+        )
+        .map(|simulation| simulation.offer_amount);
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
+}
+
+fn decimal_multiplication(a: Decimal, b: Decimal) -> Decimal {
+    Decimal::from_ratio(a * DECIMAL_FRACTIONAL * b, DECIMAL_FRACTIONAL)
+}
+
+/// Loads the factory's pairs that `token` can be swapped in, page by page
+fn load_pairs_with<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    factory: &SecretContract,
+    token: &Token,
+) -> StdResult<Vec<PairInfo>> {
+    let asset_info = token_to_asset_info(token);
+    let mut pairs = vec![];
+    let mut start_after = None;
+
+    loop {
+        let page = query_pairs_with_asset(
+            deps,
+            &factory.address,
+            &factory.code_hash,
+            &asset_info,
+            start_after,
+            Some(PAIRS_PAGE_LIMIT),
+        )?;
+        let is_last_page = page.len() < PAIRS_PAGE_LIMIT as usize;
+        start_after = page.last().map(|pair| pair.asset_infos.clone());
+
+        // pairs that were created but never registered have no contract address yet
+        pairs.extend(
+            page.into_iter()
+                .filter(|pair| !pair.contract_addr.is_empty()),
+        );
+
+        if is_last_page {
+            return Ok(pairs);
+        }
+    }
+}
+
+/// Finds the route from `from_token` to `to_token` with the highest simulated return
+pub fn find_best_route<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    factory: &SecretContract,
+    from_token: &Token,
+    to_token: &Token,
+    offer_amount: Uint128,
+    max_hops: Option<u8>,
+) -> StdResult<BestRouteResponse> {
+    let max_hops = max_hops.unwrap_or(MAX_HOPS).min(MAX_HOPS);
+    if max_hops == 0 {
+        return Err(StdError::generic_err("max_hops must be at least 1"));
+    }
+    if is_same_token(from_token, to_token) {
+        return Err(StdError::generic_err("cannot route a token to itself"));
+    }
+
+    let mut search = RouteSearch {
+        factory,
+        pairs: vec![],
+        to_token,
+        path: VecDeque::new(),
+        visited: vec![from_token.clone()],
+        best: None,
+        simulations_left: MAX_SIMULATIONS,
+        truncated: false,
+    };
+    search.extend(deps, from_token, offer_amount, max_hops)?;
+
+    let truncated = search.truncated;
+    search
+        .best
+        .map(|(hops, return_amount)| BestRouteResponse {
+            hops,
+            return_amount,
+            truncated,
+        })
+        .ok_or_else(|| StdError::generic_err("cannot find a route between these tokens"))
+}
+
+struct RouteSearch<'a> {
+    factory: &'a SecretContract,
+    /// The pairs of every token the search got to so far
+    pairs: Vec<(Token, Vec<PairInfo>)>,
+    to_token: &'a Token,
+    path: VecDeque<Hop>,
+    visited: Vec<Token>,
+    best: Option<(VecDeque<Hop>, Uint128)>,
+    simulations_left: u32,
+    /// Set when the search ran out of simulations before it tried every route
+    truncated: bool,
+}
+
+impl<'a> RouteSearch<'a> {
+    fn pairs_with<S: Storage, A: Api, Q: Querier>(
+        &mut self,
+        deps: &Extern<S, A, Q>,
+        token: &Token,
+    ) -> StdResult<Vec<PairInfo>> {
+        if let Some((_, pairs)) = self.pairs.iter().find(|(t, _)| is_same_token(t, token)) {
+            return Ok(pairs.clone());
+        }
+
+        let pairs = load_pairs_with(deps, self.factory, token)?;
+        self.pairs.push((token.clone(), pairs.clone()));
+        Ok(pairs)
+    }
+
+    /// Depth-first search over the pairs, simulating every hop along the way
+    fn extend<S: Storage, A: Api, Q: Querier>(
+        &mut self,
+        deps: &Extern<S, A, Q>,
+        current_token: &Token,
+        amount: Uint128,
+        hops_left: u8,
+    ) -> StdResult<()> {
+        for pair in self.pairs_with(deps, current_token)?.iter() {
+            let (offer_info, ask_info) =
+                if is_same_token(&asset_info_to_token(&pair.asset_infos[0]), current_token) {
+                    (&pair.asset_infos[0], &pair.asset_infos[1])
+                } else if is_same_token(&asset_info_to_token(&pair.asset_infos[1]), current_token) {
+                    (&pair.asset_infos[1], &pair.asset_infos[0])
+                } else {
+                    continue;
+                };
+
+            let ask_token = asset_info_to_token(ask_info);
+            if self.visited.iter().any(|t| is_same_token(t, &ask_token)) {
+                continue;
+            }
+
+            let is_target = is_same_token(&ask_token, self.to_token);
+            if !is_target && hops_left == 1 {
+                continue;
+            }
+
+            if self.simulations_left == 0 {
+                self.truncated = true;
+                return Ok(());
+            }
+            self.simulations_left -= 1;
+
+            let return_amount = match simulate(
+                deps,
+                &pair.contract_addr,
+                &pair.token_code_hash,
+                &Asset {
+                    info: offer_info.clone(),
+                    amount,
+                },
+            ) {
+                Ok(simulation) => simulation.return_amount,
+                // e.g. a pair without liquidity
+                Err(_) => continue,
+            };
+
+            self.path.push_back(Hop {
+                from_token: asset_info_to_token(offer_info),
+                pair_address: pair.contract_addr.clone(),
+                pair_code_hash: pair.token_code_hash.clone(),
+                min_return: None,
+            });
+
+            if is_target {
+                let is_better = match &self.best {
+                    Some((_, best_return)) => return_amount > *best_return,
+                    None => true,
+                };
+                if is_better {
+                    self.best = Some((self.path.clone(), return_amount));
+                }
+            } else {
+                self.visited.push(ask_token.clone());
+                self.extend(deps, &ask_token, return_amount, hops_left - 1)?;
+                self.visited.pop();
+            }
+
+            self.path.pop_back();
+        }
+
+        Ok(())
+    }
+}
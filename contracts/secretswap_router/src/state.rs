@@ -1,10 +1,14 @@
-use cosmwasm_std::{HumanAddr, StdResult, Storage};
-use cosmwasm_storage::{ReadonlySingleton, Singleton};
+use std::collections::VecDeque;
+
+use cosmwasm_std::{Coin, HumanAddr, StdResult, Storage, Uint128};
+use cosmwasm_storage::{Bucket, ReadonlyBucket, ReadonlySingleton, Singleton};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::msg::{Hop, Route};
+use crate::msg::{Hop, ReferralEarning, RewardEarning, RewardProgram, Route, Snip20Data, Token};
+use crate::routing::is_same_token;
+use crate::viewing_key::VIEWING_KEY_SIZE;
 
 static KEY_OWNER: &[u8] = b"owner";
 
@@ -16,45 +20,349 @@ pub fn read_owner<S: Storage>(storage: &S) -> StdResult<HumanAddr> {
     ReadonlySingleton::new(storage, KEY_OWNER).load()
 }
 
-static KEY_CASHBACK: &[u8] = b"cashback";
-
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct SecretContract {
     pub address: HumanAddr,
     pub code_hash: String,
 }
 
-pub fn store_cashback<S: Storage>(storage: &mut S, data: &SecretContract) -> StdResult<()> {
-    Singleton::new(storage, KEY_CASHBACK).save(data)
+static KEY_FACTORY: &[u8] = b"factory";
+
+pub fn store_factory<S: Storage>(storage: &mut S, data: &SecretContract) -> StdResult<()> {
+    Singleton::new(storage, KEY_FACTORY).save(data)
+}
+
+pub fn read_factory<S: Storage>(storage: &S) -> StdResult<Option<SecretContract>> {
+    ReadonlySingleton::new(storage, KEY_FACTORY).may_load()
+}
+
+static KEY_SSCRT: &[u8] = b"sscrt";
+
+pub fn store_sscrt<S: Storage>(storage: &mut S, data: &Snip20Data) -> StdResult<()> {
+    Singleton::new(storage, KEY_SSCRT).save(data)
+}
+
+pub fn read_sscrt<S: Storage>(storage: &S) -> StdResult<Option<Snip20Data>> {
+    ReadonlySingleton::new(storage, KEY_SSCRT).may_load()
+}
+
+static KEY_MAX_REFERRAL_FEE_BPS: &[u8] = b"max_referral_fee_bps";
+
+pub fn store_max_referral_fee_bps<S: Storage>(storage: &mut S, data: u16) -> StdResult<()> {
+    Singleton::new(storage, KEY_MAX_REFERRAL_FEE_BPS).save(&data)
+}
+
+/// Referrals are disabled until the owner sets a maximum fee
+pub fn read_max_referral_fee_bps<S: Storage>(storage: &S) -> StdResult<u16> {
+    Ok(ReadonlySingleton::new(storage, KEY_MAX_REFERRAL_FEE_BPS)
+        .may_load()?
+        .unwrap_or_default())
 }
 
-pub fn read_cashback<S: Storage>(storage: &S) -> StdResult<Option<SecretContract>> {
-    ReadonlySingleton::new(storage, KEY_CASHBACK).may_load()
+static PREFIX_REFERRAL_EARNINGS: &[u8] = b"referral_earnings";
+
+pub fn add_referral_earnings<S: Storage>(
+    storage: &mut S,
+    referrer: &HumanAddr,
+    token: &Token,
+    amount: Uint128,
+) -> StdResult<()> {
+    let mut earnings = read_referral_earnings(storage, referrer)?;
+    match earnings
+        .iter_mut()
+        .find(|earning| is_same_token(&earning.token, token))
+    {
+        Some(earning) => earning.amount = earning.amount + amount,
+        None => earnings.push(ReferralEarning {
+            token: token.clone(),
+            amount,
+        }),
+    }
+
+    Bucket::new(PREFIX_REFERRAL_EARNINGS, storage).save(referrer.as_str().as_bytes(), &earnings)
 }
 
-static KEY_ROUTE_STATE: &[u8] = b"route_state";
+pub fn read_referral_earnings<S: Storage>(
+    storage: &S,
+    referrer: &HumanAddr,
+) -> StdResult<Vec<ReferralEarning>> {
+    Ok(ReadonlyBucket::new(PREFIX_REFERRAL_EARNINGS, storage)
+        .may_load(referrer.as_str().as_bytes())?
+        .unwrap_or_default())
+}
+
+static PREFIX_VIEWING_KEY: &[u8] = b"viewing_key";
+
+pub fn write_viewing_key<S: Storage>(
+    storage: &mut S,
+    owner: &HumanAddr,
+    hashed_key: &[u8; VIEWING_KEY_SIZE],
+) -> StdResult<()> {
+    Bucket::new(PREFIX_VIEWING_KEY, storage).save(owner.as_str().as_bytes(), &hashed_key.to_vec())
+}
+
+pub fn read_viewing_key<S: Storage>(storage: &S, owner: &HumanAddr) -> StdResult<Option<Vec<u8>>> {
+    ReadonlyBucket::new(PREFIX_VIEWING_KEY, storage).may_load(owner.as_str().as_bytes())
+}
+
+static PREFIX_ROUTE_STATE: &[u8] = b"route_state";
+static KEY_ROUTE_COUNTER: &[u8] = b"route_counter";
+static KEY_ACTIVE_ROUTE: &[u8] = b"active_route";
+static KEY_OPEN_ROUTES: &[u8] = b"open_routes";
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
-pub struct RouteState {
+pub struct LegState {
+    /// Set once the last hop of the leg was sent out
     pub is_done: bool,
     pub current_hop: Option<Hop>,
-    pub remaining_route: Route,
+    pub remaining_hops: VecDeque<Hop>,
+    pub offer_amount: Uint128,
+    /// The router's balance of the native denom that the current hop may pay out to the router,
+    /// taken before the hop
+    pub native_balance: Option<Coin>,
+}
+
+/// Return of a route, which the router collects from every leg and pays out on FinalizeRoute.
+/// SCRT may come back in both forms, `native_amount` is the part that came back as uscrt
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct CollectedReturn {
+    pub token: Token,
+    pub amount: Uint128,
+    pub native_amount: Uint128,
+    /// Exact-output routes pay out exactly this much, anything on top goes back to the sender
+    pub exact_amount: Option<Uint128>,
+}
+
+/// Input of an exact-output route that it didn't need, sent back to the sender on FinalizeRoute
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct Refund {
+    pub token: Token,
+    pub amount: Uint128,
+}
+
+/// Profit requirement of an arbitrage route, which returns to the token it was sent
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct Cycle {
+    pub input: Uint128,
+    pub min_profit: Uint128,
+    pub profit_to: HumanAddr,
+}
+
+/// The router's balance of a route token before the route started.
+/// Whatever is above it when the route is finalized was left over by the route
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct InitialBalance {
+    pub token: Token,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct RouteState {
+    pub id: u64,
+    /// Block height at which the route was started
+    pub started_at: u64,
+    /// Legs that are not finished yet, in execution order
+    pub legs: VecDeque<LegState>,
+    pub offer_token: Token,
+    pub expected_return: Option<Uint128>,
+    /// Minimum return allowed by the route's max price impact
+    pub price_impact_min_return: Option<Uint128>,
+    pub to: HumanAddr,
+    /// Who sent the input of the route, refunds go back to them
+    pub sender: HumanAddr,
+    pub collect: Option<CollectedReturn>,
+    pub refund: Option<Refund>,
+    pub cycle: Option<Cycle>,
+    pub initial_balances: Vec<InitialBalance>,
+    /// Every hop sent out so far, for the reward programs
+    pub swapped_hops: Vec<SwappedHop>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct SwappedHop {
+    pub pair_address: HumanAddr,
+    pub from_token: Token,
+    pub amount: Uint128,
+}
+
+pub fn next_route_id<S: Storage>(storage: &mut S) -> StdResult<u64> {
+    let mut counter = Singleton::new(storage, KEY_ROUTE_COUNTER);
+    let id: u64 = counter.may_load()?.unwrap_or_default() + 1;
+    counter.save(&id)?;
+    Ok(id)
 }
 
 pub fn store_route_state<S: Storage>(storage: &mut S, data: &RouteState) -> StdResult<()> {
-    Singleton::new(storage, KEY_ROUTE_STATE).save(data)
+    let mut open_routes = read_open_routes(storage)?;
+    if !open_routes.contains(&data.id) {
+        open_routes.push(data.id);
+        Singleton::new(storage, KEY_OPEN_ROUTES).save(&open_routes)?;
+    }
+
+    Bucket::new(PREFIX_ROUTE_STATE, storage).save(&data.id.to_be_bytes(), data)
+}
+
+pub fn read_route_state<S: Storage>(storage: &S, id: u64) -> StdResult<Option<RouteState>> {
+    ReadonlyBucket::new(PREFIX_ROUTE_STATE, storage).may_load(&id.to_be_bytes())
+}
+
+pub fn delete_route_state<S: Storage>(storage: &mut S, id: u64) -> StdResult<()> {
+    let mut open_routes = read_open_routes(storage)?;
+    open_routes.retain(|open_id| *open_id != id);
+    Singleton::new(storage, KEY_OPEN_ROUTES).save(&open_routes)?;
+
+    if read_active_route(storage)? == Some(id) {
+        storage.remove(KEY_ACTIVE_ROUTE);
+    }
+
+    Bucket::<S, RouteState>::new(PREFIX_ROUTE_STATE, storage).remove(&id.to_be_bytes());
+    Ok(())
+}
+
+/// Ids of the routes that were started and not finalized or cleared yet
+pub fn read_open_routes<S: Storage>(storage: &S) -> StdResult<Vec<u64>> {
+    Ok(ReadonlySingleton::new(storage, KEY_OPEN_ROUTES)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+/// The route that is currently being swapped, which receives the returns of the pairs
+pub fn store_active_route<S: Storage>(storage: &mut S, id: u64) -> StdResult<()> {
+    Singleton::new(storage, KEY_ACTIVE_ROUTE).save(&id)
+}
+
+pub fn read_active_route<S: Storage>(storage: &S) -> StdResult<Option<u64>> {
+    ReadonlySingleton::new(storage, KEY_ACTIVE_ROUTE).may_load()
+}
+
+static PREFIX_BATCH: &[u8] = b"batch";
+
+/// What a user deposited for a batch, and the routes it is being swapped through
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct Batch {
+    pub deposits: Vec<BatchDeposit>,
+    pub routes: VecDeque<QueuedRoute>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct BatchDeposit {
+    pub token: Token,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct QueuedRoute {
+    pub offer_token: Token,
+    pub amount: Uint128,
+    pub route: Route,
 }
 
-pub fn read_route_state<S: Storage>(storage: &S) -> StdResult<Option<RouteState>> {
-    ReadonlySingleton::new(storage, KEY_ROUTE_STATE).may_load()
+pub fn store_batch<S: Storage>(storage: &mut S, owner: &HumanAddr, data: &Batch) -> StdResult<()> {
+    Bucket::new(PREFIX_BATCH, storage).save(owner.as_str().as_bytes(), data)
 }
 
-pub fn delete_route_state<S: Storage>(storage: &mut S) {
-    storage.remove(KEY_ROUTE_STATE);
+pub fn read_batch<S: Storage>(storage: &S, owner: &HumanAddr) -> StdResult<Option<Batch>> {
+    ReadonlyBucket::new(PREFIX_BATCH, storage).may_load(owner.as_str().as_bytes())
+}
+
+pub fn delete_batch<S: Storage>(storage: &mut S, owner: &HumanAddr) {
+    Bucket::<S, Batch>::new(PREFIX_BATCH, storage).remove(owner.as_str().as_bytes());
+}
+
+static KEY_REWARD_PROGRAM_COUNTER: &[u8] = b"reward_program_counter";
+static KEY_REWARD_PROGRAMS: &[u8] = b"reward_programs";
+static PREFIX_REWARD_PROGRAM: &[u8] = b"reward_program";
+static PREFIX_REWARD_EARNINGS: &[u8] = b"reward_earnings";
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct RewardProgramState {
+    pub id: u64,
+    pub program: RewardProgram,
+    /// Rewards paid out so far, out of the program's budget
+    pub paid_out: Uint128,
+    /// What was sent for the program and isn't paid out yet, the program only pays out of it
+    pub escrow: Uint128,
+}
+
+pub fn next_reward_program_id<S: Storage>(storage: &mut S) -> StdResult<u64> {
+    let mut counter = Singleton::new(storage, KEY_REWARD_PROGRAM_COUNTER);
+    let id: u64 = counter.may_load()?.unwrap_or_default() + 1;
+    counter.save(&id)?;
+    Ok(id)
+}
+
+pub fn store_reward_program<S: Storage>(
+    storage: &mut S,
+    data: &RewardProgramState,
+) -> StdResult<()> {
+    let mut program_ids = read_reward_program_ids(storage)?;
+    if !program_ids.contains(&data.id) {
+        program_ids.push(data.id);
+        Singleton::new(storage, KEY_REWARD_PROGRAMS).save(&program_ids)?;
+    }
+
+    Bucket::new(PREFIX_REWARD_PROGRAM, storage).save(&data.id.to_be_bytes(), data)
+}
+
+pub fn read_reward_program<S: Storage>(
+    storage: &S,
+    id: u64,
+) -> StdResult<Option<RewardProgramState>> {
+    ReadonlyBucket::new(PREFIX_REWARD_PROGRAM, storage).may_load(&id.to_be_bytes())
+}
+
+pub fn delete_reward_program<S: Storage>(storage: &mut S, id: u64) -> StdResult<()> {
+    let mut program_ids = read_reward_program_ids(storage)?;
+    program_ids.retain(|program_id| *program_id != id);
+    Singleton::new(storage, KEY_REWARD_PROGRAMS).save(&program_ids)?;
+
+    Bucket::<S, RewardProgramState>::new(PREFIX_REWARD_PROGRAM, storage).remove(&id.to_be_bytes());
+    Ok(())
+}
+
+pub fn read_reward_program_ids<S: Storage>(storage: &S) -> StdResult<Vec<u64>> {
+    Ok(ReadonlySingleton::new(storage, KEY_REWARD_PROGRAMS)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+pub fn add_reward_earnings<S: Storage>(
+    storage: &mut S,
+    user: &HumanAddr,
+    program: &RewardProgramState,
+    amount: Uint128,
+) -> StdResult<()> {
+    let mut earnings = read_reward_earnings(storage, user)?;
+    match earnings
+        .iter_mut()
+        .find(|earning| earning.program_id == program.id)
+    {
+        Some(earning) => earning.amount = earning.amount + amount,
+        None => earnings.push(RewardEarning {
+            program_id: program.id,
+            reward_token: program.program.reward_token.clone(),
+            amount,
+        }),
+    }
+
+    Bucket::new(PREFIX_REWARD_EARNINGS, storage).save(user.as_str().as_bytes(), &earnings)
+}
+
+pub fn read_reward_earnings<S: Storage>(
+    storage: &S,
+    user: &HumanAddr,
+) -> StdResult<Vec<RewardEarning>> {
+    Ok(ReadonlyBucket::new(PREFIX_REWARD_EARNINGS, storage)
+        .may_load(user.as_str().as_bytes())?
+        .unwrap_or_default())
 }
 
 static KEY_TOKENS: &[u8] = b"tokens";
+static PREFIX_TOKEN: &[u8] = b"token";
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
 
+/// Registered token addresses, in registration order
 pub fn store_tokens<S: Storage>(storage: &mut S, data: &Vec<HumanAddr>) -> StdResult<()> {
     Singleton::new(storage, KEY_TOKENS).save(data)
 }
@@ -62,3 +370,49 @@ pub fn store_tokens<S: Storage>(storage: &mut S, data: &Vec<HumanAddr>) -> StdRe
 pub fn read_tokens<S: Storage>(storage: &S) -> StdResult<Vec<HumanAddr>> {
     ReadonlySingleton::new(storage, KEY_TOKENS).load()
 }
+
+pub fn store_token<S: Storage>(storage: &mut S, data: &Snip20Data) -> StdResult<()> {
+    let mut tokens = read_tokens(storage)?;
+    if !tokens.contains(&data.address) {
+        tokens.push(data.address.clone());
+        store_tokens(storage, &tokens)?;
+    }
+
+    Bucket::new(PREFIX_TOKEN, storage).save(data.address.as_str().as_bytes(), data)
+}
+
+pub fn read_token<S: Storage>(storage: &S, address: &HumanAddr) -> StdResult<Option<Snip20Data>> {
+    ReadonlyBucket::new(PREFIX_TOKEN, storage).may_load(address.as_str().as_bytes())
+}
+
+pub fn remove_token<S: Storage>(storage: &mut S, address: &HumanAddr) -> StdResult<()> {
+    let mut tokens = read_tokens(storage)?;
+    tokens.retain(|token| token != address);
+    store_tokens(storage, &tokens)?;
+
+    Bucket::<S, Snip20Data>::new(PREFIX_TOKEN, storage).remove(address.as_str().as_bytes());
+    Ok(())
+}
+
+pub fn read_tokens_page<S: Storage>(
+    storage: &S,
+    start_after: Option<HumanAddr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Snip20Data>> {
+    let tokens = read_tokens(storage)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some(start_after) => match tokens.iter().position(|token| *token == start_after) {
+            Some(position) => position + 1,
+            None => tokens.len(),
+        },
+        None => 0,
+    };
+
+    tokens
+        .iter()
+        .skip(start)
+        .take(limit)
+        .map(|address| ReadonlyBucket::new(PREFIX_TOKEN, storage).load(address.as_str().as_bytes()))
+        .collect()
+}
@@ -0,0 +1,1957 @@
+use cosmwasm_std::testing::{mock_env, MockApi, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::{
+    from_binary, log, to_binary, BankMsg, Binary, Coin, CosmosMsg, Decimal, Extern, HandleResponse,
+    HumanAddr, StdError, StdResult, Uint128, WasmMsg,
+};
+use serde::Deserialize;
+
+use secretswap::{Asset, AssetInfo};
+
+use crate::contract::{handle, init, query};
+use crate::mock_querier::{mock_dependencies, WasmMockQuerier};
+use crate::msg::{
+    BatchRoute, BestRouteResponse, HandleMsg, Hop, InitMsg, LegShare, NativeSwap, QueryMsg,
+    ReceiveMsg, Referral, ReferralEarning, ReferralEarningsResponse, ReverseSimulateRouteResponse,
+    RewardProgram, RewardProgramsResponse, RewardRate, Route, RouteLeg, SimulateRouteResponse,
+    Snip20Data, Snip20Swap, SupportedTokensResponse, Token,
+};
+use crate::routing::MAX_SIMULATIONS;
+use crate::state::SecretContract;
+
+/// The SNIP-20 messages the router sends, as the token contracts see them
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Snip20Msg {
+    Send {
+        recipient: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+    },
+    Transfer {
+        recipient: HumanAddr,
+        amount: Uint128,
+    },
+}
+
+fn token(address: &str) -> Token {
+    Token::Snip20(Snip20Data {
+        address: HumanAddr::from(address),
+        code_hash: format!("{}_hash", address),
+    })
+}
+
+fn asset(address: &str, amount: u128) -> Asset {
+    Asset {
+        info: AssetInfo::Token {
+            contract_addr: HumanAddr::from(address),
+            token_code_hash: format!("{}_hash", address),
+            viewing_key: String::new(),
+        },
+        amount: Uint128(amount),
+    }
+}
+
+fn init_router(deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>) {
+    let msg = InitMsg {
+        register_tokens: None,
+        owner: None,
+        factory: Some(SecretContract {
+            address: HumanAddr::from("factory"),
+            code_hash: "factory_code_hash".to_string(),
+        }),
+        sscrt: None,
+    };
+    init(deps, mock_env("owner", &[]), msg).unwrap();
+}
+
+fn hop(from_token: &str, pair: &str) -> Hop {
+    Hop {
+        from_token: token(from_token),
+        pair_address: HumanAddr::from(pair),
+        pair_code_hash: "pair_code_hash".to_string(),
+        min_return: None,
+    }
+}
+
+fn scrt_hop(pair: &str) -> Hop {
+    Hop {
+        from_token: Token::Scrt,
+        pair_address: HumanAddr::from(pair),
+        pair_code_hash: "pair_code_hash".to_string(),
+        min_return: None,
+    }
+}
+
+fn native_hop(denom: &str, pair: &str) -> Hop {
+    Hop {
+        from_token: Token::Native {
+            denom: denom.to_string(),
+        },
+        pair_address: HumanAddr::from(pair),
+        pair_code_hash: "pair_code_hash".to_string(),
+        min_return: None,
+    }
+}
+
+fn native_asset(denom: &str, amount: u128) -> Asset {
+    Asset {
+        info: AssetInfo::NativeToken {
+            denom: denom.to_string(),
+        },
+        amount: Uint128(amount),
+    }
+}
+
+fn route(hops: Vec<Hop>) -> Route {
+    Route {
+        hops: hops.into_iter().collect(),
+        expected_return: None,
+        to: HumanAddr::from("user"),
+        return_token: None,
+        max_price_impact: None,
+        referral: None,
+        min_profit: None,
+        profit_to: None,
+    }
+}
+
+fn set_max_referral_fee_bps(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    max_fee_bps: u16,
+) -> StdResult<HandleResponse> {
+    let msg = HandleMsg::UpdateSettings {
+        new_owner: None,
+        new_factory: None,
+        new_sscrt: None,
+        new_max_referral_fee_bps: Some(max_fee_bps),
+    };
+    handle(deps, mock_env("owner", &[]), msg)
+}
+
+/// `token` was sent to the router by `from` along with `msg`
+fn receive(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    token: &str,
+    from: &str,
+    amount: u128,
+    msg: Binary,
+) -> StdResult<HandleResponse> {
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from(from),
+        msg: Some(msg),
+        amount: Uint128(amount),
+    };
+    handle(deps, mock_env(token, &[]), msg)
+}
+
+/// `pair` paid out the return of a hop of route `route_id`
+fn hop_return(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    token: &str,
+    pair: &str,
+    amount: u128,
+    route_id: u64,
+) -> StdResult<HandleResponse> {
+    let msg = to_binary(&ReceiveMsg::HopReturn { route_id }).unwrap();
+    receive(deps, token, pair, amount, msg)
+}
+
+/// `pair` paid out the return of a hop without the callback, as pairs did before HopReturn
+fn legacy_hop_return(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    token: &str,
+    pair: &str,
+    amount: u128,
+) -> StdResult<HandleResponse> {
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from(pair),
+        msg: None,
+        amount: Uint128(amount),
+    };
+    handle(deps, mock_env(token, &[]), msg)
+}
+
+/// The router executing one of its own messages
+fn callback(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    handle(deps, mock_env(MOCK_CONTRACT_ADDR, &[]), msg)
+}
+
+/// A swap of `amount` uscrt through the native pair `pair`, paying out to `to`
+fn native_swap_msg(pair: &str, amount: u128, to: &str) -> CosmosMsg {
+    native_swap(pair, "uscrt", amount, to, None)
+}
+
+/// A swap of `amount` uscrt through the native pair `pair`, for a hop of route `route_id`
+fn native_hop_msg(pair: &str, amount: u128, route_id: u64) -> CosmosMsg {
+    native_swap(pair, "uscrt", amount, MOCK_CONTRACT_ADDR, Some(route_id))
+}
+
+/// A swap of `amount` of `denom` through the native pair `pair`
+fn native_swap(
+    pair: &str,
+    denom: &str,
+    amount: u128,
+    to: &str,
+    route_id: Option<u64>,
+) -> CosmosMsg {
+    CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: HumanAddr::from(pair),
+        callback_code_hash: "pair_code_hash".to_string(),
+        msg: to_binary(&NativeSwap::Swap {
+            offer_asset: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: denom.to_string(),
+                },
+                amount: Uint128(amount),
+            },
+            expected_return: None,
+            to: Some(HumanAddr::from(to)),
+            callback: route_id.map(hop_callback),
+        })
+        .unwrap(),
+        send: vec![Coin::new(amount, denom)],
+    })
+}
+
+fn hop_callback(route_id: u64) -> Binary {
+    to_binary(&ReceiveMsg::HopReturn { route_id }).unwrap()
+}
+
+fn snip20_msg(msg: &CosmosMsg) -> (HumanAddr, Snip20Msg) {
+    match msg {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr, msg, ..
+        }) => (contract_addr.clone(), from_binary(msg).unwrap()),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+/// A swap of `amount` of `token` through `pair`, paying out to `to`
+fn swap_msg(token: &str, pair: &str, amount: u128, to: &str) -> (HumanAddr, Snip20Msg) {
+    swap(token, pair, amount, to, None)
+}
+
+/// A swap of `amount` of `token` through `pair`, for a hop of route `route_id`
+fn hop_msg(token: &str, pair: &str, amount: u128, route_id: u64) -> (HumanAddr, Snip20Msg) {
+    swap(token, pair, amount, MOCK_CONTRACT_ADDR, Some(route_id))
+}
+
+fn swap(
+    token: &str,
+    pair: &str,
+    amount: u128,
+    to: &str,
+    route_id: Option<u64>,
+) -> (HumanAddr, Snip20Msg) {
+    (
+        HumanAddr::from(token),
+        Snip20Msg::Send {
+            recipient: HumanAddr::from(pair),
+            amount: Uint128(amount),
+            msg: Some(
+                to_binary(&Snip20Swap::Swap {
+                    expected_return: None,
+                    to: Some(HumanAddr::from(to)),
+                    callback: route_id.map(hop_callback),
+                })
+                .unwrap(),
+            ),
+        },
+    )
+}
+
+/// The expected_return a swap message asks its pair for
+fn swap_expected_return(msg: &CosmosMsg) -> Option<Uint128> {
+    match snip20_msg(msg) {
+        (
+            _,
+            Snip20Msg::Send {
+                msg: Some(swap), ..
+            },
+        ) => match from_binary(&swap).unwrap() {
+            Snip20Swap::Swap {
+                expected_return, ..
+            } => expected_return,
+        },
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+fn transfer(token: &str, recipient: &str, amount: u128) -> (HumanAddr, Snip20Msg) {
+    (
+        HumanAddr::from(token),
+        Snip20Msg::Transfer {
+            recipient: HumanAddr::from(recipient),
+            amount: Uint128(amount),
+        },
+    )
+}
+
+fn route_callback(msg: HandleMsg) -> CosmosMsg {
+    CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: HumanAddr::from(MOCK_CONTRACT_ADDR),
+        callback_code_hash: mock_env(MOCK_CONTRACT_ADDR, &[]).contract_code_hash,
+        msg: to_binary(&msg).unwrap(),
+        send: vec![],
+    })
+}
+
+/// Pairs between token_a, token_b and token_c, where going through token_b is the deeper route
+fn with_abc_pairs(deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>) {
+    deps.querier.with_pair(
+        &HumanAddr::from("pair_ac"),
+        [asset("token_a", 1_000), asset("token_c", 1_000)],
+    );
+    deps.querier.with_pair(
+        &HumanAddr::from("pair_ab"),
+        [asset("token_a", 1_000_000), asset("token_b", 1_000_000)],
+    );
+    deps.querier.with_pair(
+        &HumanAddr::from("pair_bc"),
+        [asset("token_b", 1_000_000), asset("token_c", 1_000_000)],
+    );
+}
+
+fn best_route(
+    deps: &Extern<MockStorage, MockApi, WasmMockQuerier>,
+    from_token: &str,
+    to_token: &str,
+    offer_amount: u128,
+    max_hops: Option<u8>,
+) -> Result<BestRouteResponse, StdError> {
+    let res = query(
+        deps,
+        QueryMsg::BestRoute {
+            from_token: token(from_token),
+            to_token: token(to_token),
+            offer_amount: Uint128(offer_amount),
+            max_hops,
+        },
+    )?;
+    from_binary(&res)
+}
+
+#[test]
+fn best_route_prefers_the_highest_return() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    // the direct pair is too thin, going through token_b returns more
+    let route = best_route(&deps, "token_a", "token_c", 1_000, None).unwrap();
+    let pairs: Vec<HumanAddr> = route
+        .hops
+        .iter()
+        .map(|hop| hop.pair_address.clone())
+        .collect();
+    assert_eq!(
+        pairs,
+        vec![HumanAddr::from("pair_ab"), HumanAddr::from("pair_bc")]
+    );
+    assert_eq!(route.return_amount, Uint128(998));
+    assert!(!route.truncated);
+
+    let route = best_route(&deps, "token_a", "token_c", 1_000, Some(1)).unwrap();
+    assert_eq!(route.hops.len(), 1);
+    assert_eq!(route.hops[0].pair_address, HumanAddr::from("pair_ac"));
+    assert_eq!(route.return_amount, Uint128(500));
+
+    match best_route(&deps, "token_a", "token_d", 1_000, None) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "cannot find a route between these tokens")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn best_route_goes_through_native_denom_pairs() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    deps.querier.with_pair(
+        &HumanAddr::from("pair_a_luna"),
+        [
+            asset("token_a", 1_000_000),
+            native_asset("uluna", 1_000_000),
+        ],
+    );
+    deps.querier.with_pair(
+        &HumanAddr::from("pair_luna_c"),
+        [
+            native_asset("uluna", 1_000_000),
+            asset("token_c", 1_000_000),
+        ],
+    );
+
+    let route = best_route(&deps, "token_a", "token_c", 1_000, None).unwrap();
+    let from_tokens: Vec<Token> = route
+        .hops
+        .iter()
+        .map(|hop| hop.from_token.clone())
+        .collect();
+    assert_eq!(
+        from_tokens,
+        vec![
+            token("token_a"),
+            Token::Native {
+                denom: "uluna".to_string()
+            }
+        ]
+    );
+    assert_eq!(route.hops[1].pair_address, HumanAddr::from("pair_luna_c"));
+    assert_eq!(route.return_amount, Uint128(998));
+}
+
+fn with_many_routes(deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>) {
+    // more pairs than fit in a page of the factory, and more routes than can be simulated
+    for i in 0..60 {
+        let middle = format!("token_{}", i);
+        deps.querier.with_pair(
+            &HumanAddr::from(format!("pair_a_{}", i)),
+            [asset("token_a", 1_000_000), asset(&middle, 1_000_000)],
+        );
+        deps.querier.with_pair(
+            &HumanAddr::from(format!("pair_z_{}", i)),
+            [
+                asset(&middle, 1_000_000),
+                asset("token_z", 1_000_000 + i * 10_000),
+            ],
+        );
+    }
+}
+
+#[test]
+fn best_route_search_is_capped() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_many_routes(&mut deps);
+
+    let route = best_route(&deps, "token_a", "token_z", 1_000, None).unwrap();
+    assert_eq!(route.hops.len(), 2);
+    assert_eq!(deps.querier.simulation_count(), MAX_SIMULATIONS);
+    // the search stops after half of the routes, before it gets to the deeper pools
+    assert_eq!(route.hops[1].pair_address, HumanAddr::from("pair_z_49"));
+    assert!(route.truncated);
+}
+
+#[test]
+fn best_route_only_loads_the_pairs_of_the_tokens_it_reaches() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+    for i in 0..100 {
+        deps.querier.with_pair(
+            &HumanAddr::from(format!("pair_x_{}", i)),
+            [
+                asset(&format!("token_x_{}", i), 1_000_000),
+                asset("token_y", 1_000_000),
+            ],
+        );
+    }
+
+    let route = best_route(&deps, "token_a", "token_c", 1_000, Some(2)).unwrap();
+    assert_eq!(route.return_amount, Uint128(998));
+    // pair_ab and pair_ac for token_a, pair_ab and pair_bc for token_b
+    assert_eq!(deps.querier.listed_pair_count(), 4);
+}
+
+#[test]
+fn auto_route_reports_a_truncated_search() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_many_routes(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    let msg = to_binary(&ReceiveMsg::AutoRoute {
+        to_token: token("token_z"),
+        expected_return: None,
+        to: HumanAddr::from("user"),
+        max_hops: None,
+        max_price_impact: None,
+        referral: None,
+    })
+    .unwrap();
+    let res = receive(&mut deps, "token_a", "user", 1_000, msg).unwrap();
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_a_49", 1_000, 1)
+    );
+    assert!(res.log.contains(&log("route_search", "truncated")));
+}
+
+fn split_route_msg(percentages: [u8; 2]) -> Binary {
+    to_binary(&ReceiveMsg::SplitRoute {
+        legs: vec![
+            RouteLeg {
+                hops: vec![hop("token_a", "pair_ac")].into_iter().collect(),
+                share: LegShare::Percentage(percentages[0]),
+            },
+            RouteLeg {
+                hops: vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]
+                    .into_iter()
+                    .collect(),
+                share: LegShare::Percentage(percentages[1]),
+            },
+        ],
+        return_token: token("token_c"),
+        expected_return: Some(Uint128(700)),
+        to: HumanAddr::from("user"),
+        max_price_impact: None,
+        referral: None,
+    })
+    .unwrap()
+}
+
+#[test]
+fn split_route_swaps_legs_one_after_the_other() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    let res = receive(
+        &mut deps,
+        "token_a",
+        "user",
+        1_000,
+        split_route_msg([60, 40]),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_ac", 600, 1)
+    );
+    assert_eq!(
+        res.messages[1],
+        route_callback(HandleMsg::FinalizeRoute { route_id: 1 })
+    );
+
+    // the first leg returns token_c, and the second leg starts
+    let res = hop_return(&mut deps, "token_c", "pair_ac", 375, 1).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_ab", 400, 1)
+    );
+
+    let res = hop_return(&mut deps, "token_b", "pair_ab", 399, 1).unwrap();
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_b", "pair_bc", 399, 1)
+    );
+
+    let res = hop_return(&mut deps, "token_c", "pair_bc", 398, 1).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_c"), &router, 773);
+    let res = callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        transfer("token_c", "user", 773)
+    );
+}
+
+#[test]
+fn split_route_rejects_bad_shares() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    match receive(
+        &mut deps,
+        "token_a",
+        "user",
+        1_000,
+        split_route_msg([60, 30]),
+    ) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "leg percentages must add up to 100")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the return of the legs together must meet expected_return
+    receive(
+        &mut deps,
+        "token_a",
+        "user",
+        1_000,
+        split_route_msg([60, 40]),
+    )
+    .unwrap();
+    hop_return(&mut deps, "token_c", "pair_ac", 375, 1).unwrap();
+    hop_return(&mut deps, "token_b", "pair_ab", 399, 1).unwrap();
+    hop_return(&mut deps, "token_c", "pair_bc", 300, 1).unwrap();
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_c"), &router, 675);
+    match callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "route return 675 is less than expected_return 700")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+fn simulate_route(
+    deps: &Extern<MockStorage, MockApi, WasmMockQuerier>,
+    hops: Vec<Hop>,
+    offer_amount: u128,
+) -> StdResult<SimulateRouteResponse> {
+    let msg = QueryMsg::SimulateRoute {
+        hops: hops.into_iter().collect(),
+        offer_amount: Uint128(offer_amount),
+    };
+    from_binary(&query(deps, msg)?)
+}
+
+#[test]
+fn simulate_route_chains_the_hops() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let res = simulate_route(
+        &deps,
+        vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")],
+        1_000,
+    )
+    .unwrap();
+    assert_eq!(res.hops.len(), 2);
+    assert_eq!(res.hops[0].return_amount, Uint128(999));
+    assert_eq!(res.hops[0].spread_amount, Uint128(1));
+    assert_eq!(res.hops[1].return_amount, Uint128(998));
+    assert_eq!(res.hops[1].spread_amount, Uint128(1));
+    assert_eq!(res.return_amount, Uint128(998));
+    assert_eq!(res.spot_return, Uint128(1_000));
+    // about 0.2% of the spot return is lost to the spread of both hops
+    assert!(res.price_impact > Decimal::permille(1));
+    assert!(res.price_impact < Decimal::permille(3));
+}
+
+#[test]
+fn simulate_route_rejects_empty_routes() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    match simulate_route(&deps, vec![], 1_000) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "route must be at least 1 hop"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    match simulate_route(&deps, vec![hop("token_a", "pair_ab")], 0) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "cannot simulate a zero offer_amount")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // tokens that aren't registered must come with their code hash
+    let mut unknown_hop = hop("token_a", "pair_ab");
+    unknown_hop.from_token = Token::Snip20(Snip20Data {
+        address: HumanAddr::from("token_a"),
+        code_hash: String::new(),
+    });
+    match simulate_route(&deps, vec![unknown_hop], 1_000) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "the code hash of token_a is required, as it is not registered"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn scrt_return_is_measured_before_the_referral_fee_leaves() {
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    let mut deps = mock_dependencies(20, &[Coin::new(1_000, "uscrt")]);
+    init_router(&mut deps);
+
+    set_max_referral_fee_bps(&mut deps, 100).unwrap();
+
+    // the first pair pays out uscrt, which the router only notices in ContinueRoute
+    let mut scrt_route = route(vec![scrt_hop("pair_a"), scrt_hop("pair_b")]);
+    scrt_route.referral = Some(Referral {
+        address: HumanAddr::from("referrer"),
+        fee_bps: 100,
+    });
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("user"),
+        msg: Some(to_binary(&scrt_route).unwrap()),
+        amount: Uint128(1_000),
+    };
+    let res = handle(
+        &mut deps,
+        mock_env("user", &[Coin::new(1_000, "uscrt")]),
+        msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            native_hop_msg("pair_a", 990, 1),
+            route_callback(HandleMsg::ContinueRoute { route_id: 1 }),
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: router.clone(),
+                to_address: HumanAddr::from("referrer"),
+                amount: vec![Coin::new(10, "uscrt")],
+            }),
+            route_callback(HandleMsg::FinalizeRoute { route_id: 1 }),
+        ]
+    );
+
+    // only the router can continue its routes
+    match handle(
+        &mut deps,
+        mock_env("user", &[]),
+        HandleMsg::ContinueRoute { route_id: 1 },
+    ) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // pair_a didn't pay out anything
+    deps.querier
+        .with_balance(&router, &[Coin::new(10, "uscrt")]);
+    match callback(&mut deps, HandleMsg::ContinueRoute { route_id: 1 }) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "route can only be continued after receiving uscrt from the previous pair"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // pair_a paid out 500 uscrt, while the referral fee is still in the router
+    deps.querier
+        .with_balance(&router, &[Coin::new(510, "uscrt")]);
+    let res = callback(&mut deps, HandleMsg::ContinueRoute { route_id: 1 }).unwrap();
+    assert_eq!(res.messages, vec![native_swap_msg("pair_b", 500, "user")]);
+}
+
+#[test]
+fn native_denom_return_is_measured_by_continue_route() {
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    // pair_a pays out uluna, which the router only notices in ContinueRoute
+    let msg = to_binary(&route(vec![
+        hop("token_a", "pair_a"),
+        native_hop("uluna", "pair_b"),
+    ]))
+    .unwrap();
+    let res = receive(&mut deps, "token_a", "user", 1_000, msg).unwrap();
+    assert_eq!(res.messages.len(), 3);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_a", 1_000, 1)
+    );
+    assert_eq!(
+        res.messages[1..],
+        [
+            route_callback(HandleMsg::ContinueRoute { route_id: 1 }),
+            route_callback(HandleMsg::FinalizeRoute { route_id: 1 }),
+        ]
+    );
+
+    // pair_a didn't pay out anything
+    match callback(&mut deps, HandleMsg::ContinueRoute { route_id: 1 }) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "route can only be continued after receiving uluna from the previous pair"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // pair_a paid out 500 uluna, which the router sends along to pair_b
+    deps.querier
+        .with_balance(&router, &[Coin::new(500, "uluna")]);
+    let res = callback(&mut deps, HandleMsg::ContinueRoute { route_id: 1 }).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![native_swap("pair_b", "uluna", 500, "user", None)]
+    );
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    deps.querier.with_balance(&router, &[]);
+    callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+}
+
+#[test]
+fn routes_can_start_with_a_native_denom() {
+    let mut deps = mock_dependencies(20, &[Coin::new(1_000, "uluna")]);
+    init_router(&mut deps);
+
+    let msg = HandleMsg::Receive {
+        from: HumanAddr::from("user"),
+        msg: Some(
+            to_binary(&route(vec![
+                native_hop("uluna", "pair_a"),
+                hop("token_b", "pair_b"),
+            ]))
+            .unwrap(),
+        ),
+        amount: Uint128(1_000),
+    };
+    let res = handle(
+        &mut deps,
+        mock_env("user", &[Coin::new(1_000, "uluna")]),
+        msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            native_swap("pair_a", "uluna", 1_000, MOCK_CONTRACT_ADDR, Some(1)),
+            route_callback(HandleMsg::FinalizeRoute { route_id: 1 }),
+        ]
+    );
+
+    let res = hop_return(&mut deps, "token_b", "pair_a", 999, 1).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        swap_msg("token_b", "pair_b", 999, "user")
+    );
+}
+
+#[test]
+fn hop_returns_without_a_callback_continue_the_active_route() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    // without an active route, there is nothing to return to
+    match legacy_hop_return(&mut deps, "token_b", "pair_ab", 999) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "tokens can only be sent here along with a route")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    let msg = to_binary(&route(vec![
+        hop("token_a", "pair_ab"),
+        hop("token_b", "pair_bc"),
+    ]))
+    .unwrap();
+    receive(&mut deps, "token_a", "user", 1_000, msg).unwrap();
+
+    // a pair deployed before the HopReturn callback pays out without a msg
+    let res = legacy_hop_return(&mut deps, "token_b", "pair_ab", 999).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        swap_msg("token_b", "pair_bc", 999, "user")
+    );
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+}
+
+#[test]
+fn hop_returns_must_match_the_active_route() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    let msg = to_binary(&route(vec![
+        hop("token_a", "pair_ab"),
+        hop("token_b", "pair_bc"),
+    ]))
+    .unwrap();
+    let res = receive(&mut deps, "token_a", "user", 1_000, msg).unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_ab", 1_000, 1)
+    );
+
+    // returns of another route
+    for route_id in [0, 2].iter() {
+        match hop_return(&mut deps, "token_b", "pair_ab", 999, *route_id) {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "cannot find route"),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    }
+
+    // the wrong token, or the right token from the wrong pair
+    for (token, pair) in [("token_c", "pair_ab"), ("token_b", "pair_bc")].iter() {
+        match hop_return(&mut deps, token, pair, 999, 1) {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+                msg,
+                "route can only be called by receiving the token of the next hop from the previous pair"
+            ),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    }
+
+    // a return without the route id from the wrong pair
+    match legacy_hop_return(&mut deps, "token_b", "pair_bc", 999) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "route can only be called by receiving the token of the next hop from the previous pair"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the last hop pays out straight to the user
+    let res = hop_return(&mut deps, "token_b", "pair_ab", 999, 1).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        swap_msg("token_b", "pair_bc", 999, "user")
+    );
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+
+    // a late return of the finalized route
+    match hop_return(&mut deps, "token_b", "pair_ab", 999, 1) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "cannot find route"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn hops_enforce_min_return_and_the_last_one_max_price_impact() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    let mut first_hop = hop("token_a", "pair_ab");
+    first_hop.min_return = Some(Uint128(995));
+    let mut checked_route = route(vec![first_hop, hop("token_b", "pair_bc")]);
+    checked_route.expected_return = Some(Uint128(900));
+    checked_route.max_price_impact = Some(Decimal::percent(1));
+
+    let res = receive(
+        &mut deps,
+        "token_a",
+        "user",
+        1_000,
+        to_binary(&checked_route).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(swap_expected_return(&res.messages[0]), Some(Uint128(995)));
+
+    // the spot return is 1000, so the route must return at least 990
+    let res = hop_return(&mut deps, "token_b", "pair_ab", 999, 1).unwrap();
+    assert_eq!(swap_expected_return(&res.messages[0]), Some(Uint128(990)));
+}
+
+#[test]
+fn collected_returns_enforce_max_price_impact() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    let mut checked_route = route(vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]);
+    checked_route.return_token = Some(token("token_c"));
+    checked_route.max_price_impact = Some(Decimal::percent(101));
+    match receive(
+        &mut deps,
+        "token_a",
+        "user",
+        1_000,
+        to_binary(&checked_route).unwrap(),
+    ) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "max_price_impact cannot exceed 1")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    checked_route.max_price_impact = Some(Decimal::percent(1));
+    receive(
+        &mut deps,
+        "token_a",
+        "user",
+        1_000,
+        to_binary(&checked_route).unwrap(),
+    )
+    .unwrap();
+    let res = hop_return(&mut deps, "token_b", "pair_ab", 999, 1).unwrap();
+    // the router collects the return, so it checks the price impact itself
+    assert_eq!(swap_expected_return(&res.messages[0]), None);
+    hop_return(&mut deps, "token_c", "pair_bc", 980, 1).unwrap();
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_c"), &router, 980);
+    match callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "route return 980 exceeds max_price_impact, the minimum is 990"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+fn referral_route(fee_bps: u16) -> Binary {
+    let mut referred_route = route(vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]);
+    referred_route.referral = Some(Referral {
+        address: HumanAddr::from("referrer"),
+        fee_bps,
+    });
+    to_binary(&referred_route).unwrap()
+}
+
+#[test]
+fn referral_fee_is_taken_out_of_the_input() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+    set_max_referral_fee_bps(&mut deps, 100).unwrap();
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    let res = receive(&mut deps, "token_a", "user", 1_000, referral_route(50)).unwrap();
+    assert_eq!(res.messages.len(), 3);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_ab", 995, 1)
+    );
+    assert_eq!(
+        snip20_msg(&res.messages[1]),
+        transfer("token_a", "referrer", 5)
+    );
+    assert_eq!(
+        res.log,
+        vec![log("referrer", "referrer"), log("referral_fee", "5")]
+    );
+
+    let msg = HandleMsg::SetViewingKey {
+        key: "referrer_key".to_string(),
+        padding: None,
+    };
+    handle(&mut deps, mock_env("referrer", &[]), msg).unwrap();
+
+    let query_msg = QueryMsg::ReferralEarnings {
+        address: HumanAddr::from("referrer"),
+        key: "referrer_key".to_string(),
+    };
+    let res: ReferralEarningsResponse = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+    assert_eq!(
+        res.earnings,
+        vec![ReferralEarning {
+            token: token("token_a"),
+            amount: Uint128(5),
+        }]
+    );
+
+    let query_msg = QueryMsg::ReferralEarnings {
+        address: HumanAddr::from("referrer"),
+        key: "wrong_key".to_string(),
+    };
+    match query(&deps, query_msg) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn referral_fee_is_capped() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    // referrals are disabled until the owner sets a maximum fee
+    match receive(&mut deps, "token_a", "user", 1_000, referral_route(50)) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "referral fee cannot exceed 0 bps")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    match set_max_referral_fee_bps(&mut deps, 10_001) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "max referral fee cannot exceed 10000 bps")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let msg = HandleMsg::UpdateSettings {
+        new_owner: None,
+        new_factory: None,
+        new_sscrt: None,
+        new_max_referral_fee_bps: Some(100),
+    };
+    match handle(&mut deps, mock_env("referrer", &[]), msg) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    set_max_referral_fee_bps(&mut deps, 100).unwrap();
+    match receive(&mut deps, "token_a", "user", 1_000, referral_route(101)) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "referral fee cannot exceed 100 bps")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+fn exact_output_route(return_amount: u128) -> Binary {
+    to_binary(&ReceiveMsg::ExactOutputRoute {
+        hops: vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]
+            .into_iter()
+            .collect(),
+        return_token: token("token_c"),
+        return_amount: Uint128(return_amount),
+        to: HumanAddr::from("recipient"),
+        max_price_impact: None,
+        referral: None,
+    })
+    .unwrap()
+}
+
+#[test]
+fn exact_output_route_refunds_the_unused_input() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let query_msg = QueryMsg::ReverseSimulateRoute {
+        hops: vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]
+            .into_iter()
+            .collect(),
+        return_token: token("token_c"),
+        return_amount: Uint128(998),
+    };
+    let res: ReverseSimulateRouteResponse = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+    assert_eq!(res.offer_amount, Uint128(1_003));
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_100);
+
+    let res = receive(&mut deps, "token_a", "user", 1_100, exact_output_route(998)).unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_ab", 1_003, 1)
+    );
+
+    let res = hop_return(&mut deps, "token_b", "pair_ab", 1_001, 1).unwrap();
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_b", "pair_bc", 1_001, 1)
+    );
+    hop_return(&mut deps, "token_c", "pair_bc", 999, 1).unwrap();
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 97);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_c"), &router, 999);
+    let res = callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+    let msgs: Vec<_> = res.messages.iter().map(snip20_msg).collect();
+    assert_eq!(
+        msgs,
+        vec![
+            transfer("token_c", "recipient", 998),
+            // the bit more than asked for, and the input that wasn't needed, go to the sender
+            transfer("token_c", "user", 1),
+            transfer("token_a", "user", 97),
+        ]
+    );
+}
+
+#[test]
+fn exact_output_route_rejects_a_short_input() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    match receive(&mut deps, "token_a", "user", 1_000, exact_output_route(998)) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "route requires an input of 1003, but only 1000 was sent"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    match receive(&mut deps, "token_a", "user", 1_000, exact_output_route(0)) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "cannot simulate a zero return_amount")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn rounding_dust_goes_back_to_the_sender() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    // the router already holds some token_b, which isn't the route's to give away
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_b"), &router, 50);
+
+    let mut dusty_route = route(vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]);
+    dusty_route.to = HumanAddr::from("recipient");
+    receive(
+        &mut deps,
+        "token_a",
+        "user",
+        1_000,
+        to_binary(&dusty_route).unwrap(),
+    )
+    .unwrap();
+    let res = hop_return(&mut deps, "token_b", "pair_ab", 999, 1).unwrap();
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        swap_msg("token_b", "pair_bc", 999, "recipient")
+    );
+
+    // the route left 3 token_b behind
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_b"), &router, 53);
+    let res = callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(snip20_msg(&res.messages[0]), transfer("token_b", "user", 3));
+}
+
+#[test]
+fn recover_funds() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+
+    let msg = HandleMsg::RecoverFunds {
+        token: Token::Native {
+            denom: "uluna".to_string(),
+        },
+        amount: Uint128(100),
+        to: HumanAddr::from("owner"),
+        snip20_send_msg: None,
+    };
+    match handle(&mut deps, mock_env("user", &[]), msg.clone()) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let res = handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+            to_address: HumanAddr::from("owner"),
+            amount: vec![Coin::new(100, "uluna")],
+        })]
+    );
+
+    let send_msg = Some(to_binary(&"deposit").unwrap());
+    let msg = HandleMsg::RecoverFunds {
+        token: token("token_a"),
+        amount: Uint128(100),
+        to: HumanAddr::from("vault"),
+        snip20_send_msg: send_msg.clone(),
+    };
+    let res = handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        (
+            HumanAddr::from("token_a"),
+            Snip20Msg::Send {
+                recipient: HumanAddr::from("vault"),
+                amount: Uint128(100),
+                msg: send_msg,
+            }
+        )
+    );
+}
+
+/// Swaps 1000 token_a through pair_ab and pair_bc, and returns the response of its FinalizeRoute
+fn swap_a_to_c(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    route_id: u64,
+) -> StdResult<HandleResponse> {
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    let msg = to_binary(&route(vec![
+        hop("token_a", "pair_ab"),
+        hop("token_b", "pair_bc"),
+    ]))
+    .unwrap();
+    receive(deps, "token_a", "user", 1_000, msg)?;
+    hop_return(deps, "token_b", "pair_ab", 999, route_id)?;
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    callback(deps, HandleMsg::FinalizeRoute { route_id })
+}
+
+/// Adds a program that pays 10 reward tokens per hop, and funds it with `funding`
+fn add_reward_program(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    end_time: u64,
+    funding: u128,
+    min_hop_amount: Option<Uint128>,
+) {
+    let msg = HandleMsg::AddRewardProgram {
+        program: RewardProgram {
+            reward_token: Snip20Data {
+                address: HumanAddr::from("reward"),
+                code_hash: "reward_hash".to_string(),
+            },
+            rate: RewardRate::PerHop(Uint128(10)),
+            eligible_tokens: None,
+            eligible_pairs: None,
+            min_hop_amount,
+            budget: Uint128(25),
+            end_time,
+        },
+    };
+    handle(deps, mock_env("owner", &[]), msg).unwrap();
+
+    let msg = to_binary(&ReceiveMsg::FundRewardProgram { program_id: 1 }).unwrap();
+    receive(deps, "reward", "owner", funding, msg).unwrap();
+}
+
+fn reward_escrow(deps: &Extern<MockStorage, MockApi, WasmMockQuerier>) -> Uint128 {
+    let res: RewardProgramsResponse =
+        from_binary(&query(deps, QueryMsg::RewardPrograms {}).unwrap()).unwrap();
+    res.programs[0].escrow
+}
+
+#[test]
+fn reward_programs_pay_out_of_their_escrow() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let end_time = mock_env("owner", &[]).block.time + 100;
+    add_reward_program(&mut deps, end_time, 25, None);
+    // whatever else the router holds is not the program's to pay out
+    deps.querier.with_token_balance(
+        &HumanAddr::from("reward"),
+        &HumanAddr::from(MOCK_CONTRACT_ADDR),
+        1_000_000,
+    );
+
+    // 10 per hop
+    let res = swap_a_to_c(&mut deps, 1).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(snip20_msg(&res.messages[0]), transfer("reward", "user", 20));
+    assert_eq!(reward_escrow(&deps), Uint128(5));
+
+    // the rest of the escrow
+    let res = swap_a_to_c(&mut deps, 2).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(snip20_msg(&res.messages[0]), transfer("reward", "user", 5));
+    assert_eq!(reward_escrow(&deps), Uint128::zero());
+
+    // the escrow ran out
+    let res = swap_a_to_c(&mut deps, 3).unwrap();
+    assert_eq!(res.messages.len(), 0);
+}
+
+#[test]
+fn reward_programs_dont_pay_out_dust_hops() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let end_time = mock_env("owner", &[]).block.time + 100;
+    add_reward_program(&mut deps, end_time, 25, Some(Uint128(1_000)));
+
+    // the hop of 1000 token_a is rewarded, the hop of 999 token_b is not
+    let res = swap_a_to_c(&mut deps, 1).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(snip20_msg(&res.messages[0]), transfer("reward", "user", 10));
+    assert_eq!(reward_escrow(&deps), Uint128(15));
+}
+
+#[test]
+fn reward_program_funding_is_checked() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+
+    let end_time = mock_env("owner", &[]).block.time + 100;
+    add_reward_program(&mut deps, end_time, 20, None);
+
+    let msg = to_binary(&ReceiveMsg::FundRewardProgram { program_id: 1 }).unwrap();
+    match receive(&mut deps, "reward", "owner", 6, msg.clone()) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "funding exceeds the budget of reward program 1")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    match receive(&mut deps, "token_a", "owner", 5, msg.clone()) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "reward program 1 can only be funded with its reward token"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let msg = to_binary(&ReceiveMsg::FundRewardProgram { program_id: 2 }).unwrap();
+    match receive(&mut deps, "reward", "owner", 5, msg) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "cannot find reward program 2")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn ended_reward_programs_stop_paying_out() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let end_time = mock_env("owner", &[]).block.time + 100;
+    add_reward_program(&mut deps, end_time, 25, None);
+
+    // a route after the program's end_time
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    let msg = to_binary(&route(vec![
+        hop("token_a", "pair_ab"),
+        hop("token_b", "pair_bc"),
+    ]))
+    .unwrap();
+    receive(&mut deps, "token_a", "user", 1_000, msg).unwrap();
+    hop_return(&mut deps, "token_b", "pair_ab", 999, 1).unwrap();
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+
+    let mut env = mock_env(MOCK_CONTRACT_ADDR, &[]);
+    env.block.time = end_time;
+    let res = handle(&mut deps, env, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    let msg = to_binary(&ReceiveMsg::FundRewardProgram { program_id: 1 }).unwrap();
+    let mut env = mock_env("reward", &[]);
+    env.block.time = end_time;
+    let fund_msg = HandleMsg::Receive {
+        from: HumanAddr::from("owner"),
+        msg: Some(msg),
+        amount: Uint128(1),
+    };
+    match handle(&mut deps, env, fund_msg) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "reward program 1 has ended"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // only the owner ends programs, and gets back what is left of the escrow
+    let msg = HandleMsg::EndRewardProgram { program_id: 1 };
+    match handle(&mut deps, mock_env("user", &[]), msg.clone()) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+    let res = handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        transfer("reward", "owner", 25)
+    );
+}
+
+fn snip20_data(address: &str) -> Snip20Data {
+    Snip20Data {
+        address: HumanAddr::from(address),
+        code_hash: format!("{}_hash", address),
+    }
+}
+
+fn supported_tokens(
+    deps: &Extern<MockStorage, MockApi, WasmMockQuerier>,
+    start_after: Option<&str>,
+    limit: Option<u32>,
+) -> Vec<Snip20Data> {
+    let msg = QueryMsg::SupportedTokens {
+        start_after: start_after.map(HumanAddr::from),
+        limit,
+    };
+    let res: SupportedTokensResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+    res.tokens
+}
+
+#[test]
+fn register_and_unregister_tokens() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+
+    let msg = HandleMsg::RegisterTokens {
+        tokens: vec![snip20_data("token_a"), snip20_data("token_b")],
+    };
+    match handle(&mut deps, mock_env("user", &[]), msg.clone()) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the router registers its receive and sets a viewing key with every new token
+    let res = handle(&mut deps, mock_env("owner", &[]), msg.clone()).unwrap();
+    assert_eq!(res.messages.len(), 4);
+    let res = handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    assert_eq!(
+        supported_tokens(&deps, None, None),
+        vec![snip20_data("token_a"), snip20_data("token_b")]
+    );
+
+    // registering a token with another code hash overwrites the registered one
+    let rehashed_token = Snip20Data {
+        address: HumanAddr::from("token_a"),
+        code_hash: "token_a_new_hash".to_string(),
+    };
+    let msg = HandleMsg::RegisterTokens {
+        tokens: vec![rehashed_token.clone()],
+    };
+    let res = handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+    assert_eq!(res.messages.len(), 2);
+    match &res.messages[0] {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            callback_code_hash, ..
+        }) => assert_eq!(callback_code_hash, "token_a_new_hash"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+    assert_eq!(
+        supported_tokens(&deps, None, None),
+        vec![rehashed_token, snip20_data("token_b")]
+    );
+    let msg = HandleMsg::RegisterTokens {
+        tokens: vec![snip20_data("token_a")],
+    };
+    handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+    assert_eq!(
+        supported_tokens(&deps, Some("token_a"), Some(1)),
+        vec![snip20_data("token_b")]
+    );
+
+    // hops can leave out the code hashes of registered tokens
+    let mut unhashed_hop = hop("token_a", "pair_ab");
+    unhashed_hop.from_token = Token::Snip20(Snip20Data {
+        address: HumanAddr::from("token_a"),
+        code_hash: String::new(),
+    });
+    let res = simulate_route(&deps, vec![unhashed_hop.clone()], 1_000).unwrap();
+    assert_eq!(res.return_amount, Uint128(999));
+
+    let msg = HandleMsg::UnregisterTokens {
+        tokens: vec![HumanAddr::from("token_a")],
+    };
+    handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+    assert_eq!(
+        supported_tokens(&deps, None, None),
+        vec![snip20_data("token_b")]
+    );
+    match simulate_route(&deps, vec![unhashed_hop], 1_000) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "the code hash of token_a is required, as it is not registered"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn register_tokens_rejects_bad_tokens() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+
+    let msg = HandleMsg::RegisterTokens {
+        tokens: vec![Snip20Data {
+            address: HumanAddr::from("token_a"),
+            code_hash: String::new(),
+        }],
+    };
+    match handle(&mut deps, mock_env("owner", &[]), msg) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "cannot register token_a without its code hash")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let msg = HandleMsg::UnregisterTokens {
+        tokens: vec![HumanAddr::from("token_a")],
+    };
+    match handle(&mut deps, mock_env("user", &[]), msg.clone()) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+    match handle(&mut deps, mock_env("owner", &[]), msg) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "token_a is not registered"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+/// A cycle from token_a back to itself, through pair_ab and another token_a/token_b pair
+fn cycle_route(min_profit: Option<u128>) -> Route {
+    let mut cycle = route(vec![hop("token_a", "pair_ab"), hop("token_b", "pair_ab_2")]);
+    cycle.min_profit = min_profit.map(Uint128);
+    cycle.profit_to = Some(HumanAddr::from("profit"));
+    cycle
+}
+
+/// Swaps the cycle, which returns `return_amount` of the 1000 token_a sent
+fn swap_cycle(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    return_amount: u128,
+) -> StdResult<HandleResponse> {
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+
+    let msg = to_binary(&cycle_route(Some(5))).unwrap();
+    let res = receive(deps, "token_a", "arbitrageur", 1_000, msg)?;
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_ab", 1_000, 1)
+    );
+
+    // the router collects the return of a cycle, to check its profit
+    let res = hop_return(deps, "token_b", "pair_ab", 999, 1)?;
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_b", "pair_ab_2", 999, 1)
+    );
+    hop_return(deps, "token_a", "pair_ab_2", return_amount, 1)?;
+
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, return_amount);
+    callback(deps, HandleMsg::FinalizeRoute { route_id: 1 })
+}
+
+#[test]
+fn profitable_cycle_pays_out_the_profit() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+
+    let res = swap_cycle(&mut deps, 1_010).unwrap();
+    let msgs: Vec<_> = res.messages.iter().map(snip20_msg).collect();
+    assert_eq!(
+        msgs,
+        vec![
+            transfer("token_a", "user", 1_000),
+            transfer("token_a", "profit", 10),
+        ]
+    );
+}
+
+#[test]
+fn unprofitable_cycle_reverts() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    let mut cycle = cycle_route(None);
+    cycle.return_token = Some(token("token_a"));
+    match receive(
+        &mut deps,
+        "token_a",
+        "arbitrageur",
+        1_000,
+        to_binary(&cycle).unwrap(),
+    ) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "cannot route a token to itself without a min_profit")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    match swap_cycle(&mut deps, 1_004) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "route returned 1004 for an input of 1000, short of min_profit 5"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+fn batch_deposit(
+    deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>,
+    sender: &str,
+    amount: u128,
+) -> StdResult<HandleResponse> {
+    let msg = to_binary(&ReceiveMsg::BatchDeposit {}).unwrap();
+    receive(deps, "token_a", sender, amount, msg)
+}
+
+fn register_token_a(deps: &mut Extern<MockStorage, MockApi, WasmMockQuerier>) {
+    let msg = HandleMsg::RegisterTokens {
+        tokens: vec![snip20_data("token_a")],
+    };
+    handle(deps, mock_env("owner", &[]), msg).unwrap();
+}
+
+#[test]
+fn batch_routes_swap_the_deposits() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+    register_token_a(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    batch_deposit(&mut deps, "user", 600).unwrap();
+    batch_deposit(&mut deps, "user", 400).unwrap();
+
+    let msg = HandleMsg::ExecuteBatch {
+        routes: vec![
+            BatchRoute {
+                amount: Uint128(600),
+                route: route(vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]),
+            },
+            BatchRoute {
+                amount: Uint128(400),
+                route: route(vec![hop("token_a", "pair_ac")]),
+            },
+        ],
+    };
+    let res = handle(&mut deps, mock_env("user", &[]), msg).unwrap();
+    let start_batch_route = route_callback(HandleMsg::StartBatchRoute {
+        sender: HumanAddr::from("user"),
+    });
+    assert_eq!(
+        res.messages,
+        vec![
+            start_batch_route.clone(),
+            start_batch_route,
+            route_callback(HandleMsg::FinalizeBatch {
+                sender: HumanAddr::from("user"),
+            }),
+        ]
+    );
+
+    // only the router starts the routes of a batch
+    let msg = HandleMsg::StartBatchRoute {
+        sender: HumanAddr::from("user"),
+    };
+    match handle(&mut deps, mock_env("user", &[]), msg.clone()) {
+        Err(StdError::Unauthorized { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let res = callback(&mut deps, msg.clone()).unwrap();
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        hop_msg("token_a", "pair_ab", 600, 1)
+    );
+    hop_return(&mut deps, "token_b", "pair_ab", 599, 1).unwrap();
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 400);
+    let res = callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    let res = callback(&mut deps, msg).unwrap();
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        swap_msg("token_a", "pair_ac", 400, "user")
+    );
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 0);
+    callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 2 }).unwrap();
+
+    let msg = HandleMsg::FinalizeBatch {
+        sender: HumanAddr::from("user"),
+    };
+    callback(&mut deps, msg).unwrap();
+    match handle(&mut deps, mock_env("user", &[]), HandleMsg::CancelBatch {}) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "cannot find batch"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn spoofed_batch_deposits_are_rejected() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+    register_token_a(&mut deps);
+
+    // a contract that isn't a registered token makes up a deposit from the victim
+    let msg = to_binary(&ReceiveMsg::BatchDeposit {}).unwrap();
+    match receive(&mut deps, "attacker", "victim", 1, msg) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            "attacker is not registered, only registered tokens can be deposited"
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+    match handle(
+        &mut deps,
+        mock_env("victim", &[]),
+        HandleMsg::CancelBatch {},
+    ) {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "cannot find batch"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // so the victim's own batch only holds what the victim deposited
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    batch_deposit(&mut deps, "victim", 1_000).unwrap();
+    let msg = HandleMsg::ExecuteBatch {
+        routes: vec![BatchRoute {
+            amount: Uint128(1_000),
+            route: route(vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]),
+        }],
+    };
+    handle(&mut deps, mock_env("victim", &[]), msg).unwrap();
+}
+
+#[test]
+fn failed_batch_keeps_the_deposits_for_a_refund() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+    register_token_a(&mut deps);
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    batch_deposit(&mut deps, "user", 1_000).unwrap();
+
+    for (amount, error) in [
+        (1_500, "routes swap more than was deposited"),
+        (500, "routes must swap everything that was deposited"),
+    ]
+    .iter()
+    {
+        let msg = HandleMsg::ExecuteBatch {
+            routes: vec![BatchRoute {
+                amount: Uint128(*amount),
+                route: route(vec![hop("token_a", "pair_ab"), hop("token_b", "pair_bc")]),
+            }],
+        };
+        match handle(&mut deps, mock_env("user", &[]), msg) {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, *error),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    }
+
+    // a batch that was never executed can't be finalized
+    let msg = HandleMsg::FinalizeBatch {
+        sender: HumanAddr::from("user"),
+    };
+    match callback(&mut deps, msg) {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "cannot finalize: batch is not done")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let res = handle(&mut deps, mock_env("user", &[]), HandleMsg::CancelBatch {}).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        transfer("token_a", "user", 1_000)
+    );
+}
+
+#[test]
+fn rewards_dont_pay_out_batch_deposits() {
+    let mut deps = mock_dependencies(20, &[]);
+    init_router(&mut deps);
+    with_abc_pairs(&mut deps);
+    register_token_a(&mut deps);
+
+    // a program that rewards with the deposited token, which it was never funded with
+    let msg = HandleMsg::AddRewardProgram {
+        program: RewardProgram {
+            reward_token: snip20_data("token_a"),
+            rate: RewardRate::PerHop(Uint128(10)),
+            eligible_tokens: None,
+            eligible_pairs: None,
+            min_hop_amount: None,
+            budget: Uint128(100),
+            end_time: mock_env("owner", &[]).block.time + 100,
+        },
+    };
+    handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+    let router = HumanAddr::from(MOCK_CONTRACT_ADDR);
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    batch_deposit(&mut deps, "saver", 1_000).unwrap();
+
+    // another user's route, while the deposit is parked in the router
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 2_000);
+    let msg = to_binary(&route(vec![
+        hop("token_a", "pair_ab"),
+        hop("token_b", "pair_bc"),
+    ]))
+    .unwrap();
+    receive(&mut deps, "token_a", "user", 1_000, msg).unwrap();
+    hop_return(&mut deps, "token_b", "pair_ab", 999, 1).unwrap();
+    deps.querier
+        .with_token_balance(&HumanAddr::from("token_a"), &router, 1_000);
+    let res = callback(&mut deps, HandleMsg::FinalizeRoute { route_id: 1 }).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    let res = handle(&mut deps, mock_env("saver", &[]), HandleMsg::CancelBatch {}).unwrap();
+    assert_eq!(
+        snip20_msg(&res.messages[0]),
+        transfer("token_a", "saver", 1_000)
+    );
+}
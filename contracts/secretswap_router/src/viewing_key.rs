@@ -0,0 +1,17 @@
+use std::convert::TryInto;
+
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+pub const VIEWING_KEY_SIZE: usize = 32;
+
+pub fn hash_viewing_key(key: &str) -> [u8; VIEWING_KEY_SIZE] {
+    Sha256::digest(key.as_bytes())
+        .as_slice()
+        .try_into()
+        .expect("Wrong password length")
+}
+
+pub fn check_viewing_key(key: &str, hashed_key: &[u8]) -> bool {
+    bool::from(hash_viewing_key(key)[..].ct_eq(hashed_key))
+}
@@ -1,21 +1,27 @@
 /// This contract implements SNIP-20 standard:
 /// https://github.com/SecretFoundation/SNIPs/blob/master/SNIP-20.md
 use cosmwasm_std::{
-    log, to_binary, Api, Binary, CanonicalAddr, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
-    InitResponse, Querier, QueryResult, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
-    WasmMsg,
+    log, to_binary, Api, BankMsg, Binary, BlockInfo, CanonicalAddr, Coin, CosmosMsg, Env, Extern,
+    HandleResponse, HumanAddr, InitResponse, Querier, QueryResult, ReadonlyStorage, StdError,
+    StdResult, Storage, Uint128, WasmMsg,
 };
 
+use crate::batch;
 use crate::msg::{
-    space_pad, ContractStatusLevel, HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg,
-    ResponseStatus::Success,
+    space_pad, AllowanceGiven, AllowanceReceived, ContractStatusLevel, HandleAnswer, HandleMsg,
+    InitMsg, MinterQuota, QueryAnswer, QueryMsg, QueryWithPermit, ResponseStatus::Success,
 };
+use crate::observer::{BalanceChangedMsg, Observer};
+use crate::permit::{self, Permission, Permit};
 use crate::rand::sha_256;
 use crate::receiver::Snip20ReceiveMsg;
 use crate::state::{
-    get_receiver_hash, get_transfers, read_allowance, read_viewing_key, set_receiver_hash,
-    store_transfer, write_allowance, write_viewing_key, Balances, Config, Constants,
-    ReadonlyBalances, ReadonlyConfig,
+    get_owners, get_receiver_hash, get_spenders, get_transfers, get_txs, is_observed_account,
+    is_permit_revoked, read_allowance, read_balance_at, read_minter_allowance,
+    read_total_supply_at, read_viewing_key, revoke_permit, set_observed_account, set_receiver_hash,
+    store_burn, store_deposit, store_mint, store_redeem, store_transfer, write_allowance,
+    write_balance_checkpoint, write_minter_allowance, write_supply_checkpoint, write_viewing_key,
+    Balances, Config, Constants, ReadonlyBalances, ReadonlyConfig,
 };
 use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
 
@@ -29,6 +35,7 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<InitResponse> {
     let init_config = msg.config();
     let mut total_supply: u128 = 0;
+    let mut initial_accounts = vec![];
     {
         let mut balances = Balances::from_storage(&mut deps.storage);
         let initial_balances = msg.initial_balances.unwrap_or_default();
@@ -36,6 +43,7 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
             let balance_address = deps.api.canonical_address(&balance.address)?;
             let amount = balance.amount.u128();
             balances.set_account_balance(&balance_address, amount);
+            initial_accounts.push(balance_address);
             if let Some(new_total_supply) = total_supply.checked_add(amount) {
                 total_supply = new_total_supply;
             } else {
@@ -61,7 +69,16 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         return Err(StdError::generic_err("Decimals must not exceed 18"));
     }
 
-    let admin = msg.admin.unwrap_or_else(|| env.message.sender);
+    let max_supply = msg.get_cap().map(|cap| cap.u128());
+    if let Some(max_supply) = max_supply {
+        if total_supply > max_supply {
+            return Err(StdError::generic_err(
+                "The sum of all initial balances exceeds the max supply",
+            ));
+        }
+    }
+
+    let admin = msg.admin.unwrap_or_else(|| env.message.sender.clone());
 
     let prng_seed_hashed = sha_256(&msg.prng_seed.0);
 
@@ -72,12 +89,23 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         decimals: msg.decimals,
         admin: admin.clone(),
         prng_seed: prng_seed_hashed.to_vec(),
+        contract_address: env.contract.address,
         total_supply_is_public: init_config.public_total_supply(),
+        deposit_is_enabled: init_config.deposit_enabled(),
+        redeem_is_enabled: init_config.redeem_enabled(),
+        mint_is_enabled: init_config.mint_enabled(),
+        burn_is_enabled: init_config.burn_enabled(),
+        max_supply,
     })?;
     config.set_minters(vec![admin])?;
     config.set_total_supply(total_supply);
     config.set_contract_status(ContractStatusLevel::NormalRun);
 
+    for account in &initial_accounts {
+        write_balance_checkpoint(&mut deps.storage, account, env.block.height)?;
+    }
+    write_supply_checkpoint(&mut deps.storage, env.block.height)?;
+
     if let Some(hook) = msg.init_hook {
         Ok(InitResponse {
             messages: vec![CosmosMsg::Wasm(WasmMsg::Execute {
@@ -114,6 +142,11 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         ContractStatusLevel::StopAll | ContractStatusLevel::StopAllButRedeems => {
             let response = match msg {
                 HandleMsg::SetContractStatus { level, .. } => set_contract_status(deps, env, level),
+                HandleMsg::Redeem { amount, .. }
+                    if contract_status == ContractStatusLevel::StopAllButRedeems =>
+                {
+                    try_redeem(deps, env, amount)
+                }
                 _ => Err(StdError::generic_err(
                     "This contract is stopped and this action is not allowed",
                 )),
@@ -124,19 +157,33 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     }
 
     let response = match msg {
+        // Native
+        HandleMsg::Deposit { .. } => try_deposit(deps, env),
+        HandleMsg::Redeem { amount, .. } => try_redeem(deps, env, amount),
+
         // Base
         HandleMsg::Transfer {
-            recipient, amount, ..
-        } => try_transfer(deps, env, &recipient, amount),
+            recipient,
+            amount,
+            memo,
+            ..
+        } => try_transfer(deps, env, &recipient, amount, memo),
         HandleMsg::Send {
             recipient,
             amount,
             msg,
+            memo,
             ..
-        } => try_send(deps, env, &recipient, amount, msg),
+        } => try_send(deps, env, &recipient, amount, msg, memo),
+        HandleMsg::BatchTransfer { actions, .. } => try_batch_transfer(deps, env, actions),
+        HandleMsg::BatchSend { actions, .. } => try_batch_send(deps, env, actions),
         HandleMsg::RegisterReceive { code_hash, .. } => try_register_receive(deps, env, code_hash),
         HandleMsg::CreateViewingKey { entropy, .. } => try_create_key(deps, env, entropy),
         HandleMsg::SetViewingKey { key, .. } => try_set_key(deps, env, key),
+        HandleMsg::RevokePermit { permit_name, .. } => revoke_permit_name(deps, env, permit_name),
+        HandleMsg::SetBalanceObservation { enabled, .. } => {
+            set_balance_observation(deps, env, enabled)
+        }
 
         // Allowance
         HandleMsg::IncreaseAllowance {
@@ -155,30 +202,51 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             owner,
             recipient,
             amount,
+            memo,
             ..
-        } => try_transfer_from(deps, env, &owner, &recipient, amount),
+        } => try_transfer_from(deps, env, &owner, &recipient, amount, memo),
         HandleMsg::SendFrom {
             owner,
             recipient,
             amount,
             msg,
+            memo,
             ..
-        } => try_send_from(deps, env, &owner, &recipient, amount, msg),
+        } => try_send_from(deps, env, &owner, &recipient, amount, msg, memo),
+        HandleMsg::BatchTransferFrom { actions, .. } => try_batch_transfer_from(deps, env, actions),
 
         // Burn
-        HandleMsg::Burn { amount, .. } => try_burn(deps, env, amount),
+        HandleMsg::Burn { amount, memo, .. } => try_burn(deps, env, amount, memo),
+        HandleMsg::BurnFrom {
+            owner,
+            amount,
+            memo,
+            ..
+        } => try_burn_from(deps, env, &owner, amount, memo),
+        HandleMsg::BatchBurnFrom { actions, .. } => try_batch_burn_from(deps, env, actions),
 
         // Mint
         HandleMsg::Mint {
-            amount, recipient, ..
-        } => try_mint(deps, env, recipient, amount),
+            amount,
+            recipient,
+            memo,
+            ..
+        } => try_mint(deps, env, recipient, amount, memo),
+        HandleMsg::BatchMint { actions, .. } => try_batch_mint(deps, env, actions),
 
         // Other
         HandleMsg::ChangeAdmin { address, .. } => change_admin(deps, env, address),
         HandleMsg::SetContractStatus { level, .. } => set_contract_status(deps, env, level),
+        HandleMsg::SetMintEnabled { enabled, .. } => set_mint_enabled(deps, env, enabled),
+        HandleMsg::SetBurnEnabled { enabled, .. } => set_burn_enabled(deps, env, enabled),
         HandleMsg::AddMinters { minters, .. } => add_minters(deps, env, minters),
         HandleMsg::RemoveMinters { minters, .. } => remove_minters(deps, env, minters),
         HandleMsg::SetMinters { minters, .. } => set_minters(deps, env, minters),
+        HandleMsg::SetMinterAllowance {
+            minter, allowance, ..
+        } => set_minter_allowance(deps, env, minter, allowance),
+        HandleMsg::AddObservers { observers, .. } => add_observers(deps, env, observers),
+        HandleMsg::RemoveObservers { observers, .. } => remove_observers(deps, env, observers),
     };
 
     pad_response(response)
@@ -187,12 +255,236 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
 pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
     match msg {
         QueryMsg::TokenInfo {} => query_token_info(&deps.storage),
+        QueryMsg::TokenConfig {} => query_token_config(&deps.storage),
         QueryMsg::ExchangeRate {} => query_exchange_rate(),
         QueryMsg::Minters { .. } => query_minters(deps),
+        QueryMsg::Observers {} => query_observers(&deps.storage),
+        QueryMsg::MintingInfo {} => query_minting_info(deps),
+        QueryMsg::TotalSupplyAt { height } => query_total_supply_at(&deps.storage, height),
+        QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
         _ => authenticated_queries(deps, msg),
     }
 }
 
+fn permit_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> QueryResult {
+    let contract_address = ReadonlyConfig::from_storage(&deps.storage)
+        .constants()?
+        .contract_address;
+    let account = permit::validate(&deps.api, &permit, &contract_address)?;
+
+    let canonical_account = deps.api.canonical_address(&account)?;
+    if is_permit_revoked(
+        &deps.storage,
+        &canonical_account,
+        &permit.params.permit_name,
+    ) {
+        return Err(StdError::generic_err(format!(
+            "Permit {:?} was revoked by account {:?}",
+            permit.params.permit_name,
+            account.as_str()
+        )));
+    }
+
+    match query {
+        QueryWithPermit::AllowancesGiven { page, page_size } => {
+            check_permission(&permit, Permission::Allowance)?;
+            query_allowances_given(deps, account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::AllowancesReceived { page, page_size } => {
+            check_permission(&permit, Permission::Allowance)?;
+            query_allowances_received(deps, account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Balance {} => {
+            check_permission(&permit, Permission::Balance)?;
+            query_balance(deps, &account)
+        }
+        QueryWithPermit::BalanceAt { height } => {
+            check_permission(&permit, Permission::Balance)?;
+            query_balance_at(deps, &account, height)
+        }
+        QueryWithPermit::TransferHistory { page, page_size } => {
+            check_permission(&permit, Permission::History)?;
+            query_transfers(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::TransactionHistory { page, page_size } => {
+            check_permission(&permit, Permission::History)?;
+            query_transactions(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Allowance { owner, spender } => {
+            check_permission(&permit, Permission::Allowance)?;
+            if account != owner && account != spender {
+                return Err(StdError::generic_err(format!(
+                    "Cannot query allowance. Requires permit for either owner {:?} or spender {:?}, got permit for {:?}",
+                    owner.as_str(),
+                    spender.as_str(),
+                    account.as_str()
+                )));
+            }
+            try_check_allowance(deps, owner, spender)
+        }
+    }
+}
+
+fn check_permission(permit: &Permit, permission: Permission) -> StdResult<()> {
+    if permit.check_permission(&permission) {
+        Ok(())
+    } else {
+        Err(StdError::generic_err(format!(
+            "No permission to query, got permissions {:?}, expected {:?}",
+            permit.params.permissions, permission
+        )))
+    }
+}
+
+fn try_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut amount = Uint128::zero();
+
+    for coin in &env.message.sent_funds {
+        if coin.denom == "uscrt" {
+            amount = coin.amount
+        } else {
+            return Err(StdError::generic_err(
+                "Tried to deposit an unsupported token",
+            ));
+        }
+    }
+
+    if amount.is_zero() {
+        return Err(StdError::generic_err("No funds were sent to be deposited"));
+    }
+
+    let raw_amount = amount.u128();
+
+    let mut config = Config::from_storage(&mut deps.storage);
+    let constants = config.constants()?;
+    if !constants.deposit_is_enabled {
+        return Err(StdError::generic_err(
+            "Deposit functionality is not enabled for this token.",
+        ));
+    }
+
+    let total_supply = config.total_supply();
+    if let Some(total_supply) = total_supply.checked_add(raw_amount) {
+        check_max_supply(&constants, total_supply)?;
+        config.set_total_supply(total_supply);
+    } else {
+        return Err(StdError::generic_err(
+            "This deposit would overflow the currency's total supply",
+        ));
+    }
+
+    let sender_address = deps.api.canonical_address(&env.message.sender)?;
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let account_balance = balances.balance(&sender_address);
+    if let Some(account_balance) = account_balance.checked_add(raw_amount) {
+        balances.set_account_balance(&sender_address, account_balance);
+    } else {
+        return Err(StdError::generic_err(
+            "This deposit would overflow your balance",
+        ));
+    }
+
+    write_balance_checkpoint(&mut deps.storage, &sender_address, env.block.height)?;
+    write_supply_checkpoint(&mut deps.storage, env.block.height)?;
+
+    store_deposit(
+        &mut deps.storage,
+        &sender_address,
+        amount,
+        "uscrt".to_string(),
+        &env.block,
+    )?;
+
+    let mut messages = vec![];
+    notify_observers(deps, &mut messages, &sender_address)?;
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::Deposit { status: Success })?),
+    };
+
+    Ok(res)
+}
+
+fn try_redeem<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let config = ReadonlyConfig::from_storage(&deps.storage);
+    let constants = config.constants()?;
+    if !constants.redeem_is_enabled {
+        return Err(StdError::generic_err(
+            "Redeem functionality is not enabled for this token.",
+        ));
+    }
+
+    let sender_address = deps.api.canonical_address(&env.message.sender)?;
+    let raw_amount = amount.u128();
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let account_balance = balances.balance(&sender_address);
+
+    if let Some(account_balance) = account_balance.checked_sub(raw_amount) {
+        balances.set_account_balance(&sender_address, account_balance);
+    } else {
+        return Err(StdError::generic_err(format!(
+            "insufficient funds to redeem: balance={}, required={}",
+            account_balance, raw_amount
+        )));
+    }
+
+    let mut config = Config::from_storage(&mut deps.storage);
+    let total_supply = config.total_supply();
+    if let Some(total_supply) = total_supply.checked_sub(raw_amount) {
+        config.set_total_supply(total_supply);
+    } else {
+        return Err(StdError::generic_err(
+            "You are trying to redeem more tokens than what is available in the total supply",
+        ));
+    }
+
+    write_balance_checkpoint(&mut deps.storage, &sender_address, env.block.height)?;
+    write_supply_checkpoint(&mut deps.storage, env.block.height)?;
+
+    store_redeem(
+        &mut deps.storage,
+        &sender_address,
+        amount,
+        "uscrt".to_string(),
+        &env.block,
+    )?;
+
+    let withdrawal_coins: Vec<Coin> = vec![Coin {
+        denom: "uscrt".to_string(),
+        amount,
+    }];
+
+    let mut messages = vec![CosmosMsg::Bank(BankMsg::Send {
+        from_address: env.contract.address,
+        to_address: env.message.sender,
+        amount: withdrawal_coins,
+    })];
+    notify_observers(deps, &mut messages, &sender_address)?;
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::Redeem { status: Success })?),
+    };
+
+    Ok(res)
+}
+
 /// Burn tokens
 ///
 /// Remove `amount` tokens from the system irreversibly, from signer account
@@ -202,27 +494,141 @@ fn try_burn<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<HandleResponse> {
     let sender_address = deps.api.canonical_address(&env.message.sender)?;
-    let amount = amount.u128();
 
-    let mut balances = Balances::from_storage(&mut deps.storage);
-    let mut account_balance = balances.balance(&sender_address);
+    burn_impl(
+        &mut deps.storage,
+        &sender_address,
+        &sender_address,
+        amount,
+        memo,
+        &env.block,
+    )?;
+
+    let mut messages = vec![];
+    notify_observers(deps, &mut messages, &sender_address)?;
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::Burn { status: Success })?),
+    };
+
+    Ok(res)
+}
+
+fn try_burn_from<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: &HumanAddr,
+    amount: Uint128,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    let spender_address = deps.api.canonical_address(&env.message.sender)?;
+    let owner_address = deps.api.canonical_address(owner)?;
+
+    use_allowance(
+        &mut deps.storage,
+        &env,
+        &owner_address,
+        &spender_address,
+        amount.u128(),
+    )?;
+    burn_impl(
+        &mut deps.storage,
+        &owner_address,
+        &spender_address,
+        amount,
+        memo,
+        &env.block,
+    )?;
+
+    let mut messages = vec![];
+    notify_observers(deps, &mut messages, &owner_address)?;
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::BurnFrom { status: Success })?),
+    };
+
+    Ok(res)
+}
+
+fn try_batch_burn_from<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<batch::BurnFromAction>,
+) -> StdResult<HandleResponse> {
+    let spender_address = deps.api.canonical_address(&env.message.sender)?;
+    let mut messages = vec![];
+
+    for action in actions {
+        let owner_address = deps.api.canonical_address(&action.owner)?;
+        use_allowance(
+            &mut deps.storage,
+            &env,
+            &owner_address,
+            &spender_address,
+            action.amount.u128(),
+        )?;
+        burn_impl(
+            &mut deps.storage,
+            &owner_address,
+            &spender_address,
+            action.amount,
+            action.memo,
+            &env.block,
+        )?;
+        notify_observers(deps, &mut messages, &owner_address)?;
+    }
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::BatchBurnFrom { status: Success })?),
+    };
+
+    Ok(res)
+}
+
+/// Removes `amount` from the balance of `owner` and from the total supply
+fn burn_impl<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    burner: &CanonicalAddr,
+    amount: Uint128,
+    memo: Option<String>,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let constants = ReadonlyConfig::from_storage(&*storage).constants()?;
+    if !constants.burn_is_enabled {
+        return Err(StdError::generic_err(
+            "Burn functionality is not enabled for this token.",
+        ));
+    }
+
+    let raw_amount = amount.u128();
 
-    if let Some(new_account_balance) = account_balance.checked_sub(amount) {
+    let mut balances = Balances::from_storage(storage);
+    let mut account_balance = balances.balance(owner);
+
+    if let Some(new_account_balance) = account_balance.checked_sub(raw_amount) {
         account_balance = new_account_balance;
     } else {
         return Err(StdError::generic_err(format!(
             "insufficient funds to burn: balance={}, required={}",
-            account_balance, amount
+            account_balance, raw_amount
         )));
     }
 
-    balances.set_account_balance(&sender_address, account_balance);
+    balances.set_account_balance(owner, account_balance);
 
-    let mut config = Config::from_storage(&mut deps.storage);
+    let mut config = Config::from_storage(storage);
     let mut total_supply = config.total_supply();
-    if let Some(new_total_supply) = total_supply.checked_sub(amount) {
+    if let Some(new_total_supply) = total_supply.checked_sub(raw_amount) {
         total_supply = new_total_supply;
     } else {
         return Err(StdError::generic_err(
@@ -231,49 +637,129 @@ fn try_burn<S: Storage, A: Api, Q: Querier>(
     }
     config.set_total_supply(total_supply);
 
+    write_balance_checkpoint(storage, owner, block.height)?;
+    write_supply_checkpoint(storage, block.height)?;
+
+    store_burn(
+        storage,
+        owner,
+        burner,
+        amount,
+        constants.symbol,
+        memo,
+        block,
+    )
+}
+
+fn try_mint<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    amount: Uint128,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    check_if_minter(&deps.storage, &env.message.sender)?;
+
+    let minter = deps.api.canonical_address(&env.message.sender)?;
+    let recipient = deps.api.canonical_address(&address)?;
+    mint_impl(
+        &mut deps.storage,
+        &minter,
+        &recipient,
+        amount,
+        memo,
+        &env.block,
+    )?;
+
+    let mut messages = vec![];
+    notify_observers(deps, &mut messages, &recipient)?;
+
     let res = HandleResponse {
-        messages: vec![],
+        messages,
         log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
-        data: Some(to_binary(&HandleAnswer::Burn { status: Success })?),
+        data: Some(to_binary(&HandleAnswer::Mint { status: Success })?),
     };
 
     Ok(res)
 }
 
-fn try_mint<S: Storage, A: Api, Q: Querier>(
+fn try_batch_mint<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    address: HumanAddr,
-    amount: Uint128,
+    actions: Vec<batch::MintAction>,
 ) -> StdResult<HandleResponse> {
-    let mut config = Config::from_storage(&mut deps.storage);
+    check_if_minter(&deps.storage, &env.message.sender)?;
+
+    let minter = deps.api.canonical_address(&env.message.sender)?;
+    let mut messages = vec![];
+    for action in actions {
+        let recipient = deps.api.canonical_address(&action.recipient)?;
+        mint_impl(
+            &mut deps.storage,
+            &minter,
+            &recipient,
+            action.amount,
+            action.memo,
+            &env.block,
+        )?;
+        notify_observers(deps, &mut messages, &recipient)?;
+    }
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::BatchMint { status: Success })?),
+    };
+
+    Ok(res)
+}
+
+/// Adds `amount` to the balance of `recipient` and to the total supply
+fn mint_impl<S: Storage>(
+    storage: &mut S,
+    minter: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    amount: Uint128,
+    memo: Option<String>,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let raw_amount = amount.u128();
 
-    let minters = config.minters();
-    if !minters.contains(&env.message.sender) {
+    let mut config = Config::from_storage(storage);
+    let constants = config.constants()?;
+    if !constants.mint_is_enabled {
         return Err(StdError::generic_err(
-            "Minting is allowed to minter accounts only",
+            "Mint functionality is not enabled for this token.",
         ));
     }
 
-    let amount = amount.u128();
-
     let mut total_supply = config.total_supply();
-    if let Some(new_total_supply) = total_supply.checked_add(amount) {
+    if let Some(new_total_supply) = total_supply.checked_add(raw_amount) {
         total_supply = new_total_supply;
     } else {
         return Err(StdError::generic_err(
             "This mint attempt would increase the total supply above the supported maximum",
         ));
     }
+    check_max_supply(&constants, total_supply)?;
     config.set_total_supply(total_supply);
 
-    let receipient_account = &deps.api.canonical_address(&address)?;
+    if let Some(allowance) = read_minter_allowance(storage, minter)? {
+        if let Some(new_allowance) = allowance.checked_sub(raw_amount) {
+            write_minter_allowance(storage, minter, Some(new_allowance));
+        } else {
+            return Err(StdError::generic_err(format!(
+                "This mint attempt exceeds the minter's allowance: allowance={}, required={}",
+                allowance, raw_amount
+            )));
+        }
+    }
 
-    let mut balances = Balances::from_storage(&mut deps.storage);
+    let mut balances = Balances::from_storage(storage);
 
-    let mut account_balance = balances.balance(receipient_account);
+    let mut account_balance = balances.balance(recipient);
 
-    if let Some(new_balance) = account_balance.checked_add(amount) {
+    if let Some(new_balance) = account_balance.checked_add(raw_amount) {
         account_balance = new_balance;
     } else {
         // This error literally can not happen, since the account's funds are a subset
@@ -285,15 +771,30 @@ fn try_mint<S: Storage, A: Api, Q: Querier>(
         ));
     }
 
-    balances.set_account_balance(receipient_account, account_balance);
+    balances.set_account_balance(recipient, account_balance);
 
-    let res = HandleResponse {
-        messages: vec![],
-        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
-        data: Some(to_binary(&HandleAnswer::Mint { status: Success })?),
-    };
+    write_balance_checkpoint(storage, recipient, block.height)?;
+    write_supply_checkpoint(storage, block.height)?;
 
-    Ok(res)
+    store_mint(
+        storage,
+        minter,
+        recipient,
+        amount,
+        constants.symbol,
+        memo,
+        block,
+    )
+}
+
+fn check_max_supply(constants: &Constants, total_supply: u128) -> StdResult<()> {
+    match constants.max_supply {
+        Some(max_supply) if total_supply > max_supply => Err(StdError::generic_err(format!(
+            "This would increase the total supply above the max supply of {}",
+            max_supply
+        ))),
+        _ => Ok(()),
+    }
 }
 
 pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
@@ -315,15 +816,36 @@ pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
             return match msg {
                 // Base
                 QueryMsg::Balance { address, .. } => query_balance(&deps, &address),
+                QueryMsg::BalanceAt {
+                    address, height, ..
+                } => query_balance_at(&deps, &address, height),
                 QueryMsg::TransferHistory {
                     address,
                     page,
                     page_size,
                     ..
+                } => query_transfers(&deps, &address, page.unwrap_or(0), page_size),
+                QueryMsg::TransactionHistory {
+                    address,
+                    page,
+                    page_size,
+                    ..
                 } => query_transactions(&deps, &address, page.unwrap_or(0), page_size),
                 QueryMsg::Allowance { owner, spender, .. } => {
                     try_check_allowance(deps, owner, spender)
                 }
+                QueryMsg::AllowancesGiven {
+                    owner,
+                    page,
+                    page_size,
+                    ..
+                } => query_allowances_given(deps, owner, page.unwrap_or(0), page_size),
+                QueryMsg::AllowancesReceived {
+                    spender,
+                    page,
+                    page_size,
+                    ..
+                } => query_allowances_received(deps, spender, page.unwrap_or(0), page_size),
                 _ => panic!("This query type does not require authentication"),
             };
         }
@@ -341,6 +863,40 @@ fn query_minters<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdR
     to_binary(&response)
 }
 
+fn query_observers<S: ReadonlyStorage>(storage: &S) -> QueryResult {
+    let observers = ReadonlyConfig::from_storage(storage).observers();
+
+    to_binary(&QueryAnswer::Observers { observers })
+}
+
+fn query_minting_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config = ReadonlyConfig::from_storage(&deps.storage);
+    let constants = config.constants()?;
+
+    let remaining_supply = match constants.max_supply {
+        Some(max_supply) if constants.total_supply_is_public => {
+            Some(Uint128(max_supply.saturating_sub(config.total_supply())))
+        }
+        _ => None,
+    };
+
+    let mut minters = vec![];
+    for minter in config.minters() {
+        let canonical_minter = deps.api.canonical_address(&minter)?;
+        let allowance = read_minter_allowance(&deps.storage, &canonical_minter)?;
+        minters.push(MinterQuota {
+            minter,
+            allowance: allowance.map(Uint128),
+        });
+    }
+
+    to_binary(&QueryAnswer::MintingInfo {
+        max_supply: constants.max_supply.map(Uint128),
+        remaining_supply,
+        minters,
+    })
+}
+
 /// This function just returns a constant 1:1 rate to uscrt, since that's the purpose of this
 /// contract.
 fn query_exchange_rate() -> QueryResult {
@@ -368,7 +924,19 @@ fn query_token_info<S: ReadonlyStorage>(storage: &S) -> QueryResult {
     })
 }
 
-pub fn query_transactions<S: Storage, A: Api, Q: Querier>(
+fn query_token_config<S: ReadonlyStorage>(storage: &S) -> QueryResult {
+    let constants = ReadonlyConfig::from_storage(storage).constants()?;
+
+    to_binary(&QueryAnswer::TokenConfig {
+        public_total_supply: constants.total_supply_is_public,
+        deposit_enabled: constants.deposit_is_enabled,
+        redeem_enabled: constants.redeem_is_enabled,
+        mint_enabled: constants.mint_is_enabled,
+        burn_enabled: constants.burn_is_enabled,
+    })
+}
+
+pub fn query_transfers<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     account: &HumanAddr,
     page: u32,
@@ -381,6 +949,22 @@ pub fn query_transactions<S: Storage, A: Api, Q: Querier>(
     to_binary(&result)
 }
 
+pub fn query_transactions<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(account)?;
+    let (txs, total) = get_txs(&deps.api, &deps.storage, &address, page, page_size)?;
+
+    let result = QueryAnswer::TransactionHistory {
+        txs,
+        total: Some(total),
+    };
+    to_binary(&result)
+}
+
 pub fn query_balance<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     account: &HumanAddr,
@@ -392,6 +976,32 @@ pub fn query_balance<S: Storage, A: Api, Q: Querier>(
     to_binary(&response)
 }
 
+pub fn query_balance_at<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    height: u64,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(account)?;
+
+    let amount = Uint128(read_balance_at(&deps.storage, &address, height)?);
+    to_binary(&QueryAnswer::BalanceAt { height, amount })
+}
+
+fn query_total_supply_at<S: ReadonlyStorage>(storage: &S, height: u64) -> QueryResult {
+    let constants = ReadonlyConfig::from_storage(storage).constants()?;
+    if !constants.total_supply_is_public {
+        return Err(StdError::generic_err(
+            "The total supply of this token is not public",
+        ));
+    }
+
+    let total_supply = Uint128(read_total_supply_at(storage, height)?);
+    to_binary(&QueryAnswer::TotalSupplyAt {
+        height,
+        total_supply,
+    })
+}
+
 fn change_admin<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -429,6 +1039,21 @@ pub fn try_set_key<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn revoke_permit_name<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> StdResult<HandleResponse> {
+    let message_sender = deps.api.canonical_address(&env.message.sender)?;
+    revoke_permit(&mut deps.storage, &message_sender, &permit_name);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::RevokePermit { status: Success })?),
+    })
+}
+
 pub fn try_create_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -469,6 +1094,50 @@ fn set_contract_status<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn set_mint_enabled<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    let mut consts = config.constants()?;
+    consts.mint_is_enabled = enabled;
+    config.set_constants(&consts)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::SetMintEnabled {
+            status: Success,
+        })?),
+    })
+}
+
+fn set_burn_enabled<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    let mut consts = config.constants()?;
+    consts.burn_is_enabled = enabled;
+    config.set_constants(&consts)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::SetBurnEnabled {
+            status: Success,
+        })?),
+    })
+}
+
 pub fn try_check_allowance<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     owner: HumanAddr,
@@ -488,11 +1157,65 @@ pub fn try_check_allowance<S: Storage, A: Api, Q: Querier>(
     to_binary(&response)
 }
 
+fn query_allowances_given<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let owner_address = deps.api.canonical_address(&owner)?;
+    let (spenders, count) = get_spenders(&deps.storage, &owner_address, page, page_size)?;
+
+    let mut allowances = vec![];
+    for spender_address in spenders {
+        let allowance = read_allowance(&deps.storage, &owner_address, &spender_address)?;
+        allowances.push(AllowanceGiven {
+            spender: deps.api.human_address(&spender_address)?,
+            allowance: Uint128(allowance.amount),
+            expiration: allowance.expiration,
+        });
+    }
+
+    to_binary(&QueryAnswer::AllowancesGiven {
+        owner,
+        allowances,
+        count,
+    })
+}
+
+fn query_allowances_received<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    spender: HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let spender_address = deps.api.canonical_address(&spender)?;
+    let (owners, count) = get_owners(&deps.storage, &spender_address, page, page_size)?;
+
+    let mut allowances = vec![];
+    for owner_address in owners {
+        let allowance = read_allowance(&deps.storage, &owner_address, &spender_address)?;
+        allowances.push(AllowanceReceived {
+            owner: deps.api.human_address(&owner_address)?,
+            allowance: Uint128(allowance.amount),
+            expiration: allowance.expiration,
+        });
+    }
+
+    to_binary(&QueryAnswer::AllowancesReceived {
+        spender,
+        allowances,
+        count,
+    })
+}
+
 fn try_transfer_impl<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
+    messages: &mut Vec<CosmosMsg>,
+    env: &Env,
     recipient: &HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<()> {
     let sender_address = deps.api.canonical_address(&env.message.sender)?;
     let recipient_address = deps.api.canonical_address(recipient)?;
@@ -502,6 +1225,7 @@ fn try_transfer_impl<S: Storage, A: Api, Q: Querier>(
         &sender_address,
         &recipient_address,
         amount.u128(),
+        env.block.height,
     )?;
 
     let symbol = Config::from_storage(&mut deps.storage).constants()?.symbol;
@@ -513,8 +1237,15 @@ fn try_transfer_impl<S: Storage, A: Api, Q: Querier>(
         &recipient_address,
         amount,
         symbol,
+        memo,
+        &env.block,
     )?;
 
+    notify_observers(deps, messages, &sender_address)?;
+    if recipient_address != sender_address {
+        notify_observers(deps, messages, &recipient_address)?;
+    }
+
     Ok(())
 }
 
@@ -523,17 +1254,46 @@ fn try_transfer<S: Storage, A: Api, Q: Querier>(
     env: Env,
     recipient: &HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<HandleResponse> {
-    try_transfer_impl(deps, env, recipient, amount)?;
+    let mut messages = vec![];
+
+    try_transfer_impl(deps, &mut messages, &env, recipient, amount, memo)?;
 
     let res = HandleResponse {
-        messages: vec![],
+        messages,
         log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
         data: Some(to_binary(&HandleAnswer::Transfer { status: Success })?),
     };
     Ok(res)
 }
 
+fn try_batch_transfer<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<batch::TransferAction>,
+) -> StdResult<HandleResponse> {
+    let mut messages = vec![];
+
+    for action in actions {
+        try_transfer_impl(
+            deps,
+            &mut messages,
+            &env,
+            &action.recipient,
+            action.amount,
+            action.memo,
+        )?;
+    }
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::BatchTransfer { status: Success })?),
+    };
+    Ok(res)
+}
+
 fn try_add_receiver_api_callback<S: ReadonlyStorage>(
     messages: &mut Vec<CosmosMsg>,
     storage: &S,
@@ -554,27 +1314,39 @@ fn try_add_receiver_api_callback<S: ReadonlyStorage>(
     Ok(())
 }
 
-fn try_send<S: Storage, A: Api, Q: Querier>(
+fn try_send_impl<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
+    messages: &mut Vec<CosmosMsg>,
+    env: &Env,
     recipient: &HumanAddr,
     amount: Uint128,
     msg: Option<Binary>,
-) -> StdResult<HandleResponse> {
-    let sender = env.message.sender.clone();
-    try_transfer_impl(deps, env, recipient, amount)?;
-
-    let mut messages = vec![];
+    memo: Option<String>,
+) -> StdResult<()> {
+    try_transfer_impl(deps, messages, env, recipient, amount, memo)?;
 
     try_add_receiver_api_callback(
-        &mut messages,
+        messages,
         &deps.storage,
         recipient,
         msg,
-        sender.clone(),
-        sender,
+        env.message.sender.clone(),
+        env.message.sender.clone(),
         amount,
-    )?;
+    )
+}
+
+fn try_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: &HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    let mut messages = vec![];
+
+    try_send_impl(deps, &mut messages, &env, recipient, amount, msg, memo)?;
 
     let res = HandleResponse {
         messages,
@@ -584,6 +1356,33 @@ fn try_send<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
+fn try_batch_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<batch::SendAction>,
+) -> StdResult<HandleResponse> {
+    let mut messages = vec![];
+
+    for action in actions {
+        try_send_impl(
+            deps,
+            &mut messages,
+            &env,
+            &action.recipient,
+            action.amount,
+            action.msg,
+            action.memo,
+        )?;
+    }
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::BatchSend { status: Success })?),
+    };
+    Ok(res)
+}
+
 fn try_register_receive<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -600,6 +1399,31 @@ fn try_register_receive<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
+/// Tells every observer about the new balance of `account`, if the account opted in
+fn notify_observers<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    messages: &mut Vec<CosmosMsg>,
+    account: &CanonicalAddr,
+) -> StdResult<()> {
+    if !is_observed_account(&deps.storage, account) {
+        return Ok(());
+    }
+
+    let observers = ReadonlyConfig::from_storage(&deps.storage).observers();
+    if observers.is_empty() {
+        return Ok(());
+    }
+
+    let balance = Uint128(ReadonlyBalances::from_storage(&deps.storage).account_amount(account));
+    let account = deps.api.human_address(account)?;
+    for observer in &observers {
+        let msg = BalanceChangedMsg::new(account.clone(), balance);
+        messages.push(msg.into_cosmos_msg(observer)?);
+    }
+
+    Ok(())
+}
+
 fn insufficient_allowance(allowance: u128, required: u128) -> StdError {
     StdError::generic_err(format!(
         "insufficient allowance: allowance={}, required={}",
@@ -607,48 +1431,58 @@ fn insufficient_allowance(allowance: u128, required: u128) -> StdError {
     ))
 }
 
+/// Deducts `amount` from the allowance `owner` gave to `spender`
+fn use_allowance<S: Storage>(
+    storage: &mut S,
+    env: &Env,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+    amount: u128,
+) -> StdResult<()> {
+    let mut allowance = read_allowance(storage, owner, spender)?;
+
+    if allowance.expiration.map(|ex| ex < env.block.time) == Some(true) {
+        allowance.amount = 0;
+        write_allowance(storage, owner, spender, allowance)?;
+        return Err(insufficient_allowance(0, amount));
+    }
+
+    if let Some(new_allowance) = allowance.amount.checked_sub(amount) {
+        allowance.amount = new_allowance;
+    } else {
+        return Err(insufficient_allowance(allowance.amount, amount));
+    }
+
+    write_allowance(storage, owner, spender, allowance)
+}
+
 fn try_transfer_from_impl<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
+    messages: &mut Vec<CosmosMsg>,
+    env: &Env,
     owner: &HumanAddr,
     recipient: &HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<()> {
     let spender_address = deps.api.canonical_address(&env.message.sender)?;
     let owner_address = deps.api.canonical_address(owner)?;
     let recipient_address = deps.api.canonical_address(recipient)?;
     let amount_raw = amount.u128();
 
-    let mut allowance = read_allowance(&deps.storage, &owner_address, &spender_address)?;
-
-    if allowance.expiration.map(|ex| ex < env.block.time) == Some(true) {
-        allowance.amount = 0;
-        write_allowance(
-            &mut deps.storage,
-            &owner_address,
-            &spender_address,
-            allowance,
-        )?;
-        return Err(insufficient_allowance(0, amount_raw));
-    }
-
-    if let Some(new_allowance) = allowance.amount.checked_sub(amount_raw) {
-        allowance.amount = new_allowance;
-    } else {
-        return Err(insufficient_allowance(allowance.amount, amount_raw));
-    }
-
-    write_allowance(
+    use_allowance(
         &mut deps.storage,
+        env,
         &owner_address,
         &spender_address,
-        allowance,
+        amount_raw,
     )?;
     perform_transfer(
         &mut deps.storage,
         &owner_address,
         &recipient_address,
         amount_raw,
+        env.block.height,
     )?;
 
     let symbol = Config::from_storage(&mut deps.storage).constants()?.symbol;
@@ -660,8 +1494,15 @@ fn try_transfer_from_impl<S: Storage, A: Api, Q: Querier>(
         &recipient_address,
         amount,
         symbol,
+        memo,
+        &env.block,
     )?;
 
+    notify_observers(deps, messages, &owner_address)?;
+    if recipient_address != owner_address {
+        notify_observers(deps, messages, &recipient_address)?;
+    }
+
     Ok(())
 }
 
@@ -671,36 +1512,68 @@ fn try_transfer_from<S: Storage, A: Api, Q: Querier>(
     owner: &HumanAddr,
     recipient: &HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<HandleResponse> {
-    try_transfer_from_impl(deps, env, owner, recipient, amount)?;
+    let mut messages = vec![];
+
+    try_transfer_from_impl(deps, &mut messages, &env, owner, recipient, amount, memo)?;
 
     let res = HandleResponse {
-        messages: vec![],
+        messages,
         log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
         data: Some(to_binary(&HandleAnswer::TransferFrom { status: Success })?),
     };
     Ok(res)
 }
 
-fn try_send_from<S: Storage, A: Api, Q: Querier>(
+fn try_batch_transfer_from<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    owner: &HumanAddr,
-    recipient: &HumanAddr,
-    amount: Uint128,
-    msg: Option<Binary>,
+    actions: Vec<batch::TransferFromAction>,
 ) -> StdResult<HandleResponse> {
-    let sender = env.message.sender.clone();
-    try_transfer_from_impl(deps, env, owner, recipient, amount)?;
-
     let mut messages = vec![];
 
-    try_add_receiver_api_callback(
-        &mut messages,
+    for action in actions {
+        try_transfer_from_impl(
+            deps,
+            &mut messages,
+            &env,
+            &action.owner,
+            &action.recipient,
+            action.amount,
+            action.memo,
+        )?;
+    }
+
+    let res = HandleResponse {
+        messages,
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::BatchTransferFrom {
+            status: Success,
+        })?),
+    };
+    Ok(res)
+}
+
+fn try_send_from<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: &HumanAddr,
+    recipient: &HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    let mut messages = vec![];
+
+    try_transfer_from_impl(deps, &mut messages, &env, owner, recipient, amount, memo)?;
+
+    try_add_receiver_api_callback(
+        &mut messages,
         &deps.storage,
         recipient,
         msg,
-        sender,
+        env.message.sender,
         owner.clone(),
         amount,
     )?;
@@ -810,7 +1683,12 @@ fn remove_minters<S: Storage, A: Api, Q: Querier>(
 
     check_if_admin(&config, &env.message.sender)?;
 
-    config.remove_minters(minters_to_remove)?;
+    config.remove_minters(minters_to_remove.clone())?;
+
+    for minter in minters_to_remove {
+        let minter = deps.api.canonical_address(&minter)?;
+        write_minter_allowance(&mut deps.storage, &minter, None);
+    }
 
     Ok(HandleResponse {
         messages: vec![],
@@ -828,7 +1706,15 @@ fn set_minters<S: Storage, A: Api, Q: Querier>(
 
     check_if_admin(&config, &env.message.sender)?;
 
-    config.set_minters(minters_to_set)?;
+    let previous_minters = config.minters();
+    config.set_minters(minters_to_set.clone())?;
+
+    for minter in previous_minters {
+        if !minters_to_set.contains(&minter) {
+            let minter = deps.api.canonical_address(&minter)?;
+            write_minter_allowance(&mut deps.storage, &minter, None);
+        }
+    }
 
     Ok(HandleResponse {
         messages: vec![],
@@ -837,11 +1723,97 @@ fn set_minters<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn add_observers<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    observers_to_add: Vec<Observer>,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    config.add_observers(observers_to_add)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::AddObservers { status: Success })?),
+    })
+}
+
+fn remove_observers<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    observers_to_remove: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    config.remove_observers(observers_to_remove)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::RemoveObservers {
+            status: Success,
+        })?),
+    })
+}
+
+fn set_balance_observation<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let account = deps.api.canonical_address(&env.message.sender)?;
+    set_observed_account(&mut deps.storage, &account, enabled);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::SetBalanceObservation {
+            status: Success,
+        })?),
+    })
+}
+
+fn set_minter_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    minter: HumanAddr,
+    allowance: Option<Uint128>,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    if !config.minters().contains(&minter) {
+        return Err(StdError::generic_err(format!("{} is not a minter", minter)));
+    }
+
+    let minter = deps.api.canonical_address(&minter)?;
+    write_minter_allowance(
+        &mut deps.storage,
+        &minter,
+        allowance.map(|allowance| allowance.u128()),
+    );
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("status", "success")], // See https://github.com/CosmWasm/wasmd/pull/386
+        data: Some(to_binary(&HandleAnswer::SetMinterAllowance {
+            status: Success,
+        })?),
+    })
+}
+
 fn perform_transfer<T: Storage>(
     store: &mut T,
     from: &CanonicalAddr,
     to: &CanonicalAddr,
     amount: u128,
+    height: u64,
 ) -> StdResult<()> {
     let mut balances = Balances::from_storage(store);
 
@@ -862,7 +1834,8 @@ fn perform_transfer<T: Storage>(
     })?;
     balances.set_account_balance(to, to_balance);
 
-    Ok(())
+    write_balance_checkpoint(store, from, height)?;
+    write_balance_checkpoint(store, to, height)
 }
 
 fn is_admin<S: Storage>(config: &Config<S>, account: &HumanAddr) -> StdResult<bool> {
@@ -874,6 +1847,17 @@ fn is_admin<S: Storage>(config: &Config<S>, account: &HumanAddr) -> StdResult<bo
     Ok(true)
 }
 
+fn check_if_minter<S: ReadonlyStorage>(storage: &S, account: &HumanAddr) -> StdResult<()> {
+    let minters = ReadonlyConfig::from_storage(storage).minters();
+    if !minters.contains(account) {
+        return Err(StdError::generic_err(
+            "Minting is allowed to minter accounts only",
+        ));
+    }
+
+    Ok(())
+}
+
 fn check_if_admin<S: Storage>(config: &Config<S>, account: &HumanAddr) -> StdResult<()> {
     if !is_admin(config, account)? {
         return Err(StdError::generic_err(
@@ -906,8 +1890,12 @@ mod tests {
     use cosmwasm_std::testing::*;
     use cosmwasm_std::{from_binary, BlockInfo, ContractInfo, MessageInfo, QueryResponse, WasmMsg};
 
+    use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+
     use crate::msg::ResponseStatus;
-    use crate::msg::{InitConfig, InitialBalance};
+    use crate::msg::{AllowanceGiven, AllowanceReceived, InitConfig, InitialBalance, MintConfig};
+    use crate::permit::{PermitParams, PermitSignature, PubKey};
+    use crate::state::TxAction;
 
     use super::*;
 
@@ -918,6 +1906,27 @@ mod tests {
     ) -> (
         StdResult<InitResponse>,
         Extern<MockStorage, MockApi, MockQuerier>,
+    ) {
+        init_helper_with_config(initial_balances, InitConfig::default())
+    }
+
+    fn init_helper_with_config(
+        initial_balances: Vec<InitialBalance>,
+        config: InitConfig,
+    ) -> (
+        StdResult<InitResponse>,
+        Extern<MockStorage, MockApi, MockQuerier>,
+    ) {
+        init_helper_with_mint_config(initial_balances, config, MintConfig::default())
+    }
+
+    fn init_helper_with_mint_config(
+        initial_balances: Vec<InitialBalance>,
+        config: InitConfig,
+        mint: MintConfig,
+    ) -> (
+        StdResult<InitResponse>,
+        Extern<MockStorage, MockApi, MockQuerier>,
     ) {
         let mut deps = mock_dependencies(20, &[]);
         let env = mock_env("instantiator", &[]);
@@ -929,7 +1938,9 @@ mod tests {
             decimals: 8,
             initial_balances: Some(initial_balances),
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
-            config: None,
+            config: Some(config),
+            init_hook: None,
+            mint: Some(mint),
         };
 
         (init(&mut deps, env, init_msg), deps)
@@ -978,6 +1989,46 @@ mod tests {
         }
     }
 
+    /// Signs a permit with a key whose account `MockApi` can turn into a `HumanAddr`
+    fn permit_helper(
+        permit_name: &str,
+        allowed_tokens: Vec<HumanAddr>,
+        permissions: Vec<Permission>,
+    ) -> Permit {
+        let mut secret_key = [0u8; 32];
+        secret_key[29..].copy_from_slice(&[0x1f, 0x7c, 0xd9]);
+        let signing_key = SigningKey::new(&secret_key).unwrap();
+
+        let params = PermitParams {
+            allowed_tokens,
+            permit_name: permit_name.to_string(),
+            chain_id: "secret-4".to_string(),
+            permissions,
+        };
+        let signature: Signature =
+            signing_key.sign(permit::signed_bytes(&params).unwrap().as_slice());
+
+        Permit {
+            params,
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary(signing_key.verify_key().to_bytes().to_vec()),
+                },
+                signature: Binary(signature.as_ref().to_vec()),
+            },
+        }
+    }
+
+    fn permit_account(permit: &Permit) -> HumanAddr {
+        permit::validate(
+            &MockApi::new(20),
+            permit,
+            &HumanAddr::from(MOCK_CONTRACT_ADDR),
+        )
+        .unwrap()
+    }
+
     fn ensure_success(handle_result: HandleResponse) -> bool {
         let handle_result: HandleAnswer = from_binary(&handle_result.data.unwrap()).unwrap();
 
@@ -986,12 +2037,23 @@ mod tests {
             | HandleAnswer::Redeem { status }
             | HandleAnswer::Transfer { status }
             | HandleAnswer::Send { status }
+            | HandleAnswer::BatchTransfer { status }
+            | HandleAnswer::BatchSend { status }
             | HandleAnswer::RegisterReceive { status }
             | HandleAnswer::SetViewingKey { status }
             | HandleAnswer::TransferFrom { status }
             | HandleAnswer::SendFrom { status }
+            | HandleAnswer::BatchTransferFrom { status }
+            | HandleAnswer::Mint { status }
+            | HandleAnswer::BatchMint { status }
+            | HandleAnswer::SetMinterAllowance { status }
+            | HandleAnswer::Burn { status }
+            | HandleAnswer::BurnFrom { status }
+            | HandleAnswer::BatchBurnFrom { status }
             | HandleAnswer::ChangeAdmin { status }
-            | HandleAnswer::SetContractStatus { status } => {
+            | HandleAnswer::SetContractStatus { status }
+            | HandleAnswer::SetMintEnabled { status }
+            | HandleAnswer::SetBurnEnabled { status } => {
                 matches!(status, ResponseStatus::Success {..})
             }
             _ => panic!("HandleAnswer not supported for success extraction"),
@@ -1069,6 +2131,7 @@ mod tests {
         let handle_msg = HandleMsg::Transfer {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(1000),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -1089,6 +2152,7 @@ mod tests {
         let handle_msg = HandleMsg::Transfer {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(10000),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -1119,6 +2183,7 @@ mod tests {
         let handle_msg = HandleMsg::Send {
             recipient: HumanAddr("contract".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
             msg: Some(to_binary("hey hey you you").unwrap()),
         };
@@ -1141,7 +2206,7 @@ mod tests {
     }
 
     #[test]
-    fn test_handle_register_receive() {
+    fn test_handle_batch_transfer() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
@@ -1152,22 +2217,56 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::RegisterReceive {
-            code_hash: "this_is_a_hash_of_a_code".to_string(),
+        let handle_msg = HandleMsg::BatchTransfer {
+            actions: vec![
+                batch::TransferAction {
+                    recipient: HumanAddr("alice".to_string()),
+                    amount: Uint128(1000),
+                    memo: None,
+                },
+                batch::TransferAction {
+                    recipient: HumanAddr("carol".to_string()),
+                    amount: Uint128(2000),
+                    memo: Some("for carol".to_string()),
+                },
+            ],
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let hash = get_receiver_hash(&deps.storage, &HumanAddr("contract".to_string()))
-            .unwrap()
-            .unwrap();
-        assert_eq!(hash, "this_is_a_hash_of_a_code".to_string());
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        for (account, amount) in &[("bob", 2000), ("alice", 1000), ("carol", 2000)] {
+            let canonical = deps
+                .api
+                .canonical_address(&HumanAddr(account.to_string()))
+                .unwrap();
+            assert_eq!(balances.account_amount(&canonical), *amount);
+        }
+
+        // A single action the sender can't cover fails the whole batch
+        let handle_msg = HandleMsg::BatchTransfer {
+            actions: vec![
+                batch::TransferAction {
+                    recipient: HumanAddr("alice".to_string()),
+                    amount: Uint128(1000),
+                    memo: None,
+                },
+                batch::TransferAction {
+                    recipient: HumanAddr("carol".to_string()),
+                    amount: Uint128(1001),
+                    memo: None,
+                },
+            ],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient funds"));
     }
 
     #[test]
-    fn test_handle_create_viewing_key() {
+    fn test_handle_batch_send() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
@@ -1178,32 +2277,67 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::CreateViewingKey {
-            entropy: "".to_string(),
+        let handle_msg = HandleMsg::RegisterReceive {
+            code_hash: "this_is_a_hash_of_a_code".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::BatchSend {
+            actions: vec![
+                batch::SendAction {
+                    recipient: HumanAddr("contract".to_string()),
+                    amount: Uint128(100),
+                    msg: Some(to_binary("hey hey you you").unwrap()),
+                    memo: None,
+                },
+                batch::SendAction {
+                    recipient: HumanAddr("alice".to_string()),
+                    amount: Uint128(200),
+                    msg: Some(to_binary("nobody listens").unwrap()),
+                    memo: None,
+                },
+            ],
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        let result = handle_result.unwrap();
+        // Only the recipient that registered a receiver is called
+        assert_eq!(
+            result.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr("contract".to_string()),
+                callback_code_hash: "this_is_a_hash_of_a_code".to_string(),
+                msg: Snip20ReceiveMsg::new(
+                    HumanAddr("bob".to_string()),
+                    HumanAddr("bob".to_string()),
+                    Uint128(100),
+                    Some(to_binary("hey hey you you").unwrap()),
+                )
+                .into_binary()
+                .unwrap(),
+                send: vec![],
+            })]
         );
-        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert!(ensure_success(result));
 
-        let key = match answer {
-            HandleAnswer::CreateViewingKey { key } => key,
-            _ => panic!("NOPE"),
+        let handle_msg = HandleMsg::BatchSend {
+            actions: vec![batch::SendAction {
+                recipient: HumanAddr("contract".to_string()),
+                amount: Uint128(4701),
+                msg: None,
+                memo: None,
+            }],
+            padding: None,
         };
-        let bob_canonical = deps
-            .api
-            .canonical_address(&HumanAddr("bob".to_string()))
-            .unwrap();
-        let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
-        assert!(key.check_viewing_key(saved_vk.as_slice()));
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient funds"));
     }
 
     #[test]
-    fn test_handle_set_viewing_key() {
+    fn test_handle_batch_transfer_from() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
@@ -1214,76 +2348,449 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        // Set VK
-        let handle_msg = HandleMsg::SetViewingKey {
-            key: "hi lol".to_string(),
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(3000),
             padding: None,
+            expiration: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::SetViewingKey {
-                status: ResponseStatus::Success
-            })
-            .unwrap(),
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
         );
 
-        // Set valid VK
-        let actual_vk = ViewingKey("x".to_string().repeat(VIEWING_KEY_SIZE));
-        let handle_msg = HandleMsg::SetViewingKey {
-            key: actual_vk.0.clone(),
+        let handle_msg = HandleMsg::BatchTransferFrom {
+            actions: vec![
+                batch::TransferFromAction {
+                    owner: HumanAddr("bob".to_string()),
+                    recipient: HumanAddr("carol".to_string()),
+                    amount: Uint128(1000),
+                    memo: None,
+                },
+                batch::TransferFromAction {
+                    owner: HumanAddr("bob".to_string()),
+                    recipient: HumanAddr("dave".to_string()),
+                    amount: Uint128(1500),
+                    memo: None,
+                },
+            ],
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::SetViewingKey { status: Success }).unwrap(),
-        );
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
         let bob_canonical = deps
             .api
             .canonical_address(&HumanAddr("bob".to_string()))
             .unwrap();
-        let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
-        assert!(actual_vk.check_viewing_key(&saved_vk));
+        let alice_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        let bob_balance =
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&bob_canonical);
+        assert_eq!(bob_balance, 5000 - 2500);
+        let allowance = read_allowance(&deps.storage, &bob_canonical, &alice_canonical).unwrap();
+        assert_eq!(allowance.amount, 500);
+
+        let handle_msg = HandleMsg::BatchTransferFrom {
+            actions: vec![batch::TransferFromAction {
+                owner: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("carol".to_string()),
+                amount: Uint128(501),
+                memo: None,
+            }],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
     }
 
     #[test]
-    fn test_handle_transfer_from() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("bob".to_string()),
-            amount: Uint128(5000),
-        }]);
+    fn test_handle_batch_mint() {
+        let (init_result, mut deps) = init_helper(vec![]);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        // Transfer before allowance
-        let handle_msg = HandleMsg::TransferFrom {
-            owner: HumanAddr("bob".to_string()),
-            recipient: HumanAddr("alice".to_string()),
-            amount: Uint128(2500),
+        let handle_msg = HandleMsg::BatchMint {
+            actions: vec![
+                batch::MintAction {
+                    recipient: HumanAddr("alice".to_string()),
+                    amount: Uint128(1000),
+                    memo: None,
+                },
+                batch::MintAction {
+                    recipient: HumanAddr("carol".to_string()),
+                    amount: Uint128(2000),
+                    memo: None,
+                },
+            ],
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        // Transfer more than allowance
-        let handle_msg = HandleMsg::IncreaseAllowance {
-            spender: HumanAddr("alice".to_string()),
-            amount: Uint128(2000),
+        let carol_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("carol".to_string()))
+            .unwrap();
+        let carol_balance =
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&carol_canonical);
+        assert_eq!(carol_balance, 2000);
+        let total_supply = ReadonlyConfig::from_storage(&deps.storage).total_supply();
+        assert_eq!(total_supply, 3000);
+
+        let handle_msg = HandleMsg::BatchMint {
+            actions: vec![batch::MintAction {
+                recipient: HumanAddr("alice".to_string()),
+                amount: Uint128(1000),
+                memo: None,
+            }],
             padding: None,
-            expiration: Some(1_571_797_420),
         };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        assert!(
-            handle_result.is_ok(),
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Minting is allowed to minter accounts only");
+    }
+
+    #[test]
+    fn test_handle_mint_max_supply() {
+        let public_total_supply = InitConfig {
+            public_total_supply: Some(true),
+            ..InitConfig::default()
+        };
+        let (init_result, _deps) = init_helper_with_mint_config(
+            vec![InitialBalance {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(10_001),
+            }],
+            public_total_supply.clone(),
+            MintConfig {
+                cap: Some(Uint128(10_000)),
+            },
+        );
+        let error = extract_error_msg(init_result);
+        assert_eq!(
+            error,
+            "The sum of all initial balances exceeds the max supply"
+        );
+
+        let (init_result, mut deps) = init_helper_with_mint_config(
+            vec![InitialBalance {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(5000),
+            }],
+            public_total_supply,
+            MintConfig {
+                cap: Some(Uint128(10_000)),
+            },
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(5000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_answer: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::MintingInfo {}).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::MintingInfo {
+                max_supply,
+                remaining_supply,
+                ..
+            } => {
+                assert_eq!(max_supply, Some(Uint128(10_000)));
+                assert_eq!(remaining_supply, Some(Uint128(0)));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(
+            error,
+            "This would increase the total supply above the max supply of 10000"
+        );
+
+        let handle_msg = HandleMsg::BatchMint {
+            actions: vec![batch::MintAction {
+                recipient: HumanAddr("alice".to_string()),
+                amount: Uint128(1),
+                memo: None,
+            }],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(
+            error,
+            "This would increase the total supply above the max supply of 10000"
+        );
+    }
+
+    #[test]
+    fn test_handle_minter_allowance() {
+        let (init_result, mut deps) = init_helper(vec![]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetMinterAllowance {
+            minter: HumanAddr("admin".to_string()),
+            allowance: Some(Uint128(1000)),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        let handle_msg = HandleMsg::SetMinterAllowance {
+            minter: HumanAddr("bob".to_string()),
+            allowance: Some(Uint128(1000)),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "bob is not a minter");
+
+        let handle_msg = HandleMsg::SetMinterAllowance {
+            minter: HumanAddr("admin".to_string()),
+            allowance: Some(Uint128(1000)),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(600),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_answer: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::MintingInfo {}).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::MintingInfo {
+                max_supply,
+                remaining_supply,
+                minters,
+            } => {
+                assert_eq!(max_supply, None);
+                assert_eq!(remaining_supply, None);
+                assert_eq!(
+                    minters,
+                    vec![MinterQuota {
+                        minter: HumanAddr("admin".to_string()),
+                        allowance: Some(Uint128(400)),
+                    }]
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(401),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(
+            error,
+            "This mint attempt exceeds the minter's allowance: allowance=400, required=401"
+        );
+
+        // Lifting the limit allows minting any amount again
+        let handle_msg = HandleMsg::SetMinterAllowance {
+            minter: HumanAddr("admin".to_string()),
+            allowance: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(10_000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+    }
+
+    #[test]
+    fn test_handle_register_receive() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::RegisterReceive {
+            code_hash: "this_is_a_hash_of_a_code".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+
+        let hash = get_receiver_hash(&deps.storage, &HumanAddr("contract".to_string()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(hash, "this_is_a_hash_of_a_code".to_string());
+    }
+
+    #[test]
+    fn test_handle_create_viewing_key() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::CreateViewingKey {
+            entropy: "".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+
+        let key = match answer {
+            HandleAnswer::CreateViewingKey { key } => key,
+            _ => panic!("NOPE"),
+        };
+        let bob_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
+        assert!(key.check_viewing_key(saved_vk.as_slice()));
+    }
+
+    #[test]
+    fn test_handle_set_viewing_key() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // Set VK
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "hi lol".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::SetViewingKey {
+                status: ResponseStatus::Success
+            })
+            .unwrap(),
+        );
+
+        // Set valid VK
+        let actual_vk = ViewingKey("x".to_string().repeat(VIEWING_KEY_SIZE));
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: actual_vk.0.clone(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::SetViewingKey { status: Success }).unwrap(),
+        );
+        let bob_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
+        assert!(actual_vk.check_viewing_key(&saved_vk));
+    }
+
+    #[test]
+    fn test_handle_transfer_from() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // Transfer before allowance
+        let handle_msg = HandleMsg::TransferFrom {
+            owner: HumanAddr("bob".to_string()),
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(2500),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Transfer more than allowance
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(2000),
+            padding: None,
+            expiration: Some(1_571_797_420),
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
             "handle() failed: {}",
             handle_result.err().unwrap()
         );
@@ -1291,6 +2798,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2500),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1302,6 +2810,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2000),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(
@@ -1332,6 +2841,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2000),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1362,6 +2872,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(1),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1387,6 +2898,7 @@ mod tests {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2500),
             msg: None,
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1411,6 +2923,7 @@ mod tests {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2500),
             msg: None,
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1440,6 +2953,7 @@ mod tests {
             recipient: HumanAddr("contract".to_string()),
             amount: Uint128(2000),
             msg: Some(send_msg),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1476,6 +2990,7 @@ mod tests {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(1),
             msg: None,
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1629,9 +3144,147 @@ mod tests {
     }
 
     #[test]
-    fn test_handle_change_admin() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("bob".to_string()),
+    fn test_handle_deposit() {
+        let (init_result, mut deps) = init_helper(vec![]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env("lebron", &[Coin::new(1000, "uscrt")]),
+            handle_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert_eq!(
+            error,
+            "Deposit functionality is not enabled for this token."
+        );
+
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![],
+            InitConfig {
+                enable_deposit: Some(true),
+                ..InitConfig::default()
+            },
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env("lebron", &[Coin::new(1000, "uatom")]),
+            handle_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Tried to deposit an unsupported token");
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "No funds were sent to be deposited");
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env("lebron", &[Coin::new(1000, "uscrt")]),
+            handle_msg,
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let lebron_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("lebron".to_string()))
+            .unwrap();
+        let balance =
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&lebron_canonical);
+        assert_eq!(balance, 1000);
+        let total_supply = ReadonlyConfig::from_storage(&deps.storage).total_supply();
+        assert_eq!(total_supply, 1000);
+    }
+
+    #[test]
+    fn test_handle_redeem() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("lebron".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(1000),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Redeem functionality is not enabled for this token.");
+
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(5000),
+            }],
+            InitConfig {
+                enable_redeem: Some(true),
+                ..InitConfig::default()
+            },
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(5001),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient funds to redeem"));
+
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(1000),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert_eq!(
+            result.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+                to_address: HumanAddr("lebron".to_string()),
+                amount: vec![Coin::new(1000, "uscrt")],
+            })]
+        );
+        assert!(ensure_success(result));
+
+        let lebron_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("lebron".to_string()))
+            .unwrap();
+        let balance =
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&lebron_canonical);
+        assert_eq!(balance, 4000);
+        let total_supply = ReadonlyConfig::from_storage(&deps.storage).total_supply();
+        assert_eq!(total_supply, 4000);
+    }
+
+    #[test]
+    fn test_handle_change_admin() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
         }]);
         assert!(
@@ -1686,11 +3339,9 @@ mod tests {
     }
 
     #[test]
-    fn test_handle_admin_commands() {
-        let admin_err = "Admin commands can only be run from admin address".to_string();
-
+    fn test_handle_burn_from() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("lebron".to_string()),
+            address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
         }]);
         assert!(
@@ -1699,75 +3350,76 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let pause_msg = HandleMsg::SetContractStatus {
-            level: ContractStatusLevel::StopAllButRedeems,
+        let handle_msg = HandleMsg::BurnFrom {
+            owner: HumanAddr("bob".to_string()),
+            amount: Uint128(1000),
+            memo: None,
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("not_admin", &[]), pause_msg);
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
         let error = extract_error_msg(handle_result);
-        assert!(error.contains(&admin_err.clone()));
+        assert!(error.contains("insufficient allowance"));
 
-        let change_admin_msg = HandleMsg::ChangeAdmin {
-            address: HumanAddr("not_admin".to_string()),
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(3000),
             padding: None,
+            expiration: None,
         };
-        let handle_result = handle(&mut deps, mock_env("not_admin", &[]), change_admin_msg);
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains(&admin_err.clone()));
-    }
-
-    #[test]
-    fn test_handle_pause_with_withdrawals() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("lebron".to_string()),
-            amount: Uint128(5000),
-        }]);
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         assert!(
-            init_result.is_ok(),
-            "Init failed: {}",
-            init_result.err().unwrap()
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
         );
 
-        let pause_msg = HandleMsg::SetContractStatus {
-            level: ContractStatusLevel::StopAllButRedeems,
+        let handle_msg = HandleMsg::BurnFrom {
+            owner: HumanAddr("bob".to_string()),
+            amount: Uint128(1000),
+            memo: None,
             padding: None,
         };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let handle_result = handle(&mut deps, mock_env("admin", &[]), pause_msg);
-        assert!(
-            handle_result.is_ok(),
-            "Pause handle failed: {}",
-            handle_result.err().unwrap()
-        );
-
-        let send_msg = HandleMsg::Transfer {
-            recipient: HumanAddr("account".to_string()),
-            amount: Uint128(123),
+        let handle_msg = HandleMsg::BatchBurnFrom {
+            actions: vec![batch::BurnFromAction {
+                owner: HumanAddr("bob".to_string()),
+                amount: Uint128(1500),
+                memo: None,
+            }],
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("admin", &[]), send_msg);
-        let error = extract_error_msg(handle_result);
-        assert_eq!(
-            error,
-            "This contract is stopped and this action is not allowed".to_string()
-        );
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let withdraw_msg = HandleMsg::Redeem {
-            amount: Uint128(5000),
+        let bob_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let bob_balance =
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&bob_canonical);
+        assert_eq!(bob_balance, 5000 - 2500);
+        let total_supply = ReadonlyConfig::from_storage(&deps.storage).total_supply();
+        assert_eq!(total_supply, 5000 - 2500);
+
+        let handle_msg = HandleMsg::BatchBurnFrom {
+            actions: vec![batch::BurnFromAction {
+                owner: HumanAddr("bob".to_string()),
+                amount: Uint128(501),
+                memo: None,
+            }],
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("lebron", &[]), withdraw_msg);
-        assert!(
-            handle_result.is_ok(),
-            "Withdraw failed: {}",
-            handle_result.err().unwrap()
-        );
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
     }
 
     #[test]
-    fn test_handle_pause_all() {
+    fn test_handle_set_mint_and_burn_enabled() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("lebron".to_string()),
+            address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
         }]);
         assert!(
@@ -1776,275 +3428,1604 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let pause_msg = HandleMsg::SetContractStatus {
-            level: ContractStatusLevel::StopAll,
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(3000),
             padding: None,
+            expiration: None,
         };
-
-        let handle_result = handle(&mut deps, mock_env("admin", &[]), pause_msg);
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         assert!(
             handle_result.is_ok(),
-            "Pause handle failed: {}",
+            "handle() failed: {}",
             handle_result.err().unwrap()
         );
 
-        let send_msg = HandleMsg::Transfer {
-            recipient: HumanAddr("account".to_string()),
-            amount: Uint128(123),
+        let handle_msg = HandleMsg::SetMintEnabled {
+            enabled: false,
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("admin", &[]), send_msg);
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         let error = extract_error_msg(handle_result);
-        assert_eq!(
-            error,
-            "This contract is stopped and this action is not allowed".to_string()
-        );
-
-        let withdraw_msg = HandleMsg::Redeem {
-            amount: Uint128(5000),
+        assert!(error.contains("Admin commands can only be run from admin address"));
+        let handle_msg = HandleMsg::SetBurnEnabled {
+            enabled: false,
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("lebron", &[]), withdraw_msg);
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         let error = extract_error_msg(handle_result);
-        assert_eq!(
-            error,
-            "This contract is stopped and this action is not allowed".to_string()
-        );
-    }
-
-    // Query tests
-
-    #[test]
-    fn test_authenticated_queries() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("giannis".to_string()),
-            amount: Uint128(5000),
-        }]);
-        assert!(
-            init_result.is_ok(),
-            "Init failed: {}",
-            init_result.err().unwrap()
-        );
+        assert!(error.contains("Admin commands can only be run from admin address"));
 
-        let no_vk_yet_query_msg = QueryMsg::Balance {
-            address: HumanAddr("giannis".to_string()),
-            key: "no_vk_yet".to_string(),
+        let handle_msg = HandleMsg::SetMintEnabled {
+            enabled: false,
+            padding: None,
         };
-        let query_result = query(&deps, no_vk_yet_query_msg);
-        let error = extract_error_msg(query_result);
-        assert_eq!(
-            error,
-            "Wrong viewing key for this address or viewing key not set".to_string()
-        );
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_msg = HandleMsg::SetBurnEnabled {
+            enabled: false,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let create_vk_msg = HandleMsg::CreateViewingKey {
-            entropy: "34".to_string(),
+        let query_answer: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::TokenConfig {}).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::TokenConfig {
+                mint_enabled,
+                burn_enabled,
+                ..
+            } => {
+                assert!(!mint_enabled);
+                assert!(!burn_enabled);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(1000),
+            memo: None,
             padding: None,
         };
-        let handle_response = handle(&mut deps, mock_env("giannis", &[]), create_vk_msg).unwrap();
-        let vk = match from_binary(&handle_response.data.unwrap()).unwrap() {
-            HandleAnswer::CreateViewingKey { key } => key,
-            _ => panic!("Unexpected result from handle"),
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Mint functionality is not enabled for this token.");
+
+        let handle_msg = HandleMsg::BatchMint {
+            actions: vec![batch::MintAction {
+                recipient: HumanAddr("bob".to_string()),
+                amount: Uint128(1000),
+                memo: None,
+            }],
+            padding: None,
         };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Mint functionality is not enabled for this token.");
 
-        let query_balance_msg = QueryMsg::Balance {
-            address: HumanAddr("giannis".to_string()),
-            key: vk.0,
+        let handle_msg = HandleMsg::Burn {
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
         };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Burn functionality is not enabled for this token.");
 
-        let query_response = query(&deps, query_balance_msg).unwrap();
+        // Burning from an allowance is disabled along with burning, whatever the allowance
+        let handle_msg = HandleMsg::BurnFrom {
+            owner: HumanAddr("bob".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Burn functionality is not enabled for this token.");
+
+        let handle_msg = HandleMsg::BatchBurnFrom {
+            actions: vec![batch::BurnFromAction {
+                owner: HumanAddr("bob".to_string()),
+                amount: Uint128(1000),
+                memo: None,
+            }],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Burn functionality is not enabled for this token.");
+
+        let handle_msg = HandleMsg::SetMintEnabled {
+            enabled: true,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+    }
+
+    #[test]
+    fn test_init_mint_and_burn_disabled() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(5000),
+            }],
+            InitConfig {
+                enable_mint: Some(false),
+                enable_burn: Some(false),
+                ..InitConfig::default()
+            },
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Mint functionality is not enabled for this token.");
+
+        let handle_msg = HandleMsg::Burn {
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "Burn functionality is not enabled for this token.");
+    }
+
+    fn observer(address: &str) -> Observer {
+        Observer {
+            address: HumanAddr(address.to_string()),
+            code_hash: format!("{}_hash", address),
+        }
+    }
+
+    fn balance_changed(observer_address: &str, account: &str, balance: u128) -> CosmosMsg {
+        BalanceChangedMsg::new(HumanAddr(account.to_string()), Uint128(balance))
+            .into_cosmos_msg(&observer(observer_address))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_handle_add_and_remove_observers() {
+        let (init_result, mut deps) = init_helper(vec![]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::AddObservers {
+            observers: vec![observer("farm")],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        let handle_msg = HandleMsg::AddObservers {
+            observers: vec![observer("farm"), observer("gov")],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let query_answer: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::Observers {}).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::Observers { observers } => {
+                assert_eq!(observers, vec![observer("farm"), observer("gov")])
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_msg = HandleMsg::AddObservers {
+            observers: vec![Observer {
+                address: HumanAddr("farm".to_string()),
+                code_hash: "other_hash".to_string(),
+            }],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "farm is already an observer");
+
+        let handle_msg = HandleMsg::AddObservers {
+            observers: vec![observer("vault"), observer("vault")],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(error, "vault is already an observer");
+
+        let handle_msg = HandleMsg::RemoveObservers {
+            observers: vec![HumanAddr("farm".to_string())],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        let handle_msg = HandleMsg::RemoveObservers {
+            observers: vec![HumanAddr("farm".to_string())],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let query_answer: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::Observers {}).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::Observers { observers } => assert_eq!(observers, vec![observer("gov")]),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_balance_observation() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::AddObservers {
+            observers: vec![observer("farm"), observer("gov")],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        // Nobody opted in yet
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(handle_result.unwrap().messages.is_empty());
+
+        let handle_msg = HandleMsg::SetBalanceObservation {
+            enabled: true,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        // Only the opted-in side of a transfer is observed
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert_eq!(
+            handle_result.unwrap().messages,
+            vec![
+                balance_changed("farm", "bob", 3000),
+                balance_changed("gov", "bob", 3000),
+            ]
+        );
+
+        let handle_msg = HandleMsg::SetBalanceObservation {
+            enabled: true,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(500),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert_eq!(
+            handle_result.unwrap().messages,
+            vec![
+                balance_changed("farm", "bob", 2500),
+                balance_changed("gov", "bob", 2500),
+                balance_changed("farm", "alice", 2500),
+                balance_changed("gov", "alice", 2500),
+            ]
+        );
+
+        // The observers hear about a send before the recipient's Receive
+        let handle_msg = HandleMsg::RegisterReceive {
+            code_hash: "this_is_a_hash_of_a_code".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_msg = HandleMsg::Send {
+            recipient: HumanAddr("contract".to_string()),
+            amount: Uint128(100),
+            msg: None,
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert_eq!(
+            handle_result.unwrap().messages,
+            vec![
+                balance_changed("farm", "bob", 2400),
+                balance_changed("gov", "bob", 2400),
+                Snip20ReceiveMsg::new(
+                    HumanAddr("bob".to_string()),
+                    HumanAddr("bob".to_string()),
+                    Uint128(100),
+                    None,
+                )
+                .into_cosmos_msg(
+                    "this_is_a_hash_of_a_code".to_string(),
+                    HumanAddr("contract".to_string())
+                )
+                .unwrap(),
+            ]
+        );
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert_eq!(
+            handle_result.unwrap().messages,
+            vec![
+                balance_changed("farm", "alice", 3500),
+                balance_changed("gov", "alice", 3500),
+            ]
+        );
+
+        let handle_msg = HandleMsg::Burn {
+            amount: Uint128(400),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert_eq!(
+            handle_result.unwrap().messages,
+            vec![
+                balance_changed("farm", "bob", 2000),
+                balance_changed("gov", "bob", 2000),
+            ]
+        );
+
+        // Opting out stops the messages
+        let handle_msg = HandleMsg::SetBalanceObservation {
+            enabled: false,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Burn {
+            amount: Uint128(400),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(handle_result.unwrap().messages.is_empty());
+    }
+
+    #[test]
+    fn test_handle_admin_commands() {
+        let admin_err = "Admin commands can only be run from admin address".to_string();
+
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("lebron".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let pause_msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAllButRedeems,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("not_admin", &[]), pause_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains(&admin_err.clone()));
+
+        let change_admin_msg = HandleMsg::ChangeAdmin {
+            address: HumanAddr("not_admin".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("not_admin", &[]), change_admin_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains(&admin_err.clone()));
+    }
+
+    #[test]
+    fn test_handle_pause_with_withdrawals() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(5000),
+            }],
+            InitConfig {
+                enable_redeem: Some(true),
+                ..InitConfig::default()
+            },
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let pause_msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAllButRedeems,
+            padding: None,
+        };
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), pause_msg);
+        assert!(
+            handle_result.is_ok(),
+            "Pause handle failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let send_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("account".to_string()),
+            amount: Uint128(123),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), send_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(
+            error,
+            "This contract is stopped and this action is not allowed".to_string()
+        );
+
+        let withdraw_msg = HandleMsg::Redeem {
+            amount: Uint128(5000),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), withdraw_msg);
+        assert!(
+            handle_result.is_ok(),
+            "Withdraw failed: {}",
+            handle_result.err().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_handle_pause_all() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("lebron".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let pause_msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAll,
+            padding: None,
+        };
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), pause_msg);
+        assert!(
+            handle_result.is_ok(),
+            "Pause handle failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let send_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("account".to_string()),
+            amount: Uint128(123),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), send_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(
+            error,
+            "This contract is stopped and this action is not allowed".to_string()
+        );
+
+        let withdraw_msg = HandleMsg::Redeem {
+            amount: Uint128(5000),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), withdraw_msg);
+        let error = extract_error_msg(handle_result);
+        assert_eq!(
+            error,
+            "This contract is stopped and this action is not allowed".to_string()
+        );
+    }
+
+    // Query tests
+
+    #[test]
+    fn test_authenticated_queries() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("giannis".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let no_vk_yet_query_msg = QueryMsg::Balance {
+            address: HumanAddr("giannis".to_string()),
+            key: "no_vk_yet".to_string(),
+        };
+        let query_result = query(&deps, no_vk_yet_query_msg);
+        let error = extract_error_msg(query_result);
+        assert_eq!(
+            error,
+            "Wrong viewing key for this address or viewing key not set".to_string()
+        );
+
+        let create_vk_msg = HandleMsg::CreateViewingKey {
+            entropy: "34".to_string(),
+            padding: None,
+        };
+        let handle_response = handle(&mut deps, mock_env("giannis", &[]), create_vk_msg).unwrap();
+        let vk = match from_binary(&handle_response.data.unwrap()).unwrap() {
+            HandleAnswer::CreateViewingKey { key } => key,
+            _ => panic!("Unexpected result from handle"),
+        };
+
+        let query_balance_msg = QueryMsg::Balance {
+            address: HumanAddr("giannis".to_string()),
+            key: vk.0,
+        };
+
+        let query_response = query(&deps, query_balance_msg).unwrap();
         let balance = match from_binary(&query_response).unwrap() {
             QueryAnswer::Balance { amount } => amount,
-            _ => panic!("Unexpected result from query"),
+            _ => panic!("Unexpected result from query"),
+        };
+        assert_eq!(balance, Uint128(5000));
+
+        let wrong_vk_query_msg = QueryMsg::Balance {
+            address: HumanAddr("giannis".to_string()),
+            key: "wrong_vk".to_string(),
+        };
+        let query_result = query(&deps, wrong_vk_query_msg);
+        let error = extract_error_msg(query_result);
+        assert_eq!(
+            error,
+            "Wrong viewing key for this address or viewing key not set".to_string()
+        );
+    }
+
+    #[test]
+    fn test_query_token_info() {
+        let init_name = "sec-sec".to_string();
+        let init_admin = HumanAddr("admin".to_string());
+        let init_symbol = "SECSEC".to_string();
+        let init_decimals = 8;
+        let init_config: InitConfig = from_binary(&Binary::from(
+            r#"{ "public_total_supply": true }"#.as_bytes(),
+        ))
+        .unwrap();
+        let init_supply = Uint128(5000);
+
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("instantiator", &[]);
+        let init_msg = InitMsg {
+            name: init_name.clone(),
+            admin: Some(init_admin.clone()),
+            symbol: init_symbol.clone(),
+            decimals: init_decimals.clone(),
+            initial_balances: Some(vec![InitialBalance {
+                address: HumanAddr("giannis".to_string()),
+                amount: init_supply,
+            }]),
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(init_config),
+            init_hook: None,
+            mint: None,
+        };
+        let init_result = init(&mut deps, env, init_msg);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let query_msg = QueryMsg::TokenInfo {};
+        let query_result = query(&deps, query_msg);
+        assert!(
+            query_result.is_ok(),
+            "Init failed: {}",
+            query_result.err().unwrap()
+        );
+        let query_answer: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::TokenInfo {
+                name,
+                symbol,
+                decimals,
+                total_supply,
+            } => {
+                assert_eq!(name, init_name);
+                assert_eq!(symbol, init_symbol);
+                assert_eq!(decimals, init_decimals);
+                assert_eq!(total_supply, Some(Uint128(5000)));
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_query_allowance() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("giannis".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("lebron".to_string()),
+            amount: Uint128(2000),
+            padding: None,
+            expiration: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("giannis", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let vk1 = ViewingKey("key1".to_string());
+        let vk2 = ViewingKey("key2".to_string());
+
+        let query_msg = QueryMsg::Allowance {
+            owner: HumanAddr("giannis".to_string()),
+            spender: HumanAddr("lebron".to_string()),
+            key: vk1.0.clone(),
+            padding: None,
+        };
+        let query_result = query(&deps, query_msg);
+        assert!(
+            query_result.is_ok(),
+            "Query failed: {}",
+            query_result.err().unwrap()
+        );
+        let error = extract_error_msg(query_result);
+        assert!(error.contains("Wrong viewing key"));
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: vk1.0.clone(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::SetViewingKey {
+                status: ResponseStatus::Success
+            })
+            .unwrap(),
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: vk2.0.clone(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("giannis", &[]), handle_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::SetViewingKey {
+                status: ResponseStatus::Success
+            })
+            .unwrap(),
+        );
+
+        let query_msg = QueryMsg::Allowance {
+            owner: HumanAddr("giannis".to_string()),
+            spender: HumanAddr("lebron".to_string()),
+            key: vk1.0.clone(),
+            padding: None,
+        };
+        let query_result = query(&deps, query_msg);
+        let allowance = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Allowance { allowance, .. } => allowance,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(allowance, Uint128(2000));
+
+        let query_msg = QueryMsg::Allowance {
+            owner: HumanAddr("giannis".to_string()),
+            spender: HumanAddr("lebron".to_string()),
+            key: vk2.0.clone(),
+            padding: None,
+        };
+        let query_result = query(&deps, query_msg);
+        let allowance = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Allowance { allowance, .. } => allowance,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(allowance, Uint128(2000));
+
+        let query_msg = QueryMsg::Allowance {
+            owner: HumanAddr("lebron".to_string()),
+            spender: HumanAddr("giannis".to_string()),
+            key: vk2.0.clone(),
+            padding: None,
+        };
+        let query_result = query(&deps, query_msg);
+        let allowance = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Allowance { allowance, .. } => allowance,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(allowance, Uint128(0));
+    }
+
+    #[test]
+    fn test_query_balance() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::SetViewingKey {
+                status: ResponseStatus::Success
+            })
+            .unwrap(),
+        );
+
+        let query_msg = QueryMsg::Balance {
+            address: HumanAddr("bob".to_string()),
+            key: "wrong_key".to_string(),
+        };
+        let query_result = query(&deps, query_msg);
+        let error = extract_error_msg(query_result);
+        assert!(error.contains("Wrong viewing key"));
+
+        let query_msg = QueryMsg::Balance {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+        };
+        let query_result = query(&deps, query_msg);
+        let balance = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Balance { amount } => amount,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(balance, Uint128(5000));
+    }
+
+    #[test]
+    fn test_query_transfer_history() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("banana".to_string()),
+            amount: Uint128(500),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("mango".to_string()),
+            amount: Uint128(2500),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+
+        let query_msg = QueryMsg::TransferHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 0,
+        };
+        let query_result = query(&deps, query_msg);
+        // let a: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
+        // println!("{:?}", a);
+        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert!(transfers.is_empty());
+
+        let query_msg = QueryMsg::TransferHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let query_result = query(&deps, query_msg);
+        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(transfers.len(), 3);
+
+        let query_msg = QueryMsg::TransferHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 2,
+        };
+        let query_result = query(&deps, query_msg);
+        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(transfers.len(), 2);
+
+        // Pages past the end are empty
+        let query_msg = QueryMsg::TransferHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: Some(u32::max_value()),
+            page_size: u32::max_value(),
+        };
+        let query_result = query(&deps, query_msg);
+        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert!(transfers.is_empty());
+    }
+
+    #[test]
+    fn test_query_transaction_history() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
         };
-        assert_eq!(balance, Uint128(5000));
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let wrong_vk_query_msg = QueryMsg::Balance {
-            address: HumanAddr("giannis".to_string()),
-            key: "wrong_vk".to_string(),
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(1000),
+            memo: Some("payday".to_string()),
+            padding: None,
         };
-        let query_result = query(&deps, wrong_vk_query_msg);
-        let error = extract_error_msg(query_result);
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let mut env = mock_env("bob", &[]);
+        env.block.height = 20_000;
+        env.block.time = 1_600_000_000;
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(2000),
+            memo: Some("rent".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env, handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Burn {
+            amount: Uint128(500),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let query_msg = QueryMsg::TransactionHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let (txs, total) = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::TransactionHistory { txs, total } => (txs, total),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(total, Some(3));
+        assert_eq!(txs.len(), 3);
+
+        // Newest first
         assert_eq!(
-            error,
-            "Wrong viewing key for this address or viewing key not set".to_string()
+            txs[0].action,
+            TxAction::Burn {
+                burner: HumanAddr("bob".to_string()),
+                owner: HumanAddr("bob".to_string()),
+            }
+        );
+        assert_eq!(txs[0].coins.amount, Uint128(500));
+        assert_eq!(txs[0].memo, None);
+
+        assert_eq!(
+            txs[1].action,
+            TxAction::Transfer {
+                from: HumanAddr("bob".to_string()),
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+            }
+        );
+        assert_eq!(txs[1].coins.amount, Uint128(2000));
+        assert_eq!(txs[1].coins.denom, "SECSEC");
+        assert_eq!(txs[1].memo, Some("rent".to_string()));
+        assert_eq!(txs[1].block_height, 20_000);
+        assert_eq!(txs[1].block_time, 1_600_000_000);
+
+        assert_eq!(
+            txs[2].action,
+            TxAction::Mint {
+                minter: HumanAddr("admin".to_string()),
+                recipient: HumanAddr("bob".to_string()),
+            }
         );
+        assert_eq!(txs[2].memo, Some("payday".to_string()));
+        assert_eq!(txs[2].block_height, mock_env("admin", &[]).block.height);
+
+        // The transfer history only lists transfers
+        let query_msg = QueryMsg::TransferHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let transfers = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(transfers.len(), 1);
+
+        let query_msg = QueryMsg::TransactionHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: Some(1),
+            page_size: 2,
+        };
+        let (txs, total) = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::TransactionHistory { txs, total } => (txs, total),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(total, Some(3));
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].memo, Some("payday".to_string()));
+
+        // Pages past the end are empty
+        let query_msg = QueryMsg::TransactionHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: Some(u32::max_value()),
+            page_size: u32::max_value(),
+        };
+        let (txs, total) = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::TransactionHistory { txs, total } => (txs, total),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(total, Some(3));
+        assert!(txs.is_empty());
+
+        // Alice sees the transfer, but bob's key doesn't open her history
+        let query_msg = QueryMsg::TransactionHistory {
+            address: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("Wrong viewing key"));
+    }
+
+    fn transfer_at_height(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        height: u64,
+        from: &str,
+        to: &str,
+        amount: u128,
+    ) {
+        let mut env = mock_env(from, &[]);
+        env.block.height = height;
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr(to.to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(deps, env, handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+    }
+
+    fn balance_at(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        address: &str,
+        height: u64,
+    ) -> u128 {
+        let query_msg = QueryMsg::BalanceAt {
+            address: HumanAddr(address.to_string()),
+            key: "key".to_string(),
+            height,
+        };
+        match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::BalanceAt { amount, .. } => amount.u128(),
+            _ => panic!("Unexpected"),
+        }
     }
 
     #[test]
-    fn test_query_token_info() {
-        let init_name = "sec-sec".to_string();
-        let init_admin = HumanAddr("admin".to_string());
-        let init_symbol = "SECSEC".to_string();
-        let init_decimals = 8;
-        let init_config: InitConfig = from_binary(&Binary::from(
-            r#"{ "public_total_supply": true }"#.as_bytes(),
-        ))
-        .unwrap();
-        let init_supply = Uint128(5000);
+    fn test_query_balance_at() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+        let init_height = mock_env("instantiator", &[]).block.height;
 
-        let mut deps = mock_dependencies(20, &[]);
-        let env = mock_env("instantiator", &[]);
-        let init_msg = InitMsg {
-            name: init_name.clone(),
-            admin: Some(init_admin.clone()),
-            symbol: init_symbol.clone(),
-            decimals: init_decimals.clone(),
-            initial_balances: Some(vec![InitialBalance {
-                address: HumanAddr("giannis".to_string()),
-                amount: init_supply,
-            }]),
-            prng_seed: Binary::from("lolz fun yay".as_bytes()),
-            config: Some(init_config),
+        for account in &["bob", "alice"] {
+            let handle_msg = HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            };
+            let handle_result = handle(&mut deps, mock_env(*account, &[]), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        // Only the last change within a block is kept
+        transfer_at_height(&mut deps, init_height + 100, "bob", "alice", 1000);
+        transfer_at_height(&mut deps, init_height + 100, "bob", "alice", 500);
+        transfer_at_height(&mut deps, init_height + 200, "alice", "bob", 300);
+
+        // Before init there was no balance
+        assert_eq!(balance_at(&deps, "bob", init_height - 1), 0);
+        assert_eq!(balance_at(&deps, "bob", init_height), 5000);
+        assert_eq!(balance_at(&deps, "bob", init_height + 99), 5000);
+        assert_eq!(balance_at(&deps, "bob", init_height + 100), 3500);
+        assert_eq!(balance_at(&deps, "bob", init_height + 150), 3500);
+        assert_eq!(balance_at(&deps, "bob", init_height + 200), 3800);
+        assert_eq!(balance_at(&deps, "bob", init_height + 10_000), 3800);
+
+        assert_eq!(balance_at(&deps, "alice", init_height + 99), 0);
+        assert_eq!(balance_at(&deps, "alice", init_height + 100), 1500);
+        assert_eq!(balance_at(&deps, "alice", init_height + 200), 1200);
+
+        let query_msg = QueryMsg::BalanceAt {
+            address: HumanAddr("bob".to_string()),
+            key: "wrong_key".to_string(),
+            height: init_height,
         };
-        let init_result = init(&mut deps, env, init_msg);
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("Wrong viewing key"));
+    }
+
+    #[test]
+    fn test_query_total_supply_at() {
+        let (init_result, deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
+        let init_height = mock_env("instantiator", &[]).block.height;
 
-        let query_msg = QueryMsg::TokenInfo {};
-        let query_result = query(&deps, query_msg);
+        let query_result = query(
+            &deps,
+            QueryMsg::TotalSupplyAt {
+                height: init_height,
+            },
+        );
+        let error = extract_error_msg(query_result);
+        assert_eq!(error, "The total supply of this token is not public");
+
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(5000),
+            }],
+            InitConfig {
+                public_total_supply: Some(true),
+                ..InitConfig::default()
+            },
+        );
         assert!(
-            query_result.is_ok(),
+            init_result.is_ok(),
             "Init failed: {}",
-            query_result.err().unwrap()
+            init_result.err().unwrap()
         );
-        let query_answer: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
-        match query_answer {
-            QueryAnswer::TokenInfo {
-                name,
-                symbol,
-                decimals,
-                total_supply,
-            } => {
-                assert_eq!(name, init_name);
-                assert_eq!(symbol, init_symbol);
-                assert_eq!(decimals, init_decimals);
-                assert_eq!(total_supply, Some(Uint128(5000)));
+
+        let mut env = mock_env("admin", &[]);
+        env.block.height = init_height + 100;
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env, handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let mut env = mock_env("bob", &[]);
+        env.block.height = init_height + 100;
+        let handle_msg = HandleMsg::Burn {
+            amount: Uint128(200),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env, handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        for (height, expected) in &[
+            (init_height - 1, 0),
+            (init_height, 5000),
+            (init_height + 99, 5000),
+            (init_height + 100, 5800),
+            (init_height + 10_000, 5800),
+        ] {
+            let query_msg = QueryMsg::TotalSupplyAt { height: *height };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                QueryAnswer::TotalSupplyAt { total_supply, .. } => {
+                    assert_eq!(total_supply, Uint128(*expected), "height {}", height)
+                }
+                _ => panic!("Unexpected"),
             }
-            _ => panic!("unexpected"),
         }
     }
 
+    fn increase_allowance(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        owner: &str,
+        spender: &str,
+        amount: u128,
+    ) {
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr(spender.to_string()),
+            amount: Uint128(amount),
+            padding: None,
+            expiration: None,
+        };
+        let handle_result = handle(deps, mock_env(owner, &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+    }
+
+    fn allowances_given(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        page: Option<u32>,
+        page_size: u32,
+    ) -> (Vec<AllowanceGiven>, u64) {
+        let query_msg = QueryMsg::AllowancesGiven {
+            owner: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page,
+            page_size,
+        };
+        match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesGiven {
+                allowances, count, ..
+            } => (allowances, count),
+            _ => panic!("Unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_query_allowances_given() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let (allowances, count) = allowances_given(&deps, None, 10);
+        assert!(allowances.is_empty());
+        assert_eq!(count, 0);
+
+        increase_allowance(&mut deps, "bob", "alice", 1000);
+        increase_allowance(&mut deps, "bob", "carol", 2000);
+        increase_allowance(&mut deps, "bob", "dave", 3000);
+        // Changing an allowance doesn't list the spender twice
+        increase_allowance(&mut deps, "bob", "alice", 500);
+
+        let (allowances, count) = allowances_given(&deps, None, 2);
+        assert_eq!(count, 3);
+        assert_eq!(
+            allowances,
+            vec![
+                AllowanceGiven {
+                    spender: HumanAddr("alice".to_string()),
+                    allowance: Uint128(1500),
+                    expiration: None,
+                },
+                AllowanceGiven {
+                    spender: HumanAddr("carol".to_string()),
+                    allowance: Uint128(2000),
+                    expiration: None,
+                },
+            ]
+        );
+
+        let (allowances, count) = allowances_given(&deps, Some(1), 2);
+        assert_eq!(count, 3);
+        assert_eq!(
+            allowances,
+            vec![AllowanceGiven {
+                spender: HumanAddr("dave".to_string()),
+                allowance: Uint128(3000),
+                expiration: None,
+            }]
+        );
+
+        // Pages past the end are empty
+        let (allowances, count) = allowances_given(&deps, Some(2), 2);
+        assert!(allowances.is_empty());
+        assert_eq!(count, 3);
+        let (allowances, _) = allowances_given(&deps, None, 0);
+        assert!(allowances.is_empty());
+        let (allowances, _) = allowances_given(&deps, Some(u32::max_value()), u32::max_value());
+        assert!(allowances.is_empty());
+
+        let query_msg = QueryMsg::AllowancesGiven {
+            owner: HumanAddr("bob".to_string()),
+            key: "wrong_key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("Wrong viewing key"));
+
+        // A spender's key doesn't open the owner's allowances
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "alice_key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        let query_msg = QueryMsg::AllowancesGiven {
+            owner: HumanAddr("bob".to_string()),
+            key: "alice_key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("Wrong viewing key"));
+    }
+
     #[test]
-    fn test_query_allowance() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("giannis".to_string()),
-            amount: Uint128(5000),
-        }]);
+    fn test_query_allowances_received() {
+        let (init_result, mut deps) = init_helper(vec![]);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::IncreaseAllowance {
-            spender: HumanAddr("lebron".to_string()),
-            amount: Uint128(2000),
+        increase_allowance(&mut deps, "bob", "alice", 1000);
+        increase_allowance(&mut deps, "carol", "alice", 2000);
+        increase_allowance(&mut deps, "carol", "dave", 3000);
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
             padding: None,
-            expiration: None,
         };
-        let handle_result = handle(&mut deps, mock_env("giannis", &[]), handle_msg);
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let (allowances, count) = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesReceived {
+                allowances, count, ..
+            } => (allowances, count),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(count, 2);
+        assert_eq!(
+            allowances,
+            vec![
+                AllowanceReceived {
+                    owner: HumanAddr("bob".to_string()),
+                    allowance: Uint128(1000),
+                    expiration: None,
+                },
+                AllowanceReceived {
+                    owner: HumanAddr("carol".to_string()),
+                    allowance: Uint128(2000),
+                    expiration: None,
+                },
+            ]
         );
 
-        let vk1 = ViewingKey("key1".to_string());
-        let vk2 = ViewingKey("key2".to_string());
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+            page: Some(u32::max_value()),
+            page_size: u32::max_value(),
+        };
+        let allowances = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesReceived { allowances, .. } => allowances,
+            _ => panic!("Unexpected"),
+        };
+        assert!(allowances.is_empty());
 
-        let query_msg = QueryMsg::Allowance {
-            owner: HumanAddr("giannis".to_string()),
-            spender: HumanAddr("lebron".to_string()),
-            key: vk1.0.clone(),
-            padding: None,
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("dave".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
         };
-        let query_result = query(&deps, query_msg);
-        assert!(
-            query_result.is_ok(),
-            "Query failed: {}",
-            query_result.err().unwrap()
-        );
-        let error = extract_error_msg(query_result);
+        let error = extract_error_msg(query(&deps, query_msg));
         assert!(error.contains("Wrong viewing key"));
+    }
 
-        let handle_msg = HandleMsg::SetViewingKey {
-            key: vk1.0.clone(),
-            padding: None,
-        };
-        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::SetViewingKey {
-                status: ResponseStatus::Success
-            })
-            .unwrap(),
+    #[test]
+    fn test_query_allowances_with_permit() {
+        let permit = permit_helper(
+            "allowances",
+            vec![HumanAddr::from(MOCK_CONTRACT_ADDR)],
+            vec![Permission::Allowance],
+        );
+        let account = permit_account(&permit);
+        let (init_result, mut deps) = init_helper(vec![]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::SetViewingKey {
-            key: vk2.0.clone(),
-            padding: None,
+        increase_allowance(&mut deps, &account.0, "alice", 1000);
+
+        let query_msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::AllowancesGiven {
+                page: None,
+                page_size: 10,
+            },
         };
-        let handle_result = handle(&mut deps, mock_env("giannis", &[]), handle_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        let (owner, allowances) = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesGiven {
+                owner, allowances, ..
+            } => (owner, allowances),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(owner, account);
         assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::SetViewingKey {
-                status: ResponseStatus::Success
-            })
-            .unwrap(),
+            allowances,
+            vec![AllowanceGiven {
+                spender: HumanAddr("alice".to_string()),
+                allowance: Uint128(1000),
+                expiration: None,
+            }]
         );
 
-        let query_msg = QueryMsg::Allowance {
-            owner: HumanAddr("giannis".to_string()),
-            spender: HumanAddr("lebron".to_string()),
-            key: vk1.0.clone(),
-            padding: None,
+        let query_msg = QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::AllowancesReceived {
+                page: None,
+                page_size: 10,
+            },
         };
-        let query_result = query(&deps, query_msg);
-        let allowance = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::Allowance { allowance, .. } => allowance,
+        let count = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesReceived { count, .. } => count,
             _ => panic!("Unexpected"),
         };
-        assert_eq!(allowance, Uint128(2000));
+        assert_eq!(count, 0);
 
-        let query_msg = QueryMsg::Allowance {
-            owner: HumanAddr("giannis".to_string()),
-            spender: HumanAddr("lebron".to_string()),
-            key: vk2.0.clone(),
-            padding: None,
+        let balance_permit = permit_helper(
+            "balance",
+            vec![HumanAddr::from(MOCK_CONTRACT_ADDR)],
+            vec![Permission::Balance],
+        );
+        let query_msg = QueryMsg::WithPermit {
+            permit: balance_permit,
+            query: QueryWithPermit::AllowancesGiven {
+                page: None,
+                page_size: 10,
+            },
         };
-        let query_result = query(&deps, query_msg);
-        let allowance = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::Allowance { allowance, .. } => allowance,
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("No permission to query"));
+    }
+
+    #[test]
+    fn test_query_with_permit() {
+        let permit = permit_helper(
+            "balance",
+            vec![HumanAddr::from(MOCK_CONTRACT_ADDR)],
+            vec![Permission::Balance],
+        );
+        let account = permit_account(&permit);
+        let (init_result, deps) = init_helper(vec![InitialBalance {
+            address: account,
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let query_msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::Balance {},
+        };
+        let balance = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::Balance { amount } => amount,
             _ => panic!("Unexpected"),
         };
-        assert_eq!(allowance, Uint128(2000));
+        assert_eq!(balance, Uint128(5000));
 
-        let query_msg = QueryMsg::Allowance {
-            owner: HumanAddr("lebron".to_string()),
-            spender: HumanAddr("giannis".to_string()),
-            key: vk2.0.clone(),
-            padding: None,
+        // The chain id is part of what was signed
+        let mut other_chain_permit = permit;
+        other_chain_permit.params.chain_id = "secret-3".to_string();
+        let query_msg = QueryMsg::WithPermit {
+            permit: other_chain_permit,
+            query: QueryWithPermit::Balance {},
         };
-        let query_result = query(&deps, query_msg);
-        let allowance = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::Allowance { allowance, .. } => allowance,
-            _ => panic!("Unexpected"),
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert_eq!(error, "Failed to verify signatures for the given permit");
+
+        let other_token_permit = permit_helper(
+            "balance",
+            vec![HumanAddr::from("other_token")],
+            vec![Permission::Balance],
+        );
+        let query_msg = QueryMsg::WithPermit {
+            permit: other_token_permit,
+            query: QueryWithPermit::Balance {},
         };
-        assert_eq!(allowance, Uint128(0));
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("Permit doesn't apply to token"));
     }
 
     #[test]
-    fn test_query_balance() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("bob".to_string()),
+    fn test_query_with_permit_permissions() {
+        let permit = permit_helper(
+            "balance",
+            vec![HumanAddr::from(MOCK_CONTRACT_ADDR)],
+            vec![Permission::Balance],
+        );
+        let account = permit_account(&permit);
+        let (init_result, deps) = init_helper(vec![InitialBalance {
+            address: account,
             amount: Uint128(5000),
         }]);
         assert!(
@@ -2053,45 +5034,47 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::SetViewingKey {
-            key: "key".to_string(),
-            padding: None,
+        let query_msg = QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::TransferHistory {
+                page: None,
+                page_size: 10,
+            },
         };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::SetViewingKey {
-                status: ResponseStatus::Success
-            })
-            .unwrap(),
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("No permission to query"));
+
+        // The owner permission grants every query
+        let owner_permit = permit_helper(
+            "owner",
+            vec![HumanAddr::from(MOCK_CONTRACT_ADDR)],
+            vec![Permission::Owner],
         );
-
-        let query_msg = QueryMsg::Balance {
-            address: HumanAddr("bob".to_string()),
-            key: "wrong_key".to_string(),
-        };
-        let query_result = query(&deps, query_msg);
-        let error = extract_error_msg(query_result);
-        assert!(error.contains("Wrong viewing key"));
-
-        let query_msg = QueryMsg::Balance {
-            address: HumanAddr("bob".to_string()),
-            key: "key".to_string(),
+        let query_msg = QueryMsg::WithPermit {
+            permit: owner_permit,
+            query: QueryWithPermit::TransferHistory {
+                page: None,
+                page_size: 10,
+            },
         };
         let query_result = query(&deps, query_msg);
-        let balance = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::Balance { amount } => amount,
-            _ => panic!("Unexpected"),
-        };
-        assert_eq!(balance, Uint128(5000));
+        assert!(
+            query_result.is_ok(),
+            "Query failed: {}",
+            query_result.err().unwrap()
+        );
     }
 
     #[test]
-    fn test_query_transfer_history() {
+    fn test_handle_revoke_permit() {
+        let permit = permit_helper(
+            "balance",
+            vec![HumanAddr::from(MOCK_CONTRACT_ADDR)],
+            vec![Permission::Balance],
+        );
+        let account = permit_account(&permit);
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("bob".to_string()),
+            address: account.clone(),
             amount: Uint128(5000),
         }]);
         assert!(
@@ -2100,77 +5083,55 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::SetViewingKey {
-            key: "key".to_string(),
-            padding: None,
-        };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        assert!(ensure_success(handle_result.unwrap()));
-
-        let handle_msg = HandleMsg::Transfer {
-            recipient: HumanAddr("alice".to_string()),
-            amount: Uint128(1000),
-            padding: None,
-        };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
-        let handle_msg = HandleMsg::Transfer {
-            recipient: HumanAddr("banana".to_string()),
-            amount: Uint128(500),
+        // Revoking a name for another account doesn't affect the signer
+        let handle_msg = HandleMsg::RevokePermit {
+            permit_name: "balance".to_string(),
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
-        let handle_msg = HandleMsg::Transfer {
-            recipient: HumanAddr("mango".to_string()),
-            amount: Uint128(2500),
-            padding: None,
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let query_msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::Balance {},
         };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
+        assert!(query(&deps, query_msg).is_ok());
 
-        let query_msg = QueryMsg::TransferHistory {
-            address: HumanAddr("bob".to_string()),
-            key: "key".to_string(),
-            page: None,
-            page_size: 0,
-        };
-        let query_result = query(&deps, query_msg);
-        // let a: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
-        // println!("{:?}", a);
-        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::TransferHistory { txs } => txs,
-            _ => panic!("Unexpected"),
+        let handle_msg = HandleMsg::RevokePermit {
+            permit_name: "balance".to_string(),
+            padding: None,
         };
-        assert!(transfers.is_empty());
+        let handle_result = handle(&mut deps, mock_env(account.0, &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
 
-        let query_msg = QueryMsg::TransferHistory {
-            address: HumanAddr("bob".to_string()),
-            key: "key".to_string(),
-            page: None,
-            page_size: 10,
-        };
-        let query_result = query(&deps, query_msg);
-        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::TransferHistory { txs } => txs,
-            _ => panic!("Unexpected"),
+        let query_msg = QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::Balance {},
         };
-        assert_eq!(transfers.len(), 3);
-
-        let query_msg = QueryMsg::TransferHistory {
-            address: HumanAddr("bob".to_string()),
-            key: "key".to_string(),
-            page: None,
-            page_size: 2,
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("Permit \"balance\" was revoked"));
+
+        // Permits with other names keep working
+        let other_permit = permit_helper(
+            "other",
+            vec![HumanAddr::from(MOCK_CONTRACT_ADDR)],
+            vec![Permission::Balance],
+        );
+        let query_msg = QueryMsg::WithPermit {
+            permit: other_permit,
+            query: QueryWithPermit::Balance {},
         };
-        let query_result = query(&deps, query_msg);
-        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
-            QueryAnswer::TransferHistory { txs } => txs,
+        let balance = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::Balance { amount } => amount,
             _ => panic!("Unexpected"),
         };
-        assert_eq!(transfers.len(), 2);
+        assert_eq!(balance, Uint128(5000));
     }
 }
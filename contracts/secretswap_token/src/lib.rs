@@ -1,5 +1,8 @@
+pub mod batch;
 pub mod contract;
 pub mod msg;
+pub mod observer;
+pub mod permit;
 mod rand;
 pub mod receiver;
 pub mod state;
@@ -4,7 +4,10 @@ use serde::{Deserialize, Serialize};
 
 use secretswap::InitHook;
 
-use crate::state::Tx;
+use crate::batch;
+use crate::observer::Observer;
+use crate::permit::Permit;
+use crate::state::{RichTx, Tx};
 use crate::viewing_key::ViewingKey;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
@@ -23,12 +26,24 @@ pub struct InitMsg {
     pub prng_seed: Binary,
     pub config: Option<InitConfig>,
     pub init_hook: Option<InitHook>,
+    pub mint: Option<MintConfig>,
 }
 
 impl InitMsg {
     pub fn config(&self) -> InitConfig {
         self.config.clone().unwrap_or_default()
     }
+
+    pub fn get_cap(&self) -> Option<Uint128> {
+        self.mint.as_ref().and_then(|v| v.cap)
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct MintConfig {
+    /// Max supply of the token, which can't be changed after init
+    pub cap: Option<Uint128>,
 }
 
 /// This type represents optional configuration values which can be overridden.
@@ -40,27 +55,76 @@ pub struct InitConfig {
     /// Indicates whether the total supply is public or should be kept secret.
     /// default: False
     pub public_total_supply: Option<bool>,
+    /// Indicates whether deposit functionality should be enabled
+    /// default: False
+    pub enable_deposit: Option<bool>,
+    /// Indicates whether redeem functionality should be enabled
+    /// default: False
+    pub enable_redeem: Option<bool>,
+    /// Indicates whether mint functionality should be enabled, until the admin changes it
+    /// with `SetMintEnabled`
+    /// default: True
+    pub enable_mint: Option<bool>,
+    /// Indicates whether burn functionality should be enabled, until the admin changes it
+    /// with `SetBurnEnabled`
+    /// default: True
+    pub enable_burn: Option<bool>,
 }
 
 impl InitConfig {
     pub fn public_total_supply(&self) -> bool {
         self.public_total_supply.unwrap_or(false)
     }
+
+    pub fn deposit_enabled(&self) -> bool {
+        self.enable_deposit.unwrap_or(false)
+    }
+
+    pub fn redeem_enabled(&self) -> bool {
+        self.enable_redeem.unwrap_or(false)
+    }
+
+    pub fn mint_enabled(&self) -> bool {
+        self.enable_mint.unwrap_or(true)
+    }
+
+    pub fn burn_enabled(&self) -> bool {
+        self.enable_burn.unwrap_or(true)
+    }
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
+    // Native coin interactions
+    Redeem {
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    Deposit {
+        padding: Option<String>,
+    },
+
     // Base ERC-20 stuff
     Transfer {
         recipient: HumanAddr,
         amount: Uint128,
+        memo: Option<String>,
         padding: Option<String>,
     },
     Send {
         recipient: HumanAddr,
         amount: Uint128,
         msg: Option<Binary>,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    BatchTransfer {
+        actions: Vec<batch::TransferAction>,
+        padding: Option<String>,
+    },
+    BatchSend {
+        actions: Vec<batch::SendAction>,
         padding: Option<String>,
     },
     RegisterReceive {
@@ -75,6 +139,16 @@ pub enum HandleMsg {
         key: String,
         padding: Option<String>,
     },
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
+    /// Opts the sender in or out of having the observers told about its balance changes.
+    /// While opted in, an observer that fails makes the sender's transfers, mints and burns fail
+    SetBalanceObservation {
+        enabled: bool,
+        padding: Option<String>,
+    },
 
     // Allowance
     IncreaseAllowance {
@@ -93,6 +167,7 @@ pub enum HandleMsg {
         owner: HumanAddr,
         recipient: HumanAddr,
         amount: Uint128,
+        memo: Option<String>,
         padding: Option<String>,
     },
     SendFrom {
@@ -100,6 +175,11 @@ pub enum HandleMsg {
         recipient: HumanAddr,
         amount: Uint128,
         msg: Option<Binary>,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    BatchTransferFrom {
+        actions: Vec<batch::TransferFromAction>,
         padding: Option<String>,
     },
 
@@ -112,11 +192,24 @@ pub enum HandleMsg {
         level: ContractStatusLevel,
         padding: Option<String>,
     },
+    SetMintEnabled {
+        enabled: bool,
+        padding: Option<String>,
+    },
+    SetBurnEnabled {
+        enabled: bool,
+        padding: Option<String>,
+    },
 
     // Mint
     Mint {
         amount: Uint128,
         recipient: HumanAddr,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    BatchMint {
+        actions: Vec<batch::MintAction>,
         padding: Option<String>,
     },
     AddMinters {
@@ -131,10 +224,40 @@ pub enum HandleMsg {
         minters: Vec<HumanAddr>,
         padding: Option<String>,
     },
+    /// Limits how much `minter` may still mint, or lifts the limit when `allowance` is not set
+    SetMinterAllowance {
+        minter: HumanAddr,
+        allowance: Option<Uint128>,
+        padding: Option<String>,
+    },
+
+    // Observers
+    /// Registers contracts that are sent a `BalanceChanged` message after every transfer, mint
+    /// and burn of an opted-in account. The message is part of the same transaction, so an
+    /// observer that fails reverts the balance change
+    AddObservers {
+        observers: Vec<Observer>,
+        padding: Option<String>,
+    },
+    RemoveObservers {
+        observers: Vec<HumanAddr>,
+        padding: Option<String>,
+    },
 
     // Burn
     Burn {
         amount: Uint128,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    BurnFrom {
+        owner: HumanAddr,
+        amount: Uint128,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    BatchBurnFrom {
+        actions: Vec<batch::BurnFromAction>,
         padding: Option<String>,
     },
 }
@@ -142,6 +265,14 @@ pub enum HandleMsg {
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleAnswer {
+    // Native
+    Deposit {
+        status: ResponseStatus,
+    },
+    Redeem {
+        status: ResponseStatus,
+    },
+
     // Base
     Transfer {
         status: ResponseStatus,
@@ -149,6 +280,12 @@ pub enum HandleAnswer {
     Send {
         status: ResponseStatus,
     },
+    BatchTransfer {
+        status: ResponseStatus,
+    },
+    BatchSend {
+        status: ResponseStatus,
+    },
     RegisterReceive {
         status: ResponseStatus,
     },
@@ -158,6 +295,12 @@ pub enum HandleAnswer {
     SetViewingKey {
         status: ResponseStatus,
     },
+    RevokePermit {
+        status: ResponseStatus,
+    },
+    SetBalanceObservation {
+        status: ResponseStatus,
+    },
 
     // Allowance
     IncreaseAllowance {
@@ -176,15 +319,27 @@ pub enum HandleAnswer {
     SendFrom {
         status: ResponseStatus,
     },
+    BatchTransferFrom {
+        status: ResponseStatus,
+    },
 
     // Burn
     Burn {
         status: ResponseStatus,
     },
+    BurnFrom {
+        status: ResponseStatus,
+    },
+    BatchBurnFrom {
+        status: ResponseStatus,
+    },
     // Mint
     Mint {
         status: ResponseStatus,
     },
+    BatchMint {
+        status: ResponseStatus,
+    },
     AddMinters {
         status: ResponseStatus,
     },
@@ -194,6 +349,15 @@ pub enum HandleAnswer {
     SetMinters {
         status: ResponseStatus,
     },
+    SetMinterAllowance {
+        status: ResponseStatus,
+    },
+    AddObservers {
+        status: ResponseStatus,
+    },
+    RemoveObservers {
+        status: ResponseStatus,
+    },
 
     // Other
     ChangeAdmin {
@@ -202,12 +366,19 @@ pub enum HandleAnswer {
     SetContractStatus {
         status: ResponseStatus,
     },
+    SetMintEnabled {
+        status: ResponseStatus,
+    },
+    SetBurnEnabled {
+        status: ResponseStatus,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     TokenInfo {},
+    TokenConfig {},
     ExchangeRate {},
     Allowance {
         owner: HumanAddr,
@@ -215,36 +386,110 @@ pub enum QueryMsg {
         key: String,
         padding: Option<String>,
     },
+    AllowancesGiven {
+        owner: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    AllowancesReceived {
+        spender: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
     Balance {
         address: HumanAddr,
         key: String,
     },
+    /// The balance of `address` at the end of the block at `height`. Balances are
+    /// checkpointed from the block the token was instantiated in, before which this is zero
+    BalanceAt {
+        address: HumanAddr,
+        key: String,
+        height: u64,
+    },
     TransferHistory {
         address: HumanAddr,
         key: String,
         page: Option<u32>,
         page_size: u32,
     },
+    TransactionHistory {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
 
     Minters {},
+    Observers {},
+    MintingInfo {},
+    /// The total supply at the end of the block at `height`, if the total supply is public.
+    /// Like balances, it is checkpointed from the block the token was instantiated in
+    TotalSupplyAt {
+        height: u64,
+    },
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
 }
 
 impl QueryMsg {
     pub fn get_validation_params(&self) -> (Vec<&HumanAddr>, ViewingKey) {
         match self {
             Self::Balance { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::BalanceAt { address, key, .. } => (vec![address], ViewingKey(key.clone())),
             Self::TransferHistory { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::TransactionHistory { address, key, .. } => {
+                (vec![address], ViewingKey(key.clone()))
+            }
             Self::Allowance {
                 owner,
                 spender,
                 key,
                 ..
             } => (vec![owner, spender], ViewingKey(key.clone())),
+            Self::AllowancesGiven { owner, key, .. } => (vec![owner], ViewingKey(key.clone())),
+            Self::AllowancesReceived { spender, key, .. } => {
+                (vec![spender], ViewingKey(key.clone()))
+            }
             _ => panic!("This query type does not require authentication"),
         }
     }
 }
 
+/// The authenticated queries, with the account taken from the signer of the permit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    Allowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+    },
+    AllowancesGiven {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    AllowancesReceived {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Balance {},
+    BalanceAt {
+        height: u64,
+    },
+    TransferHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    TransactionHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryAnswer {
@@ -254,6 +499,13 @@ pub enum QueryAnswer {
         decimals: u8,
         total_supply: Option<Uint128>,
     },
+    TokenConfig {
+        public_total_supply: bool,
+        deposit_enabled: bool,
+        redeem_enabled: bool,
+        mint_enabled: bool,
+        burn_enabled: bool,
+    },
     ExchangeRate {
         rate: Uint128,
         denom: String,
@@ -264,12 +516,34 @@ pub enum QueryAnswer {
         allowance: Uint128,
         expiration: Option<u64>,
     },
+    AllowancesGiven {
+        owner: HumanAddr,
+        allowances: Vec<AllowanceGiven>,
+        count: u64,
+    },
+    AllowancesReceived {
+        spender: HumanAddr,
+        allowances: Vec<AllowanceReceived>,
+        count: u64,
+    },
     Balance {
         amount: Uint128,
     },
+    BalanceAt {
+        height: u64,
+        amount: Uint128,
+    },
+    TotalSupplyAt {
+        height: u64,
+        total_supply: Uint128,
+    },
     TransferHistory {
         txs: Vec<Tx>,
     },
+    TransactionHistory {
+        txs: Vec<RichTx>,
+        total: Option<u64>,
+    },
 
     ViewingKeyError {
         msg: String,
@@ -277,6 +551,40 @@ pub enum QueryAnswer {
     Minters {
         minters: Vec<HumanAddr>,
     },
+    MintingInfo {
+        max_supply: Option<Uint128>,
+        /// How much can still be minted before reaching `max_supply`, hidden along with the
+        /// total supply when it isn't public
+        remaining_supply: Option<Uint128>,
+        minters: Vec<MinterQuota>,
+    },
+    Observers {
+        observers: Vec<Observer>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowanceGiven {
+    pub spender: HumanAddr,
+    pub allowance: Uint128,
+    pub expiration: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct AllowanceReceived {
+    pub owner: HumanAddr,
+    pub allowance: Uint128,
+    pub expiration: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct MinterQuota {
+    pub minter: HumanAddr,
+    /// How much the minter may still mint, or `None` when it isn't limited
+    pub allowance: Option<Uint128>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
@@ -0,0 +1,54 @@
+use cosmwasm_std::{to_binary, Binary, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{contract::RESPONSE_BLOCK_SIZE, msg::space_pad};
+
+/// A contract registered by the admin to be told about the balance changes of opted-in accounts
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct Observer {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+/// BalanceChangedMsg should be de/serialized under `BalanceChanged()` variant in a HandleMsg
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct BalanceChangedMsg {
+    pub account: HumanAddr,
+    pub balance: Uint128,
+}
+
+impl BalanceChangedMsg {
+    pub fn new(account: HumanAddr, balance: Uint128) -> Self {
+        Self { account, balance }
+    }
+
+    /// serializes the message, and pads it to 256 bytes
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = ObserverHandleMsg::BalanceChanged(self);
+        let mut data = to_binary(&msg)?;
+        space_pad(RESPONSE_BLOCK_SIZE, &mut data.0);
+        Ok(data)
+    }
+
+    /// creates a cosmos_msg sending this struct to the observer
+    pub fn into_cosmos_msg(self, observer: &Observer) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            msg,
+            callback_code_hash: observer.code_hash.clone(),
+            contract_addr: observer.address.clone(),
+            send: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// This is just a helper to properly serialize the above message
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+enum ObserverHandleMsg {
+    BalanceChanged(BalanceChangedMsg),
+}
@@ -0,0 +1,173 @@
+use std::convert::TryFrom;
+
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, HumanAddr, StdError, StdResult, Uint128,
+};
+use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use ripemd160::Ripemd160;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A SNIP-24 query permit, signed offline by the account it grants access to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+impl Permit {
+    pub fn check_permission(&self, permission: &Permission) -> bool {
+        self.params.permissions.contains(permission)
+            || self.params.permissions.contains(&Permission::Owner)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitParams {
+    pub allowed_tokens: Vec<HumanAddr>,
+    pub permit_name: String,
+    pub chain_id: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitSignature {
+    pub pub_key: PubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PubKey {
+    /// ignored, but must be "tendermint/PubKeySecp256k1" otherwise the verification will fail
+    pub r#type: String,
+    /// Secp256k1 PubKey
+    pub value: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Allowance for SNIP-20 - Permission to query allowance of the owner & spender
+    Allowance,
+    /// Balance for SNIP-20 - Permission to query balance
+    Balance,
+    /// History for SNIP-20 - Permission to query transfer_history & transaction_history
+    History,
+    /// Owner permission indicates that the bearer of this permit should be granted all
+    /// the access of the creator/signer of the permit
+    Owner,
+}
+
+// The permit is signed as an amino-JSON `StdSignDoc`, whose fields must be serialized
+// in alphabetical order
+
+#[derive(Serialize)]
+struct SignedPermit {
+    account_number: Uint128,
+    chain_id: String,
+    fee: Fee,
+    memo: String,
+    msgs: Vec<PermitMsg>,
+    sequence: Uint128,
+}
+
+#[derive(Serialize)]
+struct Fee {
+    amount: Vec<FeeCoin>,
+    gas: Uint128,
+}
+
+#[derive(Serialize)]
+struct FeeCoin {
+    amount: Uint128,
+    denom: String,
+}
+
+#[derive(Serialize)]
+struct PermitMsg {
+    r#type: String,
+    value: PermitContent,
+}
+
+#[derive(Serialize)]
+struct PermitContent {
+    allowed_tokens: Vec<HumanAddr>,
+    permissions: Vec<Permission>,
+    permit_name: String,
+}
+
+impl SignedPermit {
+    fn from_params(params: &PermitParams) -> Self {
+        Self {
+            account_number: Uint128::zero(),
+            chain_id: params.chain_id.clone(),
+            fee: Fee {
+                amount: vec![FeeCoin {
+                    amount: Uint128::zero(),
+                    denom: "uscrt".to_string(),
+                }],
+                gas: Uint128(1),
+            },
+            memo: String::new(),
+            msgs: vec![PermitMsg {
+                r#type: "query_permit".to_string(),
+                value: PermitContent {
+                    allowed_tokens: params.allowed_tokens.clone(),
+                    permissions: params.permissions.clone(),
+                    permit_name: params.permit_name.clone(),
+                },
+            }],
+            sequence: Uint128::zero(),
+        }
+    }
+}
+
+/// Verifies the signature of `permit` and returns the account that signed it.
+/// Revocations are checked by the caller, against the returned account
+pub fn validate<A: Api>(
+    api: &A,
+    permit: &Permit,
+    current_token: &HumanAddr,
+) -> StdResult<HumanAddr> {
+    if !permit.params.allowed_tokens.contains(current_token) {
+        return Err(StdError::generic_err(format!(
+            "Permit doesn't apply to token {:?}, allowed tokens: {:?}",
+            current_token.as_str(),
+            permit
+                .params
+                .allowed_tokens
+                .iter()
+                .map(|token| token.as_str())
+                .collect::<Vec<&str>>()
+        )));
+    }
+
+    let pubkey = permit.signature.pub_key.value.as_slice();
+    let signed_bytes = signed_bytes(&permit.params)?;
+
+    let verifying_key = VerifyingKey::from_sec1_bytes(pubkey)
+        .map_err(|_| StdError::generic_err("Failed to parse the public key of the permit"))?;
+    let signature = Signature::try_from(permit.signature.signature.as_slice())
+        .map_err(|_| StdError::generic_err("Failed to parse the signature of the permit"))?;
+    verifying_key
+        .verify(signed_bytes.as_slice(), &signature)
+        .map_err(|_| StdError::generic_err("Failed to verify signatures for the given permit"))?;
+
+    api.human_address(&pubkey_to_account(pubkey))
+}
+
+/// The bytes an account signs to issue a permit with `params`
+pub(crate) fn signed_bytes(params: &PermitParams) -> StdResult<Binary> {
+    to_binary(&SignedPermit::from_params(params))
+}
+
+/// Cosmos accounts are the RIPEMD-160 of the SHA-256 of their compressed public key
+fn pubkey_to_account(pubkey: &[u8]) -> CanonicalAddr {
+    let account = Ripemd160::digest(&Sha256::digest(pubkey));
+    CanonicalAddr(Binary(account.to_vec()))
+}
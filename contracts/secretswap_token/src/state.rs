@@ -2,7 +2,8 @@ use std::any::type_name;
 use std::convert::TryFrom;
 
 use cosmwasm_std::{
-    Api, CanonicalAddr, Coin, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+    Api, BlockInfo, CanonicalAddr, Coin, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage,
+    Uint128,
 };
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
 use schemars::JsonSchema;
@@ -11,22 +12,32 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::msg::{status_level_to_u8, u8_to_status_level, ContractStatusLevel};
+use crate::observer::Observer;
 use crate::viewing_key::ViewingKey;
 
 pub static CONFIG_KEY: &[u8] = b"config";
 pub const PREFIX_TXS: &[u8] = b"transfers";
+pub const PREFIX_TRANSACTIONS: &[u8] = b"transactions";
 
 pub const KEY_CONSTANTS: &[u8] = b"constants";
 pub const KEY_TOTAL_SUPPLY: &[u8] = b"total_supply";
 pub const KEY_CONTRACT_STATUS: &[u8] = b"contract_status";
 pub const KEY_TX_COUNT: &[u8] = b"tx-count";
 pub const KEY_MINTERS: &[u8] = b"minters";
+pub const KEY_OBSERVERS: &[u8] = b"observers";
 
 pub const PREFIX_CONFIG: &[u8] = b"config";
 pub const PREFIX_BALANCES: &[u8] = b"balances";
+pub const PREFIX_BALANCE_CHECKPOINTS: &[u8] = b"balance_checkpoints";
+pub const PREFIX_SUPPLY_CHECKPOINTS: &[u8] = b"supply_checkpoints";
 pub const PREFIX_ALLOWANCES: &[u8] = b"allowances";
+pub const PREFIX_ALLOWANCES_GIVEN: &[u8] = b"allowances_given";
+pub const PREFIX_ALLOWANCES_RECEIVED: &[u8] = b"allowances_received";
+pub const PREFIX_MINTER_ALLOWANCES: &[u8] = b"minter_allowances";
 pub const PREFIX_VIEW_KEY: &[u8] = b"viewingkey";
 pub const PREFIX_RECEIVERS: &[u8] = b"receivers";
+pub const PREFIX_OBSERVED_ACCOUNTS: &[u8] = b"observed_accounts";
+pub const PREFIX_REVOKED_PERMITS: &[u8] = b"revoked_permits";
 
 // Note that id is a globally incrementing counter.
 // Since it's 64 bits long, even at 50 tx/s it would take
@@ -76,6 +87,132 @@ impl StoredTx {
     }
 }
 
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TxAction {
+    Transfer {
+        from: HumanAddr,
+        sender: HumanAddr,
+        recipient: HumanAddr,
+    },
+    Mint {
+        minter: HumanAddr,
+        recipient: HumanAddr,
+    },
+    Burn {
+        burner: HumanAddr,
+        owner: HumanAddr,
+    },
+    Deposit {},
+    Redeem {},
+}
+
+/// An entry of the transaction history, which unlike `Tx` covers every kind of balance change
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RichTx {
+    pub id: u64,
+    pub action: TxAction,
+    pub coins: Coin,
+    pub memo: Option<String>,
+    pub block_time: u64,
+    pub block_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum StoredTxAction {
+    Transfer {
+        from: CanonicalAddr,
+        sender: CanonicalAddr,
+        recipient: CanonicalAddr,
+    },
+    Mint {
+        minter: CanonicalAddr,
+        recipient: CanonicalAddr,
+    },
+    Burn {
+        burner: CanonicalAddr,
+        owner: CanonicalAddr,
+    },
+    Deposit,
+    Redeem,
+}
+
+impl StoredTxAction {
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<TxAction> {
+        let action = match self {
+            StoredTxAction::Transfer {
+                from,
+                sender,
+                recipient,
+            } => TxAction::Transfer {
+                from: api.human_address(&from)?,
+                sender: api.human_address(&sender)?,
+                recipient: api.human_address(&recipient)?,
+            },
+            StoredTxAction::Mint { minter, recipient } => TxAction::Mint {
+                minter: api.human_address(&minter)?,
+                recipient: api.human_address(&recipient)?,
+            },
+            StoredTxAction::Burn { burner, owner } => TxAction::Burn {
+                burner: api.human_address(&burner)?,
+                owner: api.human_address(&owner)?,
+            },
+            StoredTxAction::Deposit => TxAction::Deposit {},
+            StoredTxAction::Redeem => TxAction::Redeem {},
+        };
+        Ok(action)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct StoredRichTx {
+    id: u64,
+    action: StoredTxAction,
+    coins: Coin,
+    memo: Option<String>,
+    block_time: u64,
+    block_height: u64,
+}
+
+impl StoredRichTx {
+    fn new(
+        id: u64,
+        action: StoredTxAction,
+        coins: Coin,
+        memo: Option<String>,
+        block: &BlockInfo,
+    ) -> Self {
+        Self {
+            id,
+            action,
+            coins,
+            memo,
+            block_time: block.time,
+            block_height: block.height,
+        }
+    }
+
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<RichTx> {
+        Ok(RichTx {
+            id: self.id,
+            action: self.action.into_humanized(api)?,
+            coins: self.coins,
+            memo: self.memo,
+            block_time: self.block_time,
+            block_height: self.block_height,
+        })
+    }
+}
+
+fn increment_tx_count<S: Storage>(store: &mut S) -> StdResult<u64> {
+    let mut config = Config::from_storage(store);
+    let id = config.tx_count() + 1;
+    config.set_tx_count(id)?;
+    Ok(id)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn store_transfer<S: Storage>(
     store: &mut S,
     owner: &CanonicalAddr,
@@ -83,10 +220,10 @@ pub fn store_transfer<S: Storage>(
     receiver: &CanonicalAddr,
     amount: Uint128,
     denom: String,
+    memo: Option<String>,
+    block: &BlockInfo,
 ) -> StdResult<()> {
-    let mut config = Config::from_storage(store);
-    let id = config.tx_count() + 1;
-    config.set_tx_count(id)?;
+    let id = increment_tx_count(store)?;
 
     let coins = Coin { denom, amount };
     let tx = StoredTx {
@@ -94,18 +231,109 @@ pub fn store_transfer<S: Storage>(
         from: owner.clone(),
         sender: sender.clone(),
         receiver: receiver.clone(),
-        coins,
+        coins: coins.clone(),
+    };
+    let action = StoredTxAction::Transfer {
+        from: owner.clone(),
+        sender: sender.clone(),
+        recipient: receiver.clone(),
     };
+    let rich_tx = StoredRichTx::new(id, action, coins, memo, block);
 
     if owner != sender {
         append_tx(store, tx.clone(), &owner)?;
+        append_rich_tx(store, &rich_tx, &owner)?;
     }
     append_tx(store, tx.clone(), &sender)?;
+    append_rich_tx(store, &rich_tx, &sender)?;
     append_tx(store, tx, &receiver)?;
+    append_rich_tx(store, &rich_tx, &receiver)?;
 
     Ok(())
 }
 
+pub fn store_mint<S: Storage>(
+    store: &mut S,
+    minter: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let action = StoredTxAction::Mint {
+        minter: minter.clone(),
+        recipient: recipient.clone(),
+    };
+    let tx = StoredRichTx::new(id, action, Coin { denom, amount }, memo, block);
+
+    if minter != recipient {
+        append_rich_tx(store, &tx, minter)?;
+    }
+    append_rich_tx(store, &tx, recipient)
+}
+
+pub fn store_burn<S: Storage>(
+    store: &mut S,
+    owner: &CanonicalAddr,
+    burner: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let action = StoredTxAction::Burn {
+        burner: burner.clone(),
+        owner: owner.clone(),
+    };
+    let tx = StoredRichTx::new(id, action, Coin { denom, amount }, memo, block);
+
+    if burner != owner {
+        append_rich_tx(store, &tx, burner)?;
+    }
+    append_rich_tx(store, &tx, owner)
+}
+
+pub fn store_deposit<S: Storage>(
+    store: &mut S,
+    recipient: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let tx = StoredRichTx::new(
+        id,
+        StoredTxAction::Deposit,
+        Coin { denom, amount },
+        None,
+        block,
+    );
+
+    append_rich_tx(store, &tx, recipient)
+}
+
+pub fn store_redeem<S: Storage>(
+    store: &mut S,
+    redeemer: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let tx = StoredRichTx::new(
+        id,
+        StoredTxAction::Redeem,
+        Coin { denom, amount },
+        None,
+        block,
+    );
+
+    append_rich_tx(store, &tx, redeemer)
+}
+
 fn append_tx<S: Storage>(
     store: &mut S,
     tx: StoredTx,
@@ -116,6 +344,17 @@ fn append_tx<S: Storage>(
     store.push(&tx)
 }
 
+fn append_rich_tx<S: Storage>(
+    store: &mut S,
+    tx: &StoredRichTx,
+    for_address: &CanonicalAddr,
+) -> StdResult<()> {
+    let mut store =
+        PrefixedStorage::multilevel(&[PREFIX_TRANSACTIONS, for_address.as_slice()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(tx)
+}
+
 pub fn get_transfers<A: Api, S: ReadonlyStorage>(
     api: &A,
     storage: &S,
@@ -138,7 +377,7 @@ pub fn get_transfers<A: Api, S: ReadonlyStorage>(
     let tx_iter = store
         .iter()
         .rev()
-        .skip((page * page_size) as _)
+        .skip((page as usize).saturating_mul(page_size as usize))
         .take(page_size as _);
     // The `and_then` here flattens the `StdResult<StdResult<Tx>>` to an `StdResult<Tx>`
     let txs: StdResult<Vec<Tx>> = tx_iter
@@ -147,6 +386,36 @@ pub fn get_transfers<A: Api, S: ReadonlyStorage>(
     txs
 }
 
+/// Returns a page of the transaction history of the account, latest first, along with the
+/// total amount of transactions in it
+pub fn get_txs<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    for_address: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<RichTx>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_TRANSACTIONS, for_address.as_slice()],
+        storage,
+    );
+
+    let store = if let Some(result) = AppendStore::<StoredRichTx, _>::attach(&store) {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let txs: StdResult<Vec<RichTx>> = store
+        .iter()
+        .rev()
+        .skip((page as usize).saturating_mul(page_size as usize))
+        .take(page_size as _)
+        .map(|tx| tx.and_then(|tx| tx.into_humanized(api)))
+        .collect();
+    txs.map(|txs| (txs, store.len() as u64))
+}
+
 // Config
 
 #[derive(Serialize, Debug, Deserialize, Clone, PartialEq, JsonSchema)]
@@ -156,8 +425,20 @@ pub struct Constants {
     pub symbol: String,
     pub decimals: u8,
     pub prng_seed: Vec<u8>,
+    // permits name the tokens they apply to, and queries don't know the contract address
+    pub contract_address: HumanAddr,
     // privacy configuration
     pub total_supply_is_public: bool,
+    // is deposit enabled
+    pub deposit_is_enabled: bool,
+    // is redeem enabled
+    pub redeem_is_enabled: bool,
+    // is mint enabled
+    pub mint_is_enabled: bool,
+    // is burn enabled
+    pub burn_is_enabled: bool,
+    // the total supply can never grow above this
+    pub max_supply: Option<u128>,
 }
 
 pub struct ReadonlyConfig<'a, S: ReadonlyStorage> {
@@ -191,6 +472,10 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfig<'a, S> {
         self.as_readonly().minters()
     }
 
+    pub fn observers(&self) -> Vec<Observer> {
+        self.as_readonly().observers()
+    }
+
     pub fn tx_count(&self) -> u64 {
         self.as_readonly().tx_count()
     }
@@ -280,6 +565,33 @@ impl<'a, S: Storage> Config<'a, S> {
         self.as_readonly().minters()
     }
 
+    pub fn set_observers(&mut self, observers: Vec<Observer>) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_OBSERVERS, &observers)
+    }
+
+    /// Adds new observers, failing if any of them is already registered
+    pub fn add_observers(&mut self, observers_to_add: Vec<Observer>) -> StdResult<()> {
+        let mut observers = self.as_readonly().observers();
+        for observer in observers_to_add {
+            if observers.iter().any(|x| x.address == observer.address) {
+                return Err(StdError::generic_err(format!(
+                    "{} is already an observer",
+                    observer.address
+                )));
+            }
+            observers.push(observer);
+        }
+
+        self.set_observers(observers)
+    }
+
+    pub fn remove_observers(&mut self, observers_to_remove: Vec<HumanAddr>) -> StdResult<()> {
+        let mut observers = self.as_readonly().observers();
+        observers.retain(|x| !observers_to_remove.contains(&x.address));
+
+        self.set_observers(observers)
+    }
+
     pub fn tx_count(&self) -> u64 {
         self.as_readonly().tx_count()
     }
@@ -330,6 +642,10 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfigImpl<'a, S> {
         get_bin_data(self.0, KEY_MINTERS).unwrap()
     }
 
+    fn observers(&self) -> Vec<Observer> {
+        get_bin_data(self.0, KEY_OBSERVERS).unwrap_or_default()
+    }
+
     pub fn tx_count(&self) -> u64 {
         get_bin_data(self.0, KEY_TX_COUNT).unwrap_or_default()
     }
@@ -400,6 +716,92 @@ impl<'a, S: ReadonlyStorage> ReadonlyBalancesImpl<'a, S> {
     }
 }
 
+// Checkpoints
+//
+// Init checkpoints the initial balances and supply, and every later change writes another
+// checkpoint, so no account holds a balance without one. Contracts can't be migrated on this
+// chain, so a token instantiated from an earlier version can't gain a history; it has to be
+// redeployed for its balances to be checkpointed
+
+/// The value of a balance or of the total supply at the end of the block at `height`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Checkpoint {
+    height: u64,
+    value: u128,
+}
+
+fn push_checkpoint<S: Storage>(store: &mut S, height: u64, value: u128) -> StdResult<()> {
+    let mut store = AppendStoreMut::<Checkpoint, _>::attach_or_create(store)?;
+    let checkpoint = Checkpoint { height, value };
+
+    // only the last change within a block is kept
+    match store.len().checked_sub(1) {
+        Some(last) if store.get_at(last)?.height == height => store.set_at(last, &checkpoint),
+        _ => store.push(&checkpoint),
+    }
+}
+
+fn find_checkpoint<S: ReadonlyStorage>(store: &S, height: u64) -> StdResult<u128> {
+    let store = if let Some(result) = AppendStore::<Checkpoint, _>::attach(store) {
+        result?
+    } else {
+        return Ok(0);
+    };
+
+    // binary search for the first checkpoint after `height`, the one before it is the value
+    // at `height`
+    let (mut low, mut high) = (0, store.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if store.get_at(mid)?.height <= height {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    match low.checked_sub(1) {
+        Some(pos) => Ok(store.get_at(pos)?.value),
+        None => Ok(0),
+    }
+}
+
+/// Records the current balance of `account` as its balance at `height`
+pub fn write_balance_checkpoint<S: Storage>(
+    store: &mut S,
+    account: &CanonicalAddr,
+    height: u64,
+) -> StdResult<()> {
+    let balance = ReadonlyBalances::from_storage(&*store).account_amount(account);
+    let mut store =
+        PrefixedStorage::multilevel(&[PREFIX_BALANCE_CHECKPOINTS, account.as_slice()], store);
+    push_checkpoint(&mut store, height, balance)
+}
+
+pub fn read_balance_at<S: ReadonlyStorage>(
+    store: &S,
+    account: &CanonicalAddr,
+    height: u64,
+) -> StdResult<u128> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_BALANCE_CHECKPOINTS, account.as_slice()],
+        store,
+    );
+    find_checkpoint(&store, height)
+}
+
+/// Records the current total supply as the total supply at `height`
+pub fn write_supply_checkpoint<S: Storage>(store: &mut S, height: u64) -> StdResult<()> {
+    let total_supply = ReadonlyConfig::from_storage(&*store).total_supply();
+    let mut store = PrefixedStorage::new(PREFIX_SUPPLY_CHECKPOINTS, store);
+    push_checkpoint(&mut store, height, total_supply)
+}
+
+pub fn read_total_supply_at<S: ReadonlyStorage>(store: &S, height: u64) -> StdResult<u128> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_SUPPLY_CHECKPOINTS, store);
+    find_checkpoint(&store, height)
+}
+
 // Allowances
 
 #[derive(Serialize, Debug, Deserialize, Clone, PartialEq, Default, JsonSchema)]
@@ -430,7 +832,98 @@ pub fn write_allowance<S: Storage>(
         PrefixedStorage::multilevel(&[PREFIX_ALLOWANCES, owner.as_slice()], store);
     let mut owner_store = TypedStoreMut::attach(&mut owner_store);
 
-    owner_store.store(spender.as_slice(), &allowance)
+    let is_new = owner_store
+        .may_load(spender.as_slice())
+        .map(|allowance: Option<Allowance>| allowance.is_none())?;
+    owner_store.store(spender.as_slice(), &allowance)?;
+
+    // allowances are never removed, so the first write is the only one that needs indexing
+    if is_new {
+        push_allowance_index(store, PREFIX_ALLOWANCES_GIVEN, owner, spender)?;
+        push_allowance_index(store, PREFIX_ALLOWANCES_RECEIVED, spender, owner)?;
+    }
+
+    Ok(())
+}
+
+fn push_allowance_index<S: Storage>(
+    store: &mut S,
+    prefix: &[u8],
+    account: &CanonicalAddr,
+    other: &CanonicalAddr,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[prefix, account.as_slice()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(other)
+}
+
+fn read_allowance_index<S: ReadonlyStorage>(
+    store: &S,
+    prefix: &[u8],
+    account: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<CanonicalAddr>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[prefix, account.as_slice()], store);
+
+    let store = if let Some(result) = AppendStore::<CanonicalAddr, _>::attach(&store) {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let accounts: StdResult<Vec<CanonicalAddr>> = store
+        .iter()
+        .skip((page as usize).saturating_mul(page_size as usize))
+        .take(page_size as _)
+        .collect();
+    accounts.map(|accounts| (accounts, store.len() as u64))
+}
+
+/// Returns a page of the spenders `owner` gave an allowance to, along with their total count
+pub fn get_spenders<S: ReadonlyStorage>(
+    store: &S,
+    owner: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<CanonicalAddr>, u64)> {
+    read_allowance_index(store, PREFIX_ALLOWANCES_GIVEN, owner, page, page_size)
+}
+
+/// Returns a page of the owners that gave `spender` an allowance, along with their total count
+pub fn get_owners<S: ReadonlyStorage>(
+    store: &S,
+    spender: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<CanonicalAddr>, u64)> {
+    read_allowance_index(store, PREFIX_ALLOWANCES_RECEIVED, spender, page, page_size)
+}
+
+// Minter allowances
+
+pub fn read_minter_allowance<S: ReadonlyStorage>(
+    store: &S,
+    minter: &CanonicalAddr,
+) -> StdResult<Option<u128>> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_MINTER_ALLOWANCES, store);
+    store
+        .get(minter.as_slice())
+        .map(|bytes| slice_to_u128(&bytes))
+        .transpose()
+}
+
+/// Stores how much `minter` may still mint, removing the limit when `allowance` is `None`
+pub fn write_minter_allowance<S: Storage>(
+    store: &mut S,
+    minter: &CanonicalAddr,
+    allowance: Option<u128>,
+) {
+    let mut store = PrefixedStorage::new(PREFIX_MINTER_ALLOWANCES, store);
+    match allowance {
+        Some(allowance) => store.set(minter.as_slice(), &allowance.to_be_bytes()),
+        None => store.remove(minter.as_slice()),
+    }
 }
 
 // Viewing Keys
@@ -445,6 +938,40 @@ pub fn read_viewing_key<S: Storage>(store: &S, owner: &CanonicalAddr) -> Option<
     balance_store.get(owner.as_slice())
 }
 
+// Permits
+
+pub fn revoke_permit<S: Storage>(store: &mut S, account: &CanonicalAddr, permit_name: &str) {
+    let mut store =
+        PrefixedStorage::multilevel(&[PREFIX_REVOKED_PERMITS, account.as_slice()], store);
+    store.set(permit_name.as_bytes(), &[]);
+}
+
+pub fn is_permit_revoked<S: ReadonlyStorage>(
+    store: &S,
+    account: &CanonicalAddr,
+    permit_name: &str,
+) -> bool {
+    let store =
+        ReadonlyPrefixedStorage::multilevel(&[PREFIX_REVOKED_PERMITS, account.as_slice()], store);
+    store.get(permit_name.as_bytes()).is_some()
+}
+
+// Observed accounts
+
+pub fn set_observed_account<S: Storage>(store: &mut S, account: &CanonicalAddr, observed: bool) {
+    let mut store = PrefixedStorage::new(PREFIX_OBSERVED_ACCOUNTS, store);
+    if observed {
+        store.set(account.as_slice(), &[1]);
+    } else {
+        store.remove(account.as_slice());
+    }
+}
+
+pub fn is_observed_account<S: ReadonlyStorage>(store: &S, account: &CanonicalAddr) -> bool {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_OBSERVED_ACCOUNTS, store);
+    store.get(account.as_slice()).is_some()
+}
+
 // Receiver Interface
 
 pub fn get_receiver_hash<S: ReadonlyStorage>(
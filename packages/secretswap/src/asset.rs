@@ -1,8 +1,8 @@
 use std::fmt;
 
 use cosmwasm_std::{
-    to_binary, Api, BankMsg, CanonicalAddr, Coin, CosmosMsg, Env, Extern, HumanAddr, Querier,
-    StdError, StdResult, Storage, Uint128, WasmMsg,
+    to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Env, Extern, HumanAddr,
+    Querier, StdError, StdResult, Storage, Uint128, WasmMsg,
 };
 use schemars::JsonSchema;
 use secret_toolkit::snip20::HandleMsg;
@@ -72,6 +72,18 @@ impl Asset {
         deps: &Extern<S, A, Q>,
         sender: HumanAddr,
         recipient: HumanAddr,
+    ) -> StdResult<CosmosMsg> {
+        self.into_msg_with_callback(deps, sender, recipient, None)
+    }
+
+    /// Like `into_msg`, but tokens are sent along with `callback`, which the recipient
+    /// gets as the msg of its Receive. Native coins can't carry it
+    pub fn into_msg_with_callback<S: Storage, A: Api, Q: Querier>(
+        self,
+        deps: &Extern<S, A, Q>,
+        sender: HumanAddr,
+        recipient: HumanAddr,
+        callback: Option<Binary>,
     ) -> StdResult<CosmosMsg> {
         let amount = self.amount;
 
@@ -89,7 +101,7 @@ impl Asset {
                     recipient,
                     amount,
                     padding: None,
-                    msg: None,
+                    msg: callback,
                 })?,
                 send: vec![],
             })),
@@ -339,6 +351,14 @@ impl AssetInfoRaw {
     }
 }
 
+/// SNIP-20 token_info, as cached by the factory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PairInfo {
@@ -349,6 +369,9 @@ pub struct PairInfo {
     pub asset0_volume: Uint128,
     pub asset1_volume: Uint128,
     pub factory: Factory,
+    /// Only filled in by the factory, `None` for native tokens
+    #[serde(default)]
+    pub asset_metadata: [Option<TokenMetadata>; 2],
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -378,6 +401,7 @@ impl PairInfoRaw {
             asset0_volume: self.asset0_volume.clone(),
             asset1_volume: self.asset1_volume.clone(),
             factory: self.factory.clone(),
+            asset_metadata: Default::default(),
         })
     }
 
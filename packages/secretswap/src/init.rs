@@ -40,6 +40,9 @@ pub struct PairInitMsg {
     /// Hook for post initalization
     pub init_hook: Option<InitHook>,
     pub prng_seed: Binary,
+    /// Asset symbols used to name the LP token, falls back to the asset addresses
+    #[serde(default)]
+    pub asset_symbols: Option<[String; 2]>,
 }
 
 /// TokenContract InitMsg
@@ -53,6 +56,14 @@ pub struct TokenInitMsg {
     pub prng_seed: Binary,
     pub init_hook: Option<InitHook>,
     pub config: Option<InitConfig>,
+    pub mint: Option<MintConfig>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct MintConfig {
+    /// Max supply of the token, which can't be changed after init
+    pub cap: Option<Uint128>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
@@ -61,12 +72,19 @@ pub struct InitConfig {
     /// Indicates whether the total supply is public or should be kept secret.
     /// default: False
     pub public_total_supply: Option<bool>,
+    /// Indicates whether mint functionality should be enabled
+    /// default: True
+    pub enable_mint: Option<bool>,
+    /// Indicates whether burn functionality should be enabled
+    /// default: True
+    pub enable_burn: Option<bool>,
 }
 
 impl TokenInitMsg {
-    // pub fn get_cap(&self) -> Option<Uint128> {
-    //     self.mint.as_ref().and_then(|v| v.cap)
-    // }
+    pub fn get_cap(&self) -> Option<Uint128> {
+        self.mint.as_ref().and_then(|v| v.cap)
+    }
+
     pub fn new(
         name: String,
         admin: HumanAddr,
@@ -85,7 +103,10 @@ impl TokenInitMsg {
             init_hook: Some(init_hook),
             config: Some(InitConfig {
                 public_total_supply: Some(true),
+                enable_mint: Some(true),
+                enable_burn: Some(true),
             }),
+            mint: None,
         }
     }
     pub fn validate(&self) -> StdResult<()> {
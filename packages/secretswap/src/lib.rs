@@ -1,13 +1,16 @@
-pub use crate::asset::{Asset, AssetInfo, AssetInfoRaw, AssetRaw, Factory, PairInfo, PairInfoRaw};
+pub use crate::asset::{
+    Asset, AssetInfo, AssetInfoRaw, AssetRaw, Factory, PairInfo, PairInfoRaw, TokenMetadata,
+};
 pub use crate::hook::InitHook;
 pub use crate::init::{Balance, PairInitMsg, TokenInitMsg};
 pub use crate::msg::{
-    FactoryHandleMsg, FactoryQueryMsg, PairCw20HookMsg, PairHandleMsg, PairQueryMsg,
+    FactoryHandleMsg, FactoryPairsResponse, FactoryQueryMsg, PairCw20HookMsg, PairHandleMsg,
+    PairQueryMsg, ReverseSimulationResponse, SimulationResponse,
 };
 pub use crate::pair_settings::{Fee, PairSettings, SwapDataEndpoint, SwapDataEndpointMsg};
 pub use crate::querier::{
-    query_all_balances, query_balance, query_pair_info, query_supply, query_token_balance,
-    reverse_simulate, simulate,
+    query_all_balances, query_balance, query_pair_info, query_pairs, query_pairs_with_asset,
+    query_supply, query_token_balance, reverse_simulate, simulate,
 };
 
 mod asset;
@@ -10,7 +10,7 @@ use cosmwasm_storage::to_length_prefixed;
 use secret_toolkit::snip20::{TokenInfo, TokenInfoResponse};
 
 use crate::asset::PairInfo;
-use crate::msg::FactoryQueryMsg;
+use crate::msg::{FactoryPairsResponse, FactoryQueryMsg};
 use serde::de::DeserializeOwned;
 
 /// mock_dependencies is a drop-in replacement for cosmwasm_std::testing::mock_dependencies
@@ -132,6 +132,14 @@ impl WasmMockQuerier {
                         }),
                     }
                 }
+                FactoryQueryMsg::Pairs { .. } => Ok(to_binary(&FactoryPairsResponse {
+                    pairs: self
+                        .terraswap_factory_querier
+                        .pairs
+                        .values()
+                        .cloned()
+                        .collect(),
+                })),
             },
             QueryRequest::Wasm(WasmQuery::Raw {
                 contract_addr,
@@ -2,7 +2,7 @@ use cosmwasm_std::{Decimal, HumanAddr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{Asset, AssetInfo, InitHook};
+use crate::{Asset, AssetInfo, InitHook, PairInfo};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -14,6 +14,11 @@ pub enum FactoryHandleMsg {
         /// Init hook for after works
         init_hook: Option<InitHook>,
     },
+    /// ReportSwapVolume is invoked by a registered pair contract after every swap
+    ReportSwapVolume {
+        asset_infos: [AssetInfo; 2],
+        offer_asset: Asset,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -48,7 +53,25 @@ pub enum PairCw20HookMsg {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum FactoryQueryMsg {
-    Pair { asset_infos: [AssetInfo; 2] },
+    Pair {
+        asset_infos: [AssetInfo; 2],
+    },
+    Pairs {
+        start_after: Option<[AssetInfo; 2]>,
+        limit: Option<u32>,
+    },
+    PairsWithAsset {
+        asset_info: AssetInfo,
+        start_after: Option<[AssetInfo; 2]>,
+        limit: Option<u32>,
+    },
+}
+
+/// FactoryPairsResponse mirrors the factory's response to `FactoryQueryMsg::Pairs`
+/// and `FactoryQueryMsg::PairsWithAsset`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FactoryPairsResponse {
+    pub pairs: Vec<PairInfo>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
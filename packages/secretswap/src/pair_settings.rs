@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 pub struct SwapDataEndpoint {
     pub address: HumanAddr,
     pub code_hash: String,
+    /// Pair contracts this endpoint is subscribed to. `None` subscribes to all pairs
+    pub pairs: Option<Vec<HumanAddr>>,
 }
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Fee {
@@ -16,10 +18,18 @@ pub struct Fee {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PairSettings {
     pub swap_fee: Fee,
-    pub swap_data_endpoint: Option<SwapDataEndpoint>,
+    #[serde(default)]
+    pub swap_data_endpoints: Vec<SwapDataEndpoint>,
 }
 
 impl SwapDataEndpoint {
+    pub fn is_subscribed(&self, pair: &HumanAddr) -> bool {
+        match &self.pairs {
+            Some(pairs) => pairs.contains(pair),
+            None => true,
+        }
+    }
+
     pub fn into_msg(
         self,
         asset_in: Asset,
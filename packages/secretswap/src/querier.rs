@@ -5,7 +5,10 @@ use cosmwasm_std::{
 use secret_toolkit::snip20::{balance_query, token_info_query};
 
 use crate::asset::{Asset, AssetInfo, PairInfo};
-use crate::msg::{FactoryQueryMsg, PairQueryMsg, ReverseSimulationResponse, SimulationResponse};
+use crate::msg::{
+    FactoryPairsResponse, FactoryQueryMsg, PairQueryMsg, ReverseSimulationResponse,
+    SimulationResponse,
+};
 
 const BLOCK_SIZE: usize = 256;
 
@@ -99,6 +102,44 @@ pub fn query_pair_info<S: Storage, A: Api, Q: Querier>(
     }))
 }
 
+pub fn query_pairs<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    factory_contract: &HumanAddr,
+    factory_contract_hash: &String,
+    start_after: Option<[AssetInfo; 2]>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PairInfo>> {
+    let res: FactoryPairsResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: factory_contract.clone(),
+        callback_code_hash: factory_contract_hash.clone(),
+        msg: to_binary(&FactoryQueryMsg::Pairs { start_after, limit })?,
+    }))?;
+
+    Ok(res.pairs)
+}
+
+/// Pages through the factory's pairs that have `asset_info` as one of their assets
+pub fn query_pairs_with_asset<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    factory_contract: &HumanAddr,
+    factory_contract_hash: &String,
+    asset_info: &AssetInfo,
+    start_after: Option<[AssetInfo; 2]>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PairInfo>> {
+    let res: FactoryPairsResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: factory_contract.clone(),
+        callback_code_hash: factory_contract_hash.clone(),
+        msg: to_binary(&FactoryQueryMsg::PairsWithAsset {
+            asset_info: asset_info.clone(),
+            start_after,
+            limit,
+        })?,
+    }))?;
+
+    Ok(res.pairs)
+}
+
 pub fn simulate<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     pair_contract: &HumanAddr,
@@ -281,6 +281,7 @@ fn query_terraswap_pair_contract() {
             contract_addr: HumanAddr::from("pair0000"),
             liquidity_token: HumanAddr::from("liquidity0000"),
             token_code_hash: "".to_string(),
+            asset_metadata: [None, None],
         },
     )]);
 
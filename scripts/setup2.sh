@@ -157,7 +157,7 @@ echo "SCRT/sSCRT Pair contract address: '$pair_contract_sscrt_scrt'"
 secretcli tx compute execute $(echo "$scrt_addr" | tr -d '"') '{"increase_allowance": {"spender": '$pair_contract_sscrt_scrt', "amount": "5000000000"}}' -b block -y --from $deployer_name
 secretcli tx compute execute $(echo "$pair_contract_sscrt_scrt" | tr -d '"') '{"provide_liquidity": {"assets": [{"info": {"native_token": {"denom": "uscrt"}}, "amount": "5000000000"}, {"info": {"token": {"contract_addr": '$scrt_addr', "token_code_hash": '$token_code_hash', "viewing_key": ""}}, "amount": "5000000000"}]}}' --from $deployer_name --amount 5000000000uscrt -y --gas 1500000 -b block
 
-secretcli tx compute execute $(echo "$factory_contract" | tr -d '"') '{"update_config": {"swap_data_endpoint": {"address":'$dummy_contract', "code_hash":'$dummy_code_hash'}}}' -b block -y --from $deployer_name
+secretcli tx compute execute $(echo "$factory_contract" | tr -d '"') '{"add_swap_data_endpoint": {"endpoint": {"address":'$dummy_contract', "code_hash":'$dummy_code_hash'}}}' -b block -y --from $deployer_name
 
 secretcli tx send a secret1x6my6xxxkladvsupcka7k092m50rdw8pk8dpq9 100000000uscrt -y -b block
 secretcli tx compute execute $(echo "$eth_addr" | tr -d '"') '{"transfer":{"recipient":"secret1x6my6xxxkladvsupcka7k092m50rdw8pk8dpq9","amount":"1000000000000000000000"}}' --from a -y -b block